@@ -0,0 +1,159 @@
+//! Opt-in debug log of outbound AI requests, written to `$APP_DATA/logs/ai.log`.
+//!
+//! 只记录请求摘要（Provider、地址、模型、token、耗时、结果），从不记录消息内容或 API Key。
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::ai_provider::{self, AiChatResult};
+use crate::app_prefs;
+
+const LOG_DIR_NAME: &str = "logs";
+const LOG_FILE_NAME: &str = "ai.log";
+// 超过该大小时轮转为 ai.log.1，只保留一份旧日志。
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+pub const MAX_TAIL_LINES: usize = 1000;
+
+// 串行化写入与轮转，避免并发请求交错写出半行。
+static LOG_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// 单次 AI 请求的日志记录。
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiLogEntry<'a> {
+    pub timestamp: String,
+    pub provider_id: &'a str,
+    pub operation: &'a str,
+    pub endpoint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completion_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_tokens: Option<u32>,
+}
+
+impl<'a> AiLogEntry<'a> {
+    /// 由请求结果构建日志记录；`Ok(None)` 表示成功但没有 usage（如模型列表），错误信息会再次脱敏。
+    pub fn new(
+        provider_id: &'a str,
+        operation: &'a str,
+        api_base: &str,
+        model: Option<&str>,
+        started: std::time::Instant,
+        outcome: Result<Option<&AiChatResult>, &str>,
+    ) -> Self {
+        let (status, error, usage) = match outcome {
+            Ok(result) => ("ok", None, result),
+            Err(err) => ("error", Some(ai_provider::redact(err)), None),
+        };
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            provider_id,
+            operation,
+            endpoint: ai_provider::sanitize_endpoint(api_base),
+            model: usage
+                .and_then(|result| result.model.clone())
+                .or_else(|| model.map(str::to_string)),
+            status,
+            error,
+            latency_ms: started.elapsed().as_millis(),
+            prompt_tokens: usage.and_then(|result| result.prompt_tokens),
+            completion_tokens: usage.and_then(|result| result.completion_tokens),
+            total_tokens: usage.and_then(|result| result.total_tokens),
+        }
+    }
+}
+
+/// 调试日志开启时追加一行 JSON；日志失败只输出到 stderr，不影响请求本身。
+pub fn record(app: &AppHandle, entry: &AiLogEntry<'_>) {
+    if app_prefs::is_ephemeral() || !is_enabled(app) {
+        return;
+    }
+    if let Err(err) = append(app, entry) {
+        eprintln!("[EchoNote] failed to write AI log: {err}");
+    }
+}
+
+/// 读取日志末尾的若干行（含已轮转的旧日志），最多 `MAX_TAIL_LINES` 行。
+pub fn read_tail(app: &AppHandle, lines: usize) -> Result<Vec<String>, String> {
+    let limit = lines.min(MAX_TAIL_LINES);
+    let path = log_path(app)?;
+    let mut collected = Vec::new();
+    for candidate in [path.with_extension("log.1"), path] {
+        if !candidate.exists() {
+            continue;
+        }
+        let content = fs::read_to_string(&candidate)
+            .map_err(|err| format!("failed to read {}: {err}", candidate.display()))?;
+        collected.extend(content.lines().map(str::to_string));
+    }
+    let skip = collected.len().saturating_sub(limit);
+    Ok(collected.split_off(skip))
+}
+
+/// 删除整个日志目录（含轮转文件），返回被删除的目录路径。
+pub fn clear(app: &AppHandle) -> Result<Option<PathBuf>, String> {
+    let _guard = LOG_LOCK
+        .lock()
+        .map_err(|_| "failed to lock AI log".to_string())?;
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|err| format!("failed to resolve app data dir: {err}"))?
+        .join(LOG_DIR_NAME);
+    if !dir.exists() {
+        return Ok(None);
+    }
+    fs::remove_dir_all(&dir)
+        .map_err(|err| format!("failed to remove log dir {}: {err}", dir.display()))?;
+    Ok(Some(dir))
+}
+
+fn is_enabled(app: &AppHandle) -> bool {
+    app_prefs::load_preferences(app).is_ok_and(|prefs| prefs.debug_logging)
+}
+
+fn append(app: &AppHandle, entry: &AiLogEntry<'_>) -> Result<(), String> {
+    let line = serde_json::to_string(entry)
+        .map_err(|err| format!("failed to serialize AI log entry: {err}"))?;
+    let path = log_path(app)?;
+    let _guard = LOG_LOCK
+        .lock()
+        .map_err(|_| "failed to lock AI log".to_string())?;
+
+    if fs::metadata(&path).is_ok_and(|meta| meta.len() >= MAX_LOG_BYTES) {
+        let rotated = path.with_extension("log.1");
+        fs::rename(&path, &rotated)
+            .map_err(|err| format!("failed to rotate {}: {err}", path.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|err| format!("failed to open {}: {err}", path.display()))?;
+    writeln!(file, "{line}").map_err(|err| format!("failed to write {}: {err}", path.display()))
+}
+
+fn log_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|err| format!("failed to resolve app data dir: {err}"))?
+        .join(LOG_DIR_NAME);
+    fs::create_dir_all(&dir)
+        .map_err(|err| format!("failed to prepare log dir {}: {err}", dir.display()))?;
+    Ok(dir.join(LOG_FILE_NAME))
+}
@@ -1,32 +1,91 @@
-//! One-time migration helpers to split legacy `ai_config.json` into preferences + secrets.
+//! One-time migration helpers to split legacy `ai_config.json` into preferences + secrets,
+//! and to keep config files in one canonical location.
 
+use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
 
-use tauri::AppHandle;
+use tauri::{AppHandle, Manager};
 
 use crate::ai_prefs::{merge_legacy_into_preferences, save_preferences};
+use crate::app_prefs::{self, LegacyConfigHandling};
+use crate::models::{ConfigPaths, MigrationReport};
 use crate::security::secrets::{
     legacy_combined_path, persist_store_snapshot, read_legacy_combined, secrets_path, SecretSlot,
 };
+use crate::storage::StorageLayout;
 
 pub fn migrate_if_needed(app: &AppHandle) -> Result<(), String> {
+    let report = run_migration(app, false)?;
+    // 没有旧配置属于常态；选择保留旧文件时每次启动都会跳过，同样不提示。
+    let keeps_legacy = app_prefs::legacy_config_handling(app) == LegacyConfigHandling::Keep;
+    if let Some(reason) = report
+        .skipped_reason
+        .filter(|reason| reason != NO_LEGACY_CONFIG && !keeps_legacy)
+    {
+        eprintln!("[EchoNote] AI config migration skipped: {reason}");
+    }
+    Ok(())
+}
+
+const NO_LEGACY_CONFIG: &str = "no legacy ai_config.json found";
+
+/// 将旧版 `ai_config.json` 拆分为偏好与密钥，返回迁移内容或跳过原因。
+///
+/// 新结构已存在时默认跳过以免覆盖最新配置；`force` 为 true 时先把现有文件备份为
+/// `*.pre-migration.bak` 再迁移。成功后按偏好重命名为 `.bak`、删除或保留旧文件。
+pub fn run_migration(app: &AppHandle, force: bool) -> Result<MigrationReport, String> {
     let Some(legacy_store) = read_legacy_combined(app)? else {
-        return Ok(());
+        return Ok(MigrationReport {
+            skipped_reason: Some(NO_LEGACY_CONFIG.to_string()),
+            ..MigrationReport::default()
+        });
     };
 
     let secret_path = secrets_path(app)?;
     let prefs_path = crate::ai_prefs::preferences_path(app)?;
+    let existing: Vec<&Path> = [secret_path.as_path(), prefs_path.as_path()]
+        .into_iter()
+        .filter(|path| path.exists())
+        .collect();
 
     // 如果新结构已存在，避免覆盖用户的最新配置。
-    if secret_path.exists() || prefs_path.exists() {
-        return Ok(());
+    let mut backups = Vec::new();
+    if !existing.is_empty() {
+        if !force {
+            return Ok(MigrationReport {
+                skipped_reason: Some(format!(
+                    "new configuration files already exist: {}",
+                    existing
+                        .iter()
+                        .map(|path| display(path))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+                ..MigrationReport::default()
+            });
+        }
+        for path in existing {
+            let backup = path.with_extension("pre-migration.bak");
+            fs::copy(path, &backup).map_err(|err| {
+                format!(
+                    "failed to back up {} to {}: {err}",
+                    path.display(),
+                    backup.display()
+                )
+            })?;
+            backups.push(display(&backup));
+        }
     }
 
+    let mut providers: Vec<String> = legacy_store.keys().cloned().collect();
+    providers.sort();
+
     let mut prefs = crate::ai_prefs::load_preferences(app)?;
     merge_legacy_into_preferences(&mut prefs, legacy_store.clone());
     save_preferences(app, &prefs)?;
 
-    let secret_snapshot = legacy_store
+    let secret_snapshot: HashMap<String, SecretSlot> = legacy_store
         .into_iter()
         .filter_map(|(provider_id, slot)| {
             if slot.salt.is_none() && slot.nonce.is_none() && slot.ciphertext.is_none() {
@@ -42,11 +101,144 @@ pub fn migrate_if_needed(app: &AppHandle) -> Result<(), String> {
             ))
         })
         .collect();
+    let mut keys: Vec<String> = secret_snapshot.keys().cloned().collect();
+    keys.sort();
     persist_store_snapshot(app, &secret_snapshot)?;
 
     let legacy_path = legacy_combined_path(app)?;
-    let backup_path = legacy_path.with_extension("bak");
-    let _ = fs::rename(legacy_path, backup_path);
+    let handled = match app_prefs::legacy_config_handling(app) {
+        LegacyConfigHandling::Rename => fs::rename(&legacy_path, legacy_path.with_extension("bak")),
+        LegacyConfigHandling::Delete => fs::remove_file(&legacy_path),
+        LegacyConfigHandling::Keep => Ok(()),
+    };
+    if let Err(err) = handled {
+        eprintln!(
+            "[EchoNote] failed to clean up {}: {err}",
+            legacy_path.display()
+        );
+    }
 
-    Ok(())
+    Ok(MigrationReport {
+        migrated: true,
+        providers,
+        keys,
+        backups,
+        skipped_reason: None,
+    })
+}
+
+/// 设置旧版配置迁移后的处理方式，仅影响之后的迁移。
+pub fn set_legacy_config_handling(
+    app: &AppHandle,
+    handling: LegacyConfigHandling,
+) -> Result<(), String> {
+    let mut prefs = app_prefs::load_preferences(app)?;
+    prefs.legacy_config_handling = handling;
+    app_prefs::save_preferences(app, &prefs)
+}
+
+/// 启动时检查配置文件是否散落在配置目录与数据目录两处，并迁移到规范位置。
+///
+/// 偏好文件以配置目录为准，密钥以数据目录为准；系统更新后目录解析结果变化时，
+/// 文件可能仍留在另一处。规范位置缺失而另一处存在时移动过去，两处都存在时保留
+/// 规范位置的文件并输出警告。返回已迁移文件的说明。
+pub fn reconcile_config_locations(app: &AppHandle) -> Result<Vec<String>, String> {
+    if app_prefs::is_ephemeral() {
+        return Ok(Vec::new());
+    }
+    let resolver = app.path();
+    let (Ok(config_dir), Ok(data_dir)) = (resolver.app_config_dir(), resolver.app_data_dir())
+    else {
+        return Ok(Vec::new());
+    };
+    if config_dir == data_dir {
+        return Ok(Vec::new());
+    }
+
+    let mut moved = Vec::new();
+    for canonical in [
+        app_prefs::preferences_path(app)?,
+        crate::ai_prefs::preferences_path(app)?,
+        secrets_path(app)?,
+    ] {
+        let Some(name) = canonical.file_name() else {
+            continue;
+        };
+        let stray = if canonical.starts_with(&config_dir) {
+            data_dir.join(name)
+        } else {
+            config_dir.join(name)
+        };
+        if let Some(note) = move_stray_file(&stray, &canonical)? {
+            moved.push(note);
+        }
+    }
+    if !moved.is_empty() {
+        app_prefs::clear_cache();
+    }
+    Ok(moved)
+}
+
+fn move_stray_file(stray: &Path, canonical: &Path) -> Result<Option<String>, String> {
+    if !stray.is_file() {
+        return Ok(None);
+    }
+    if canonical.exists() {
+        eprintln!(
+            "[EchoNote] found {} in both {} and {}; keeping the latter",
+            canonical.file_name().unwrap_or_default().to_string_lossy(),
+            stray.display(),
+            canonical.display()
+        );
+        return Ok(None);
+    }
+    if let Some(dir) = canonical.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|err| format!("failed to create {}: {err}", dir.display()))?;
+    }
+    // 跨卷时 rename 会失败，退回到复制后删除。
+    if fs::rename(stray, canonical).is_err() {
+        fs::copy(stray, canonical).map_err(|err| {
+            format!(
+                "failed to copy {} to {}: {err}",
+                stray.display(),
+                canonical.display()
+            )
+        })?;
+        fs::remove_file(stray)
+            .map_err(|err| format!("failed to remove {}: {err}", stray.display()))?;
+    }
+    Ok(Some(format!(
+        "moved {} to {}",
+        stray.display(),
+        canonical.display()
+    )))
+}
+
+/// 返回当前解析出的全部配置相关路径。
+pub fn config_paths(app: &AppHandle) -> ConfigPaths {
+    let resolver = app.path();
+    let config_dir = resolver.app_config_dir().ok();
+    let data_dir = resolver.app_data_dir().ok();
+    ConfigPaths {
+        same_dir: config_dir.is_some() && config_dir == data_dir,
+        config_dir: config_dir.as_deref().map(display),
+        data_dir: data_dir.as_deref().map(display),
+        app_preferences: app_prefs::preferences_path(app)
+            .ok()
+            .as_deref()
+            .map(display),
+        ai_preferences: crate::ai_prefs::preferences_path(app)
+            .ok()
+            .as_deref()
+            .map(display),
+        secrets: secrets_path(app).ok().as_deref().map(display),
+        storage_root: StorageLayout::prepare(app)
+            .ok()
+            .map(|layout| display(layout.root())),
+    }
+}
+
+fn display(path: &Path) -> String {
+    path.display().to_string()
 }
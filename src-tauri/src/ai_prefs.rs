@@ -3,18 +3,52 @@
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager};
 
+use crate::ai_provider::{
+    self, ProviderCapabilities, ProviderScope, RetryPolicy, DEFAULT_FALLBACK_MAX_TOKENS,
+};
+use crate::app_prefs;
+use crate::env_overrides;
 use crate::security::secrets::LegacyStore;
 
 pub const PREFS_FILE_NAME: &str = "ai_preferences.json";
+
+/// 临时模式下保存的偏好只保留在内存中；尚未保存过时仍读取磁盘上的配置。
+static EPHEMERAL_PREFERENCES: Lazy<Mutex<Option<AiPreferences>>> = Lazy::new(|| Mutex::new(None));
+const BUILTIN_PROVIDERS: [&str; 6] = [
+    "noai", "chatgpt", "deepseek", "gemini", "claude", "lmstudio",
+];
 pub const DEFAULT_PROMPT: &str =
     "Analyze the content and date to provide a summary and seasonal emoji.";
 pub const DEFAULT_GREETING_PROMPT: &str = "Craft a short, warm greeting. Reference the current season or holiday if applicable. Add an emoji.";
 pub const DEFAULT_TEMPERATURE: f32 = 1.0;
+/// 摘要需要稳定输出，默认使用较低的温度。
+pub const DEFAULT_SUMMARY_TEMPERATURE: f32 = 0.3;
 pub const DEFAULT_MAX_TOKENS: u32 = 60;
+pub const DEFAULT_GREETING_CONTEXT_DAYS: u32 = 30;
+pub const MAX_GREETING_CONTEXT_DAYS: u32 = 90;
+pub const MAX_POOL_IDLE_TIMEOUT_SECS: u64 = 600;
+pub const DEFAULT_SUMMARY_RETRY_ATTEMPTS: u32 = 3;
+pub const MAX_SUMMARY_RETRY_ATTEMPTS: u32 = 10;
+pub const MAX_SUMMARY_PROMPT_AFFIX_CHARS: usize = 300;
+pub const DEFAULT_MAX_EMOJI_GRAPHEMES: u32 = 1;
+pub const MAX_EMOJI_GRAPHEMES: u32 = 4;
+pub const DEFAULT_TRANSPORT_RETRIES: u32 = 2;
+pub const MAX_TRANSPORT_RETRIES: u32 = 5;
+/// 单次 AI 请求序列化后的体积上限，超出时摘要正文会被截断，其他请求直接报错。
+pub const DEFAULT_MAX_REQUEST_BYTES: u64 = 1024 * 1024;
+pub const MAX_STOP_SEQUENCES: usize = 4;
+pub const MAX_STOP_SEQUENCE_CHARS: usize = 64;
+pub const MIN_MAX_REQUEST_BYTES: u64 = 16 * 1024;
+pub const MAX_MAX_REQUEST_BYTES: u64 = 16 * 1024 * 1024;
+/// 保存后等待多久没有新的保存才开始生成摘要，避免自动保存连续触发多次 AI 调用。
+pub const DEFAULT_SUMMARY_DEBOUNCE_MS: u64 = 2000;
+pub const MAX_SUMMARY_DEBOUNCE_MS: u64 = 30_000;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
@@ -25,6 +59,9 @@ pub struct AiPreferences {
     pub advanced: Option<AdvancedPreferences>,
     #[serde(default)]
     pub api_key_hints: HashMap<String, String>,
+    /// 用户隐藏的内置 Provider：不再出现在 `providers` 中，也不能被选用，取消隐藏后恢复默认设置。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hidden_providers: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -37,7 +74,50 @@ pub struct ProviderPreferences {
     pub prompt: Option<String>,
     pub max_tokens: Option<u32>,
     pub temperature: Option<f32>,
+    /// 仅用于日记摘要的温度，与 `invoke_ai_chat_json` 等其他流程使用的 temperature 分开配置。
+    #[serde(default)]
+    pub summary_temperature: Option<f32>,
     pub greeting_prompt: Option<String>,
+    /// `OpenAI` 组织 ID，作为 `OpenAI-Organization` 请求头发送。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    /// `OpenAI` 项目 ID，作为 `OpenAI-Project` 请求头发送。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// 摘要是否以严格 JSON Schema（structured outputs）请求；未设置时按模型名自动判断。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured_outputs: Option<bool>,
+    /// 模型别名 → 实际模型名，发送请求前解析；界面上仍显示别名。
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub model_aliases: HashMap<String, String>,
+    /// `OpenAI` 兼容接口的对话路径，默认 `/chat/completions`；用于不带 `/v1` 或嵌套路径的网关。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_path: Option<String>,
+    /// `OpenAI` 兼容接口的模型列表路径，默认 `/models`。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub models_path: Option<String>,
+    /// 覆盖内置的能力默认值（如自建网关支持视觉输入），未设置的项沿用默认值。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<CapabilityOverrides>,
+    /// 停止序列，模型输出任一序列时立即结束；仅用于摘要、问候等要求 JSON 输出的请求，
+    /// 避免模型在 JSON 之后继续输出多余内容。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    /// `invoke_ai_chat_json` 请求未携带 system 消息时自动前置的系统提示词（人设等），由后端统一注入。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_system_prompt: Option<String>,
+    /// 问候语专用模型（通常选更小更快的模型），未设置时沿用 `selectedModel`。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub greeting_model: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CapabilityOverrides {
+    pub vision: Option<bool>,
+    pub json_schema: Option<bool>,
+    pub streaming: Option<bool>,
+    pub embeddings: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -47,16 +127,119 @@ pub struct AdvancedPreferences {
     pub greeting_prompt: Option<String>,
     pub temperature: Option<f32>,
     pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub fallback_max_tokens: Option<u32>,
+    /// 问候语回溯的天数，0 表示不附带历史摘要。
+    #[serde(default)]
+    pub greeting_context_days: Option<u32>,
+    #[serde(default)]
+    pub network_preset: Option<NetworkPreset>,
+    #[serde(default)]
+    pub pool_idle_timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub pool_max_idle_per_host: Option<usize>,
+    /// 后台摘要的总尝试次数（含首次），全部失败后回落到本地摘要。
+    #[serde(default)]
+    pub summary_retry_attempts: Option<u32>,
+    /// 问候语上下文仅使用 AI 生成的摘要，跳过本地截断的摘要。
+    #[serde(default)]
+    pub greeting_ai_summaries_only: Option<bool>,
+    /// 插入在摘要 JSON 规则之前的自定义说明，不替换内置规则。
+    #[serde(default)]
+    pub summary_prompt_prefix: Option<String>,
+    /// 追加在摘要 JSON 规则之后的自定义说明（如"使用过去时"）。
+    #[serde(default)]
+    pub summary_prompt_suffix: Option<String>,
+    /// 摘要因 token 上限被截断时，是否加倍上限后自动重试一次（默认开启）。
+    #[serde(default)]
+    pub retry_truncated_summaries: Option<bool>,
+    /// AI 返回的 Emoji 最多允许的字素簇数量（国旗、ZWJ 家庭等组合 Emoji 计为 1 个）。
+    #[serde(default)]
+    pub max_emoji_graphemes: Option<u32>,
+    /// 网络抖动时传输层的额外重试次数（连接失败、超时、连接被重置）。
+    #[serde(default)]
+    pub transport_retries: Option<u32>,
+    /// 是否对 `invoke_ai_chat_json`、摘要等 POST 请求也进行传输层重试（默认仅重试模型列表等 GET）。
+    #[serde(default)]
+    pub retry_post_requests: Option<bool>,
+    /// 单次请求的体积上限（字节），避免超长日记触发 Provider 的 413 或高额计费。
+    #[serde(default)]
+    pub max_request_bytes: Option<u64>,
+    /// 后台摘要的防抖窗口（毫秒），窗口内的新保存会取消并重新计时；0 表示保存后立即生成。
+    #[serde(default)]
+    pub summary_debounce_ms: Option<u64>,
+}
+
+/// 网络连接池预设：`lowPower` 完全关闭 keep-alive，适合移动端省电。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum NetworkPreset {
+    #[default]
+    Default,
+    LowPower,
+}
+
+/// 可分享的 AI 偏好：不含 `apiKeyHints` 等与密钥相关的数据，导入时拒绝未知的顶层字段。
+///
+/// Provider 的请求地址（Base URL、接口路径）与组织 / 项目 ID 在导出和导入时都会被剔除，
+/// 避免分享的文件把 API Key 发往他人的服务器，或泄露账号信息。
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SharedAiPreferences {
+    #[serde(default)]
+    pub active_provider_id: Option<String>,
+    #[serde(default)]
+    pub providers: HashMap<String, ProviderPreferences>,
+    #[serde(default)]
+    pub advanced: Option<AdvancedPreferences>,
+    #[serde(default)]
+    pub hidden_providers: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ProviderContext {
+    pub base_url: String,
+    pub model: String,
+    /// 问候语使用的模型，已解析别名；未单独配置时与 `model` 相同。
+    pub greeting_model: String,
+    pub prompt: String,
+    pub greeting_prompt: String,
+    pub temperature: f32,
+    pub summary_temperature: f32,
+    /// `None` 表示不限制，请求中省略该字段并使用 Provider 默认值。
+    pub max_tokens: Option<u32>,
+    pub fallback_max_tokens: u32,
+    pub greeting_context_days: usize,
+    pub pool_idle_timeout_secs: Option<u64>,
+    pub pool_max_idle_per_host: Option<usize>,
+    pub summary_retry_attempts: u32,
+    pub greeting_ai_summaries_only: bool,
+    pub summary_prompt_prefix: Option<String>,
+    pub summary_prompt_suffix: Option<String>,
+    pub retry_truncated_summaries: bool,
+    pub scope: ProviderScope,
+    pub structured_outputs: bool,
+    pub max_emoji_graphemes: usize,
+    pub retry_policy: RetryPolicy,
+    pub max_request_bytes: u64,
+    pub stop: Vec<String>,
+    pub chat_system_prompt: Option<String>,
+}
+
+/// 供排查配置使用的生效值：Provider 偏好、全局高级设置与内置默认值合并后的结果，不含任何密钥。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveProviderContext {
+    pub provider_id: String,
     pub base_url: String,
     pub model: String,
     pub prompt: String,
     pub greeting_prompt: String,
     pub temperature: f32,
-    pub max_tokens: u32,
+    pub summary_temperature: f32,
+    pub max_tokens: Option<u32>,
+    pub fallback_max_tokens: u32,
+    pub structured_outputs: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -65,7 +248,33 @@ struct WrappedPreferences {
     pub ai_settings: Option<AiPreferences>,
 }
 
+/// 返回 Provider 实际生效的配置，便于排查"为什么用了错误的模型"之类的问题。
+pub fn effective_provider_context(
+    app: &AppHandle,
+    provider_id: &str,
+) -> Result<EffectiveProviderContext, String> {
+    let ctx = resolve_provider_context(app, provider_id)?;
+    Ok(EffectiveProviderContext {
+        provider_id: provider_id.to_string(),
+        base_url: ctx.base_url,
+        model: ctx.model,
+        prompt: ctx.prompt,
+        greeting_prompt: ctx.greeting_prompt,
+        temperature: ctx.temperature,
+        summary_temperature: ctx.summary_temperature,
+        max_tokens: ctx.max_tokens,
+        fallback_max_tokens: ctx.fallback_max_tokens,
+        structured_outputs: ctx.structured_outputs,
+    })
+}
+
 pub fn load_preferences(app: &AppHandle) -> Result<AiPreferences, String> {
+    if app_prefs::is_ephemeral() {
+        let saved = lock_ephemeral_preferences()?.clone();
+        if let Some(prefs) = saved {
+            return Ok(prefs);
+        }
+    }
     let path = preferences_path(app)?;
     if !path.exists() {
         return Ok(default_preferences());
@@ -82,8 +291,13 @@ pub fn load_preferences(app: &AppHandle) -> Result<AiPreferences, String> {
     Ok(sanitize_preferences(parsed.ai_settings.unwrap_or_default()))
 }
 
+/// 保存偏好；临时模式下只更新内存中的副本，不写入磁盘。
 pub fn save_preferences(app: &AppHandle, prefs: &AiPreferences) -> Result<(), String> {
     let sanitized = sanitize_preferences(prefs.clone());
+    if app_prefs::is_ephemeral() {
+        *lock_ephemeral_preferences()? = Some(sanitized);
+        return Ok(());
+    }
     let wrapped = WrappedPreferences {
         ai_settings: Some(sanitized),
     };
@@ -98,6 +312,20 @@ pub fn save_preferences(app: &AppHandle, prefs: &AiPreferences) -> Result<(), St
         .map_err(|err| format!("failed to write preferences {}: {err}", path.display()))
 }
 
+/// 丢弃临时模式下保存在内存中的偏好。
+pub fn clear_ephemeral_preferences() {
+    if let Ok(mut saved) = EPHEMERAL_PREFERENCES.lock() {
+        *saved = None;
+    }
+}
+
+fn lock_ephemeral_preferences(
+) -> Result<std::sync::MutexGuard<'static, Option<AiPreferences>>, String> {
+    EPHEMERAL_PREFERENCES
+        .lock()
+        .map_err(|_| "failed to lock ephemeral AI preferences".to_string())
+}
+
 pub fn persist_model_list(
     app: &AppHandle,
     provider_id: &str,
@@ -120,61 +348,381 @@ pub fn persist_model_list(
     save_preferences(app, &prefs)
 }
 
+/// 导出当前 AI 偏好为格式化 JSON，剔除密钥提示、请求地址与组织 / 项目 ID。
+pub fn export_preferences(app: &AppHandle) -> Result<String, String> {
+    let prefs = load_preferences(app)?;
+    let shared = SharedAiPreferences {
+        active_provider_id: prefs.active_provider_id,
+        providers: prefs
+            .providers
+            .into_iter()
+            .map(|(id, provider)| (id, shareable_provider(provider)))
+            .collect(),
+        advanced: prefs.advanced,
+        hidden_providers: Some(prefs.hidden_providers).filter(|hidden| !hidden.is_empty()),
+    };
+    serde_json::to_string_pretty(&shared)
+        .map_err(|err| format!("failed to serialize AI preferences: {err}"))
+}
+
+/// 将分享的偏好合并到当前设置：仅覆盖导入内容中存在的字段，不读写密钥存储；
+/// 请求地址与组织 / 项目 ID 即使出现在导入内容中也会被忽略，保留本地设置。
+pub fn import_preferences(app: &AppHandle, json: &str) -> Result<AiPreferences, String> {
+    let shared: SharedAiPreferences =
+        serde_json::from_str(json).map_err(|err| format!("invalid AI preferences JSON: {err}"))?;
+
+    let mut prefs = load_preferences(app)?;
+    for (id, incoming) in shared.providers {
+        let id = id.trim().to_string();
+        if id.is_empty() {
+            return Err("provider id must not be empty".to_string());
+        }
+        merge_provider(
+            prefs.providers.entry(id).or_default(),
+            shareable_provider(incoming),
+        );
+    }
+    if let Some(advanced) = shared.advanced {
+        prefs.advanced = Some(advanced);
+    }
+    if let Some(active) = shared.active_provider_id {
+        prefs.active_provider_id = Some(active);
+    }
+    if let Some(hidden) = shared.hidden_providers {
+        prefs.hidden_providers = hidden;
+    }
+
+    save_preferences(app, &prefs)?;
+    load_preferences(app)
+}
+
+/// 去掉不随偏好分享的字段：请求发往何处（Base URL、接口路径）以及账号归属（组织、项目）。
+fn shareable_provider(provider: ProviderPreferences) -> ProviderPreferences {
+    ProviderPreferences {
+        base_url: None,
+        chat_path: None,
+        models_path: None,
+        organization: None,
+        project: None,
+        ..provider
+    }
+}
+
+fn merge_provider(target: &mut ProviderPreferences, incoming: ProviderPreferences) {
+    if incoming.base_url.is_some() {
+        target.base_url = incoming.base_url;
+    }
+    if incoming.selected_model.is_some() {
+        target.selected_model = incoming.selected_model;
+    }
+    if incoming.model_list.is_some() {
+        target.model_list = incoming.model_list;
+    }
+    if incoming.prompt.is_some() {
+        target.prompt = incoming.prompt;
+    }
+    if incoming.max_tokens.is_some() {
+        target.max_tokens = incoming.max_tokens;
+    }
+    if incoming.temperature.is_some() {
+        target.temperature = incoming.temperature;
+    }
+    if incoming.summary_temperature.is_some() {
+        target.summary_temperature = incoming.summary_temperature;
+    }
+    if incoming.greeting_prompt.is_some() {
+        target.greeting_prompt = incoming.greeting_prompt;
+    }
+    if incoming.organization.is_some() {
+        target.organization = incoming.organization;
+    }
+    if incoming.project.is_some() {
+        target.project = incoming.project;
+    }
+    if incoming.structured_outputs.is_some() {
+        target.structured_outputs = incoming.structured_outputs;
+    }
+    target.model_aliases.extend(incoming.model_aliases);
+    if incoming.chat_path.is_some() {
+        target.chat_path = incoming.chat_path;
+    }
+    if incoming.models_path.is_some() {
+        target.models_path = incoming.models_path;
+    }
+    if incoming.capabilities.is_some() {
+        target.capabilities = incoming.capabilities;
+    }
+    if !incoming.stop.is_empty() {
+        target.stop = incoming.stop;
+    }
+    if incoming.chat_system_prompt.is_some() {
+        target.chat_system_prompt = incoming.chat_system_prompt;
+    }
+    if incoming.greeting_model.is_some() {
+        target.greeting_model = incoming.greeting_model;
+    }
+}
+
+/// 返回 Provider 的能力：内置默认值叠加偏好中的覆盖项。
+pub fn resolve_capabilities(
+    app: &AppHandle,
+    provider_id: &str,
+) -> Result<ProviderCapabilities, String> {
+    let defaults = ai_provider::capabilities(provider_id);
+    let prefs = load_preferences(app)?;
+    let Some(overrides) = prefs
+        .providers
+        .get(provider_id)
+        .and_then(|provider| provider.capabilities)
+    else {
+        return Ok(defaults);
+    };
+    Ok(ProviderCapabilities {
+        vision: overrides.vision.unwrap_or(defaults.vision),
+        json_schema: overrides.json_schema.unwrap_or(defaults.json_schema),
+        streaming: overrides.streaming.unwrap_or(defaults.streaming),
+        embeddings: overrides.embeddings.unwrap_or(defaults.embeddings),
+    })
+}
+
+/// 设置或删除（`target` 为空）Provider 的模型别名，返回更新后的别名表。
+pub fn set_model_alias(
+    app: &AppHandle,
+    provider_id: &str,
+    alias: &str,
+    target: Option<&str>,
+) -> Result<HashMap<String, String>, String> {
+    let alias = alias.trim();
+    if alias.is_empty() {
+        return Err("model alias must not be empty".to_string());
+    }
+    let mut prefs = load_preferences(app)?;
+    let provider = prefs
+        .providers
+        .get_mut(provider_id)
+        .ok_or_else(|| format!("unknown provider: {provider_id}"))?;
+    match target.map(str::trim).filter(|value| !value.is_empty()) {
+        Some(target) => {
+            provider
+                .model_aliases
+                .insert(alias.to_string(), target.to_string());
+        }
+        None => {
+            provider.model_aliases.remove(alias);
+        }
+    }
+    let aliases = provider.model_aliases.clone();
+    save_preferences(app, &prefs)?;
+    Ok(aliases)
+}
+
+/// 隐藏或恢复内置 Provider，返回更新后的偏好；`noai` 与自定义 Provider 不能隐藏。
+pub fn set_provider_hidden(
+    app: &AppHandle,
+    provider_id: &str,
+    hidden: bool,
+) -> Result<AiPreferences, String> {
+    let provider_id = provider_id.trim();
+    if !is_hideable_builtin(provider_id) {
+        return Err(format!("provider {provider_id} cannot be hidden"));
+    }
+    let mut prefs = load_preferences(app)?;
+    prefs.hidden_providers.retain(|id| id != provider_id);
+    if hidden {
+        prefs.hidden_providers.push(provider_id.to_string());
+    }
+    save_preferences(app, &prefs)?;
+    load_preferences(app)
+}
+
+fn is_hideable_builtin(provider_id: &str) -> bool {
+    provider_id != "noai" && BUILTIN_PROVIDERS.contains(&provider_id)
+}
+
+/// 清除缓存的模型列表；`provider_id` 为 None 时清除全部 Provider，返回被清除的数量。
+pub fn clear_model_list(app: &AppHandle, provider_id: Option<&str>) -> Result<usize, String> {
+    let mut prefs = load_preferences(app)?;
+    let mut cleared = 0;
+    for (id, provider) in &mut prefs.providers {
+        if provider_id.is_some_and(|target| target != id) {
+            continue;
+        }
+        if provider.model_list.take().is_some() {
+            cleared += 1;
+        }
+    }
+    if cleared > 0 {
+        save_preferences(app, &prefs)?;
+    }
+    Ok(cleared)
+}
+
 pub fn resolve_provider_context(
     app: &AppHandle,
     provider_id: &str,
 ) -> Result<ProviderContext, String> {
-    let prefs = sanitize_preferences(load_preferences(app)?);
+    context_from_preferences(load_preferences(app)?, provider_id)
+}
+
+/// 由已加载的偏好解析 Provider 的请求上下文，不读取磁盘。
+pub fn context_from_preferences(
+    prefs: AiPreferences,
+    provider_id: &str,
+) -> Result<ProviderContext, String> {
+    let prefs = sanitize_preferences(prefs);
+    if prefs.hidden_providers.iter().any(|id| id == provider_id) {
+        return Err(format!("provider {provider_id} is hidden"));
+    }
     let advanced = sanitize_advanced(prefs.advanced.unwrap_or_default());
     let provider = prefs.providers.get(provider_id);
 
-    let base_url = provider
-        .and_then(|p| sanitize_base_url(p.base_url.clone()))
+    // 优先级：环境变量 > 偏好设置 > 内置默认值；环境变量只在此处读取，从不写回磁盘。
+    let base_url = sanitize_base_url(env_overrides::provider_env_var(provider_id, "BASE_URL"))
+        .or_else(|| provider.and_then(|p| sanitize_base_url(p.base_url.clone())))
         .unwrap_or_else(|| default_api_base_for(provider_id).to_string());
 
-    let model = provider
-        .and_then(|p| p.selected_model.clone())
+    let model = env_overrides::provider_env_var(provider_id, "MODEL")
+        .or_else(|| provider.and_then(|p| p.selected_model.clone()))
         .filter(|m| !m.trim().is_empty())
         .unwrap_or_else(|| default_model_for(provider_id));
+    // 选中的是别名时解析为实际模型名，未配置别名的模型原样使用。
+    let model = provider
+        .and_then(|p| p.model_aliases.get(model.trim()))
+        .cloned()
+        .unwrap_or(model);
+    let greeting_model = resolve_greeting_model(provider, &model);
 
-    let prompt = provider
-        .and_then(|p| p.prompt.clone())
-        .filter(|p| !p.trim().is_empty())
-        .unwrap_or_else(|| {
-            advanced
-                .prompt
-                .clone()
-                .unwrap_or_else(|| DEFAULT_PROMPT.to_string())
-        });
-
-    let greeting_prompt = provider
-        .and_then(|p| p.greeting_prompt.clone())
-        .filter(|p| !p.trim().is_empty())
-        .unwrap_or_else(|| {
-            advanced
-                .greeting_prompt
-                .clone()
-                .unwrap_or_else(|| DEFAULT_GREETING_PROMPT.to_string())
-        });
+    let prompt = resolve_prompt(
+        provider.and_then(|p| p.prompt.as_deref()),
+        advanced.prompt.as_deref(),
+        DEFAULT_PROMPT,
+    );
+    let greeting_prompt = resolve_prompt(
+        provider.and_then(|p| p.greeting_prompt.as_deref()),
+        advanced.greeting_prompt.as_deref(),
+        DEFAULT_GREETING_PROMPT,
+    );
 
     let temperature = provider
         .and_then(|p| p.temperature)
         .unwrap_or_else(|| advanced.temperature.unwrap_or(DEFAULT_TEMPERATURE));
 
-    let max_tokens = provider
-        .and_then(|p| p.max_tokens)
-        .unwrap_or_else(|| advanced.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS));
+    let summary_temperature = provider
+        .and_then(|p| p.summary_temperature)
+        .unwrap_or(DEFAULT_SUMMARY_TEMPERATURE);
+
+    // 0 表示"不限制"：省略字段，交由 Provider 使用其默认值。
+    let max_tokens = Some(
+        provider
+            .and_then(|p| p.max_tokens)
+            .unwrap_or_else(|| advanced.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS)),
+    )
+    .filter(|v| *v > 0);
+    let fallback_max_tokens = advanced
+        .fallback_max_tokens
+        .unwrap_or(DEFAULT_FALLBACK_MAX_TOKENS);
+
+    // 省电预设下不保留空闲连接，忽略自定义的连接池参数。
+    let (pool_idle_timeout_secs, pool_max_idle_per_host) = match advanced.network_preset {
+        Some(NetworkPreset::LowPower) => (None, Some(0)),
+        _ => (
+            advanced.pool_idle_timeout_secs,
+            advanced.pool_max_idle_per_host,
+        ),
+    };
+
+    // 未显式开关时，优先采用能力覆盖中的 jsonSchema，再按模型名自动判断。
+    let structured_outputs = provider
+        .and_then(|p| {
+            p.structured_outputs
+                .or_else(|| p.capabilities.and_then(|caps| caps.json_schema))
+        })
+        .unwrap_or_else(|| ai_provider::supports_json_schema(provider_id, &model));
 
     Ok(ProviderContext {
         base_url,
         model,
+        greeting_model,
         prompt,
         greeting_prompt,
         temperature,
+        summary_temperature,
         max_tokens,
+        fallback_max_tokens,
+        greeting_context_days: advanced
+            .greeting_context_days
+            .unwrap_or(DEFAULT_GREETING_CONTEXT_DAYS) as usize,
+        pool_idle_timeout_secs,
+        pool_max_idle_per_host,
+        summary_retry_attempts: advanced
+            .summary_retry_attempts
+            .unwrap_or(DEFAULT_SUMMARY_RETRY_ATTEMPTS),
+        greeting_ai_summaries_only: advanced.greeting_ai_summaries_only.unwrap_or(false),
+        summary_prompt_prefix: advanced.summary_prompt_prefix,
+        summary_prompt_suffix: advanced.summary_prompt_suffix,
+        retry_truncated_summaries: advanced.retry_truncated_summaries.unwrap_or(true),
+        scope: ProviderScope {
+            organization: provider.and_then(|p| p.organization.clone()),
+            project: provider.and_then(|p| p.project.clone()),
+            chat_path: provider.and_then(|p| p.chat_path.clone()),
+            models_path: provider.and_then(|p| p.models_path.clone()),
+        },
+        structured_outputs,
+        max_emoji_graphemes: advanced
+            .max_emoji_graphemes
+            .unwrap_or(DEFAULT_MAX_EMOJI_GRAPHEMES) as usize,
+        retry_policy: RetryPolicy {
+            max_retries: advanced
+                .transport_retries
+                .unwrap_or(DEFAULT_TRANSPORT_RETRIES),
+            retry_posts: advanced.retry_post_requests.unwrap_or(false),
+        },
+        max_request_bytes: advanced
+            .max_request_bytes
+            .unwrap_or(DEFAULT_MAX_REQUEST_BYTES),
+        stop: provider.map(|p| p.stop.clone()).unwrap_or_default(),
+        chat_system_prompt: provider.and_then(|p| p.chat_system_prompt.clone()),
     })
 }
 
+/// 问候语模型：未设置时沿用主模型；设置了别名时解析为实际模型名。
+///
+/// 已缓存模型列表且其中没有该模型时同样回退到主模型，避免列表过期后问候语一直请求失败。
+fn resolve_greeting_model(provider: Option<&ProviderPreferences>, model: &str) -> String {
+    let Some((provider, greeting)) = provider.and_then(|p| {
+        p.greeting_model
+            .as_deref()
+            .map(str::trim)
+            .filter(|m| !m.is_empty())
+            .map(|m| (p, m))
+    }) else {
+        return model.to_string();
+    };
+    if let Some(target) = provider.model_aliases.get(greeting) {
+        return target.clone();
+    }
+    let unlisted = provider
+        .model_list
+        .as_ref()
+        .is_some_and(|list| !list.is_empty() && !list.iter().any(|m| m == greeting));
+    if unlisted {
+        eprintln!(
+            "[EchoNote] greeting model {greeting} is not in the cached model list, using {model}"
+        );
+        return model.to_string();
+    }
+    greeting.to_string()
+}
+
+/// Provider 级提示词非空时优先使用，否则回退到全局提示词与内置默认值。
+fn resolve_prompt(provider: Option<&str>, global: Option<&str>, default: &str) -> String {
+    provider
+        .filter(|p| !p.trim().is_empty())
+        .or(global)
+        .unwrap_or(default)
+        .to_string()
+}
+
 pub fn merge_legacy_into_preferences(prefs: &mut AiPreferences, legacy: LegacyStore) {
     for (provider_id, slot) in legacy.into_iter() {
         let provider = prefs.providers.entry(provider_id).or_default();
@@ -192,7 +740,7 @@ pub fn merge_legacy_into_preferences(prefs: &mut AiPreferences, legacy: LegacySt
 
 pub fn default_preferences() -> AiPreferences {
     let mut providers = HashMap::new();
-    for id in ["noai", "chatgpt", "deepseek", "gemini", "claude"] {
+    for id in BUILTIN_PROVIDERS {
         providers.insert(id.to_string(), default_provider_preferences(id));
     }
     AiPreferences {
@@ -203,8 +751,24 @@ pub fn default_preferences() -> AiPreferences {
             greeting_prompt: Some(DEFAULT_GREETING_PROMPT.to_string()),
             temperature: Some(DEFAULT_TEMPERATURE),
             max_tokens: Some(DEFAULT_MAX_TOKENS),
+            fallback_max_tokens: Some(DEFAULT_FALLBACK_MAX_TOKENS),
+            greeting_context_days: Some(DEFAULT_GREETING_CONTEXT_DAYS),
+            network_preset: Some(NetworkPreset::Default),
+            pool_idle_timeout_secs: None,
+            pool_max_idle_per_host: None,
+            summary_retry_attempts: Some(DEFAULT_SUMMARY_RETRY_ATTEMPTS),
+            greeting_ai_summaries_only: Some(false),
+            summary_prompt_prefix: None,
+            summary_prompt_suffix: None,
+            retry_truncated_summaries: Some(true),
+            max_emoji_graphemes: Some(DEFAULT_MAX_EMOJI_GRAPHEMES),
+            transport_retries: Some(DEFAULT_TRANSPORT_RETRIES),
+            retry_post_requests: Some(false),
+            max_request_bytes: Some(DEFAULT_MAX_REQUEST_BYTES),
+            summary_debounce_ms: Some(DEFAULT_SUMMARY_DEBOUNCE_MS),
         }),
         api_key_hints: HashMap::new(),
+        hidden_providers: Vec::new(),
     }
 }
 
@@ -222,15 +786,23 @@ pub fn default_api_base_for(provider_id: &str) -> &'static str {
         "deepseek" => "https://api.deepseek.com",
         "gemini" => "https://generativelanguage.googleapis.com",
         "claude" => "https://api.anthropic.com",
+        "lmstudio" => "http://localhost:1234/v1",
         _ => "https://api.openai.com/v1",
     }
 }
 
+/// 本地 `OpenAI` 兼容服务（LM Studio、`llama.cpp`）无需 API Key。
+pub fn requires_api_key(provider_id: &str) -> bool {
+    provider_id != "lmstudio"
+}
+
 pub fn default_model_for(provider_id: &str) -> String {
     match provider_id {
         "deepseek" => "deepseek-chat".to_string(),
         "gemini" => "gemini-flash-lite-latest".to_string(),
         "claude" => "claude-haiku-4-5".to_string(),
+        // 本地服务通常只加载一个模型，并忽略或宽松匹配该字段。
+        "lmstudio" => "local-model".to_string(),
         "noai" => String::new(),
         _ => "gpt-5.1".to_string(),
     }
@@ -239,7 +811,15 @@ pub fn default_model_for(provider_id: &str) -> String {
 fn sanitize_preferences(mut prefs: AiPreferences) -> AiPreferences {
     let mut providers: HashMap<String, ProviderPreferences> = HashMap::new();
 
-    for builtin in ["noai", "chatgpt", "deepseek", "gemini", "claude"] {
+    // 隐藏列表只保留可隐藏的内置 Provider，去重并保持顺序。
+    // 被隐藏的 Provider 仍保留已保存的设置，取消隐藏后原样恢复。
+    let mut hidden_providers: Vec<String> = Vec::new();
+    for id in prefs.hidden_providers.iter().map(|id| id.trim()) {
+        if is_hideable_builtin(id) && !hidden_providers.iter().any(|seen| seen == id) {
+            hidden_providers.push(id.to_string());
+        }
+    }
+    for builtin in visible_builtins(&hidden_providers) {
         providers.insert(
             builtin.to_string(),
             sanitize_provider(
@@ -259,14 +839,22 @@ fn sanitize_preferences(mut prefs: AiPreferences) -> AiPreferences {
     AiPreferences {
         active_provider_id: prefs
             .active_provider_id
-            .filter(|id| providers.contains_key(id))
+            .filter(|id| providers.contains_key(id) && !hidden_providers.contains(id))
             .or_else(|| Some("noai".to_string())),
         providers,
         advanced: Some(sanitize_advanced(prefs.advanced.unwrap_or_default())),
         api_key_hints: prefs.api_key_hints,
+        hidden_providers,
     }
 }
 
+/// 未被隐藏的内置 Provider。
+fn visible_builtins(hidden: &[String]) -> impl Iterator<Item = &'static str> + '_ {
+    BUILTIN_PROVIDERS
+        .into_iter()
+        .filter(move |id| !hidden.iter().any(|hidden| hidden == id))
+}
+
 fn sanitize_provider(provider_id: &str, mut provider: ProviderPreferences) -> ProviderPreferences {
     provider.base_url = sanitize_base_url(provider.base_url.take())
         .or_else(|| Some(default_api_base_for(provider_id).to_string()));
@@ -291,10 +879,89 @@ fn sanitize_provider(provider_id: &str, mut provider: ProviderPreferences) -> Pr
         .map(|p| p.trim().to_string())
         .filter(|p| !p.is_empty());
     provider.temperature = provider.temperature.map(|t| t.clamp(0.0, 2.0));
-    provider.max_tokens = provider.max_tokens.filter(|v| *v > 0);
+    provider.summary_temperature = provider.summary_temperature.map(|t| t.clamp(0.0, 2.0));
+    provider.model_aliases = provider
+        .model_aliases
+        .into_iter()
+        .map(|(alias, target)| (alias.trim().to_string(), target.trim().to_string()))
+        .filter(|(alias, target)| !alias.is_empty() && !target.is_empty())
+        .collect();
+    provider.organization = sanitize_header_value(provider.organization);
+    provider.project = sanitize_header_value(provider.project);
+    provider.chat_path = sanitize_endpoint_path(provider.chat_path);
+    provider.models_path = sanitize_endpoint_path(provider.models_path);
+    provider.stop = sanitize_stop_sequences(provider.stop);
+    provider.chat_system_prompt = provider
+        .chat_system_prompt
+        .map(|prompt| prompt.trim().to_string())
+        .filter(|prompt| !prompt.is_empty());
+    provider.greeting_model = provider
+        .greeting_model
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty());
     provider
 }
 
+/// 停止序列保留原样（换行等空白可能正是用户想要的分隔符），只丢弃空串与重复项并限制数量。
+fn sanitize_stop_sequences(stop: Vec<String>) -> Vec<String> {
+    let mut sanitized: Vec<String> = Vec::new();
+    for sequence in stop {
+        if sequence.is_empty() || sanitized.contains(&sequence) {
+            continue;
+        }
+        if sequence.chars().count() > MAX_STOP_SEQUENCE_CHARS {
+            eprintln!("[EchoNote] ignoring stop sequence longer than {MAX_STOP_SEQUENCE_CHARS} characters");
+            continue;
+        }
+        sanitized.push(sequence);
+    }
+    // OpenAI 最多接受 4 个停止序列，超出部分直接截断以保证各 Provider 行为一致。
+    sanitized.truncate(MAX_STOP_SEQUENCES);
+    sanitized
+}
+
+/// 请求头取值只接受可见 ASCII 字符；为空或含其他字符时忽略。
+fn sanitize_header_value(value: Option<String>) -> Option<String> {
+    let trimmed = value?.trim().to_string();
+    if trimmed.is_empty() {
+        return None;
+    }
+    if !trimmed.chars().all(|ch| ch.is_ascii_graphic()) {
+        eprintln!("[EchoNote] ignoring non-ASCII OpenAI organization/project value");
+        return None;
+    }
+    Some(trimmed)
+}
+
+/// 接口路径必须以 `/` 开头且不含空白、查询串或片段；为空或不合法时忽略并使用默认路径。
+///
+/// 路径拼接在 Base URL 之后，因此还拒绝 `//host` 形式的协议相对地址、反斜杠与 `.`/`..`
+/// 段（含百分号编码），避免请求被带离配置的服务地址。
+fn sanitize_endpoint_path(value: Option<String>) -> Option<String> {
+    let trimmed = value?.trim().trim_end_matches('/').to_string();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let valid = trimmed.starts_with('/')
+        && !trimmed.starts_with("//")
+        && !trimmed
+            .chars()
+            .any(|ch| ch.is_whitespace() || matches!(ch, '?' | '#' | '\\'))
+        && !trimmed.split('/').any(|segment| {
+            matches!(
+                segment.to_ascii_lowercase().replace("%2e", ".").as_str(),
+                "." | ".."
+            )
+        });
+    if !valid {
+        eprintln!(
+            "[EchoNote] ignoring invalid endpoint path {trimmed:?}; paths must start with '/'"
+        );
+        return None;
+    }
+    Some(trimmed)
+}
+
 fn sanitize_advanced(mut advanced: AdvancedPreferences) -> AdvancedPreferences {
     advanced.prompt = Some(
         advanced
@@ -316,15 +983,91 @@ fn sanitize_advanced(mut advanced: AdvancedPreferences) -> AdvancedPreferences {
             .map(|t| t.clamp(0.0, 2.0))
             .unwrap_or(DEFAULT_TEMPERATURE),
     );
-    advanced.max_tokens = Some(
+    advanced.max_tokens = Some(advanced.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS));
+    advanced.fallback_max_tokens = Some(
         advanced
-            .max_tokens
+            .fallback_max_tokens
             .filter(|v| *v > 0)
-            .unwrap_or(DEFAULT_MAX_TOKENS),
+            .unwrap_or(DEFAULT_FALLBACK_MAX_TOKENS),
+    );
+    advanced.greeting_context_days = Some(
+        advanced
+            .greeting_context_days
+            .unwrap_or(DEFAULT_GREETING_CONTEXT_DAYS)
+            .min(MAX_GREETING_CONTEXT_DAYS),
+    );
+    advanced.network_preset = Some(advanced.network_preset.unwrap_or_default());
+    advanced.pool_idle_timeout_secs = advanced
+        .pool_idle_timeout_secs
+        .filter(|v| *v > 0)
+        .map(|v| v.min(MAX_POOL_IDLE_TIMEOUT_SECS));
+    advanced.summary_retry_attempts = Some(
+        advanced
+            .summary_retry_attempts
+            .unwrap_or(DEFAULT_SUMMARY_RETRY_ATTEMPTS)
+            .clamp(1, MAX_SUMMARY_RETRY_ATTEMPTS),
+    );
+    advanced.summary_prompt_prefix = normalize_prompt_affix(advanced.summary_prompt_prefix);
+    advanced.summary_prompt_suffix = normalize_prompt_affix(advanced.summary_prompt_suffix);
+    advanced.max_emoji_graphemes = Some(
+        advanced
+            .max_emoji_graphemes
+            .unwrap_or(DEFAULT_MAX_EMOJI_GRAPHEMES)
+            .clamp(1, MAX_EMOJI_GRAPHEMES),
+    );
+    advanced.transport_retries = Some(
+        advanced
+            .transport_retries
+            .unwrap_or(DEFAULT_TRANSPORT_RETRIES)
+            .min(MAX_TRANSPORT_RETRIES),
+    );
+    advanced.max_request_bytes = Some(
+        advanced
+            .max_request_bytes
+            .unwrap_or(DEFAULT_MAX_REQUEST_BYTES)
+            .clamp(MIN_MAX_REQUEST_BYTES, MAX_MAX_REQUEST_BYTES),
+    );
+    advanced.summary_debounce_ms = Some(
+        advanced
+            .summary_debounce_ms
+            .unwrap_or(DEFAULT_SUMMARY_DEBOUNCE_MS)
+            .min(MAX_SUMMARY_DEBOUNCE_MS),
     );
     advanced
 }
 
+/// 读取当前生效的请求体积上限，供前端展示与本地预检。
+pub fn max_request_bytes(app: &AppHandle) -> Result<u64, String> {
+    Ok(load_advanced(app)?
+        .max_request_bytes
+        .unwrap_or(DEFAULT_MAX_REQUEST_BYTES))
+}
+
+/// 读取后台摘要的防抖窗口；偏好读取失败时使用默认值，不阻塞保存。
+pub fn summary_debounce(app: &AppHandle) -> std::time::Duration {
+    let millis = load_advanced(app)
+        .ok()
+        .and_then(|advanced| advanced.summary_debounce_ms)
+        .unwrap_or(DEFAULT_SUMMARY_DEBOUNCE_MS);
+    std::time::Duration::from_millis(millis)
+}
+
+/// 读取已补全默认值的全局高级偏好，供不依赖具体 Provider 的流程使用。
+pub fn load_advanced(app: &AppHandle) -> Result<AdvancedPreferences, String> {
+    let prefs = load_preferences(app)?;
+    Ok(sanitize_advanced(prefs.advanced.unwrap_or_default()))
+}
+
+/// 将自定义前后缀压缩为单行并限制长度，避免换行或超长文本冲淡 JSON 输出约束。
+fn normalize_prompt_affix(value: Option<String>) -> Option<String> {
+    let collapsed = value?.split_whitespace().collect::<Vec<_>>().join(" ");
+    let trimmed: String = collapsed
+        .chars()
+        .take(MAX_SUMMARY_PROMPT_AFFIX_CHARS)
+        .collect();
+    Some(trimmed).filter(|text| !text.is_empty())
+}
+
 fn sanitize_base_url(value: Option<String>) -> Option<String> {
     let raw = value?;
     let trimmed = raw.trim();
@@ -342,6 +1085,115 @@ fn default_provider_preferences(provider_id: &str) -> ProviderPreferences {
         prompt: None,
         max_tokens: None,
         temperature: None,
+        summary_temperature: None,
         greeting_prompt: None,
+        organization: None,
+        project: None,
+        structured_outputs: None,
+        model_aliases: HashMap::new(),
+        chat_path: None,
+        models_path: None,
+        capabilities: None,
+        stop: Vec::new(),
+        chat_system_prompt: None,
+        greeting_model: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hidden_provider_keeps_its_settings() {
+        let mut prefs = default_preferences();
+        let gemini = prefs.providers.get_mut("gemini").unwrap();
+        gemini.selected_model = Some("gemini-2.5-pro".to_string());
+        prefs.active_provider_id = Some("gemini".to_string());
+        prefs.hidden_providers = vec!["gemini".to_string()];
+
+        let hidden = sanitize_preferences(prefs);
+        assert_eq!(hidden.active_provider_id.as_deref(), Some("noai"));
+        assert_eq!(
+            hidden.providers["gemini"].selected_model.as_deref(),
+            Some("gemini-2.5-pro")
+        );
+
+        let mut shown = hidden;
+        shown.hidden_providers.clear();
+        let shown = sanitize_preferences(shown);
+        assert_eq!(
+            shown.providers["gemini"].selected_model.as_deref(),
+            Some("gemini-2.5-pro")
+        );
+    }
+
+    #[test]
+    fn endpoint_path_rejects_traversal_and_foreign_hosts() {
+        let sanitize = |path: &str| sanitize_endpoint_path(Some(path.to_string()));
+        assert_eq!(
+            sanitize(" /openai/deployments/gpt4o/chat/completions/ ").as_deref(),
+            Some("/openai/deployments/gpt4o/chat/completions")
+        );
+        for path in [
+            "chat/completions",
+            "https://attacker.example/collect",
+            "//attacker.example/collect",
+            "/v1/../../collect",
+            "/v1/%2E%2E/collect",
+            "/v1\\collect",
+            "/v1/chat?x=1",
+        ] {
+            assert_eq!(sanitize(path), None, "{path}");
+        }
+    }
+
+    #[test]
+    fn import_keeps_local_endpoint_and_account_fields() {
+        let shared: SharedAiPreferences = serde_json::from_str(
+            r#"{"providers": {"chatgpt": {
+                "baseUrl": "https://attacker.example/v1",
+                "chatPath": "/collect",
+                "modelsPath": "/collect",
+                "organization": "org-other",
+                "project": "proj-other",
+                "selectedModel": "gpt-4o-mini"
+            }}}"#,
+        )
+        .unwrap();
+        let mut target = default_provider_preferences("chatgpt");
+        target.organization = Some("org-mine".to_string());
+        for (_, incoming) in shared.providers {
+            merge_provider(&mut target, shareable_provider(incoming));
+        }
+        assert_eq!(
+            target.base_url.as_deref(),
+            Some(default_api_base_for("chatgpt"))
+        );
+        assert_eq!(target.chat_path, None);
+        assert_eq!(target.models_path, None);
+        assert_eq!(target.organization.as_deref(), Some("org-mine"));
+        assert_eq!(target.project, None);
+        assert_eq!(target.selected_model.as_deref(), Some("gpt-4o-mini"));
+    }
+
+    #[test]
+    fn exported_provider_omits_endpoint_and_account_fields() {
+        let mut provider = default_provider_preferences("chatgpt");
+        provider.organization = Some("org-mine".to_string());
+        provider.chat_path = Some("/v2/chat".to_string());
+        let json = serde_json::to_value(shareable_provider(provider)).unwrap();
+        for key in [
+            "baseUrl",
+            "chatPath",
+            "modelsPath",
+            "organization",
+            "project",
+        ] {
+            assert!(
+                json.get(key).map_or(true, serde_json::Value::is_null),
+                "{key}"
+            );
+        }
     }
 }
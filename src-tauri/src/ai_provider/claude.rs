@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 
-use super::{AiChatRequest, AiChatResult, HTTP_CLIENT};
+use super::{
+    decode_json, describe_network_error, http_client, http_get_client, join_endpoint,
+    read_sse_data, redact, send_with_retry, split_system_prompt, AiChatRequest, AiChatResult,
+    DEFAULT_FALLBACK_MAX_TOKENS,
+};
 
 #[derive(Debug, Serialize)]
 struct AnthropicMessagePayload {
@@ -14,6 +18,10 @@ struct AnthropicMessagePayload {
     system: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none", rename = "response_format")]
     response_format: Option<AnthropicResponseFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -37,8 +45,12 @@ struct AnthropicMessageResponse {
     usage: Option<AnthropicUsage>,
 }
 
+/// 响应内容块；`thinking`、`tool_use` 等非文本块仅按类型识别后跳过，
+/// 缺少 `type` 但带有 `text` 的旧式块按文本处理。
 #[derive(Debug, Deserialize)]
 struct AnthropicContentBlock {
+    #[serde(rename = "type", default)]
+    kind: String,
     text: Option<String>,
 }
 
@@ -52,6 +64,37 @@ struct AnthropicUsage {
     total_tokens: Option<u32>,
 }
 
+/// 流式事件；仅解析生成问候语所需的字段。
+#[derive(Debug, Deserialize)]
+struct AnthropicStreamEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    message: Option<AnthropicStreamMessage>,
+    delta: Option<AnthropicStreamDelta>,
+    usage: Option<AnthropicUsage>,
+    error: Option<AnthropicStreamError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicStreamMessage {
+    model: Option<String>,
+    usage: Option<AnthropicUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicStreamDelta {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    text: Option<String>,
+    #[serde(rename = "stop_reason")]
+    stop_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicStreamError {
+    message: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct AnthropicModelList {
     data: Vec<AnthropicModelEntry>,
@@ -68,25 +111,117 @@ pub async fn invoke_claude_completion(
     api_key: &str,
     api_base: &str,
 ) -> Result<AiChatResult, String> {
+    let payload = build_payload(request, model, false)?;
+
+    let endpoint = join_endpoint(api_base, "/v1/messages");
+    let response = send_with_retry(
+        http_client()
+            .post(&endpoint)
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01")
+            .json(&payload),
+        false,
+    )
+    .await
+    .map_err(|err| describe_network_error("Claude", &endpoint, err))?;
+
+    handle_claude_response(response).await
+}
+
+pub async fn stream_claude_completion(
+    request: AiChatRequest,
+    model: String,
+    api_key: &str,
+    api_base: &str,
+    on_delta: &mut (dyn FnMut(&str) + Send),
+) -> Result<AiChatResult, String> {
+    let payload = build_payload(request, model, true)?;
+
+    let endpoint = join_endpoint(api_base, "/v1/messages");
+    let response = send_with_retry(
+        http_client()
+            .post(&endpoint)
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01")
+            .json(&payload),
+        false,
+    )
+    .await
+    .map_err(|err| describe_network_error("Claude", &endpoint, err))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "<failed to read body>".to_string());
+        return Err(redact(&format!(
+            "Claude API error (status {status}): {text}"
+        )));
+    }
+
+    let mut result = AiChatResult::default();
+    read_sse_data("Claude", response, |data| {
+        let event: AnthropicStreamEvent = serde_json::from_str(data)
+            .map_err(|err| format!("failed to decode Claude stream event: {err}"))?;
+        match event.kind.as_str() {
+            "message_start" => {
+                if let Some(message) = event.message {
+                    result.model = message.model;
+                    if let Some(usage) = message.usage {
+                        result.prompt_tokens = usage.input_tokens;
+                    }
+                }
+            }
+            "content_block_delta" => {
+                // 仅拼接 text_delta，thinking 等其他增量直接忽略。
+                if let Some(text) = event
+                    .delta
+                    .filter(|delta| delta.kind.as_deref() == Some("text_delta"))
+                    .and_then(|delta| delta.text)
+                    .filter(|text| !text.is_empty())
+                {
+                    on_delta(&text);
+                    result.content.push_str(&text);
+                }
+            }
+            "message_delta" => {
+                if let Some(stop_reason) = event.delta.and_then(|delta| delta.stop_reason) {
+                    result.finish_reason = Some(stop_reason);
+                }
+                if let Some(usage) = event.usage {
+                    result.completion_tokens = usage.output_tokens;
+                }
+            }
+            "error" => {
+                let message = event
+                    .error
+                    .map_or_else(|| "unknown error".to_string(), |error| error.message);
+                return Err(redact(&format!("Claude stream error: {message}")));
+            }
+            _ => {}
+        }
+        Ok(())
+    })
+    .await?;
+
+    result.total_tokens = result
+        .prompt_tokens
+        .zip(result.completion_tokens)
+        .map(|(input, output)| input + output);
+    Ok(result)
+}
+
+fn build_payload(
+    request: AiChatRequest,
+    model: String,
+    stream: bool,
+) -> Result<AnthropicMessagePayload, String> {
     if request.messages.is_empty() {
         return Err("AI request must contain at least one message".to_string());
     }
 
-    let (system_messages, conversation): (Vec<_>, Vec<_>) = request
-        .messages
-        .into_iter()
-        .partition(|msg| msg.role.eq_ignore_ascii_case("system"));
-    let system = if system_messages.is_empty() {
-        None
-    } else {
-        Some(
-            system_messages
-                .into_iter()
-                .map(|msg| msg.content)
-                .collect::<Vec<_>>()
-                .join("\n\n"),
-        )
-    };
+    let (system, conversation) = split_system_prompt(request.messages);
 
     let mut messages: Vec<AnthropicMessage> = Vec::new();
     for msg in conversation {
@@ -105,40 +240,36 @@ pub async fn invoke_claude_completion(
         return Err("Claude request must contain at least one user message".to_string());
     }
 
-    let max_tokens = request.max_tokens.unwrap_or(1024).max(1);
-    let payload = AnthropicMessagePayload {
+    let max_tokens = request
+        .max_tokens
+        .filter(|value| *value > 0)
+        .or_else(|| request.fallback_max_tokens.filter(|value| *value > 0))
+        .unwrap_or(DEFAULT_FALLBACK_MAX_TOKENS);
+    Ok(AnthropicMessagePayload {
         model,
         messages,
         max_tokens,
         temperature: request.temperature,
         system,
-        response_format: Some(AnthropicResponseFormat {
+        response_format: request.json_mode.then(|| AnthropicResponseFormat {
             kind: "json_object".to_string(),
         }),
-    };
-
-    let endpoint = format!("{}/v1/messages", api_base.trim_end_matches('/'));
-    let response = HTTP_CLIENT
-        .post(endpoint)
-        .header("x-api-key", api_key)
-        .header("anthropic-version", "2023-06-01")
-        .json(&payload)
-        .send()
-        .await
-        .map_err(|err| format!("failed to reach Claude API: {err}"))?;
-
-    handle_claude_response(response).await
+        stream: stream.then_some(true),
+        stop_sequences: request.stop,
+    })
 }
 
 pub async fn list_claude_models(api_base: &str, api_key: &str) -> Result<Vec<String>, String> {
-    let endpoint = format!("{}/v1/models", api_base.trim_end_matches('/'));
-    let response = HTTP_CLIENT
-        .get(endpoint)
-        .header("x-api-key", api_key)
-        .header("anthropic-version", "2023-06-01")
-        .send()
-        .await
-        .map_err(|err| format!("failed to reach Claude API: {err}"))?;
+    let endpoint = join_endpoint(api_base, "/v1/models");
+    let response = send_with_retry(
+        http_get_client()
+            .get(&endpoint)
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01"),
+        true,
+    )
+    .await
+    .map_err(|err| describe_network_error("Claude", &endpoint, err))?;
 
     let status = response.status();
     if !status.is_success() {
@@ -146,13 +277,12 @@ pub async fn list_claude_models(api_base: &str, api_key: &str) -> Result<Vec<Str
             .text()
             .await
             .unwrap_or_else(|_| "<failed to read body>".to_string());
-        return Err(format!("Claude API error (status {}): {}", status, text));
+        return Err(redact(&format!(
+            "Claude API error (status {status}): {text}"
+        )));
     }
 
-    let payload: AnthropicModelList = response
-        .json()
-        .await
-        .map_err(|err| format!("failed to decode Claude response: {err}"))?;
+    let payload: AnthropicModelList = decode_json("Claude", response).await?;
     let mut models = payload
         .data
         .into_iter()
@@ -169,19 +299,23 @@ async fn handle_claude_response(response: reqwest::Response) -> Result<AiChatRes
             .text()
             .await
             .unwrap_or_else(|_| "<failed to read body>".to_string());
-        return Err(format!("Claude API error (status {}): {}", status, text));
+        return Err(redact(&format!(
+            "Claude API error (status {status}): {text}"
+        )));
     }
 
-    let parsed: AnthropicMessageResponse = response
-        .json()
-        .await
-        .map_err(|err| format!("failed to decode Claude response: {err}"))?;
+    let parsed: AnthropicMessageResponse = decode_json("Claude", response).await?;
+    Ok(chat_result(parsed))
+}
 
+fn chat_result(parsed: AnthropicMessageResponse) -> AiChatResult {
+    // 多个文本块按顺序拼接，其余类型的块直接忽略。
     let content = parsed
         .content
         .into_iter()
-        .find_map(|block| block.text)
-        .unwrap_or_default();
+        .filter(|block| block.kind == "text" || block.kind.is_empty())
+        .filter_map(|block| block.text)
+        .collect::<String>();
     let total_tokens = parsed
         .usage
         .as_ref()
@@ -195,12 +329,68 @@ async fn handle_claude_response(response: reqwest::Response) -> Result<AiChatRes
                 .map(|(input, output)| input + output)
         });
 
-    Ok(AiChatResult {
+    AiChatResult {
         content,
         finish_reason: parsed.stop_reason,
         model: parsed.model,
         prompt_tokens: parsed.usage.as_ref().and_then(|u| u.input_tokens),
         completion_tokens: parsed.usage.as_ref().and_then(|u| u.output_tokens),
         total_tokens,
-    })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn system_only_request() -> AiChatRequest {
+        serde_json::from_str(
+            r#"{"providerId": "claude", "messages": [{"role": "system", "content": "be brief"}]}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn system_only_request_needs_user_message() {
+        let err = build_payload(system_only_request(), "claude-haiku-4-5".to_string(), false)
+            .unwrap_err();
+        assert_eq!(err, "Claude request must contain at least one user message");
+    }
+
+    #[test]
+    fn concatenates_text_blocks_and_skips_others() {
+        let parsed: AnthropicMessageResponse = serde_json::from_str(
+            r#"{
+                "content": [
+                    {"type": "thinking", "thinking": "plan the reply"},
+                    {"type": "text", "text": "{\"summary\": "},
+                    {"type": "tool_use", "id": "toolu_1", "name": "noop", "input": {}},
+                    {"type": "text", "text": "\"A quiet day.\"}"}
+                ],
+                "stop_reason": "end_turn",
+                "model": "claude-3-5-haiku-latest",
+                "usage": {"input_tokens": 12, "output_tokens": 8}
+            }"#,
+        )
+        .unwrap();
+        let result = chat_result(parsed);
+        assert_eq!(result.content, r#"{"summary": "A quiet day."}"#);
+        assert_eq!(result.total_tokens, Some(20));
+    }
+
+    #[test]
+    fn untyped_blocks_with_text_are_kept() {
+        let parsed: AnthropicMessageResponse = serde_json::from_str(
+            r#"{
+                "content": [
+                    {"text": "{\"summary\": "},
+                    {"type": "tool_use", "id": "toolu_1", "name": "noop", "input": {}},
+                    {"id": "untyped_without_text"},
+                    {"type": "text", "text": "\"Legacy.\"}"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(chat_result(parsed).content, r#"{"summary": "Legacy."}"#);
+    }
 }
@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-use super::{AiChatRequest, AiChatResult, HTTP_CLIENT};
+use super::{
+    decode_json, describe_network_error, http_client, http_get_client, join_endpoint,
+    read_sse_data, redact, send_with_retry, split_system_prompt, AiChatRequest, AiChatResult,
+};
 
 #[derive(Debug, Serialize)]
 struct GeminiPayload {
@@ -31,6 +34,8 @@ struct GeminiGenerationConfig {
     max_output_tokens: Option<u32>,
     #[serde(rename = "responseMimeType", skip_serializing_if = "Option::is_none")]
     response_mime_type: Option<String>,
+    #[serde(rename = "stopSequences", skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -42,13 +47,16 @@ struct GeminiGenerateResponse {
 
 #[derive(Debug, Deserialize)]
 struct GeminiCandidate {
+    /// 流式响应的最后一个片段可能只携带 finishReason。
+    #[serde(default)]
     content: GeminiCandidateContent,
     #[serde(rename = "finishReason")]
     finish_reason: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 struct GeminiCandidateContent {
+    #[serde(default)]
     parts: Vec<GeminiCandidatePart>,
 }
 
@@ -83,27 +91,98 @@ pub async fn invoke_gemini_completion(
     api_key: &str,
     api_base: &str,
 ) -> Result<AiChatResult, String> {
+    let payload = build_payload(request)?;
+
+    let endpoint = model_endpoint(api_base, &model, "generateContent");
+    let response = send_with_retry(
+        http_client()
+            .post(&endpoint)
+            .query(&[("key", api_key)])
+            .json(&payload),
+        false,
+    )
+    .await
+    .map_err(|err| describe_network_error("Gemini", &endpoint, err))?;
+
+    handle_gemini_response(response).await
+}
+
+pub async fn stream_gemini_completion(
+    request: AiChatRequest,
+    model: String,
+    api_key: &str,
+    api_base: &str,
+    on_delta: &mut (dyn FnMut(&str) + Send),
+) -> Result<AiChatResult, String> {
+    let payload = build_payload(request)?;
+
+    let endpoint = model_endpoint(api_base, &model, "streamGenerateContent");
+    let response = send_with_retry(
+        http_client()
+            .post(&endpoint)
+            .query(&[("alt", "sse"), ("key", api_key)])
+            .json(&payload),
+        false,
+    )
+    .await
+    .map_err(|err| describe_network_error("Gemini", &endpoint, err))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "<failed to read body>".to_string());
+        return Err(redact(&format!(
+            "Gemini API error (status {status}): {text}"
+        )));
+    }
+
+    let mut result = AiChatResult::default();
+    read_sse_data("Gemini", response, |data| {
+        let chunk: GeminiGenerateResponse = serde_json::from_str(data)
+            .map_err(|err| format!("failed to decode Gemini stream chunk: {err}"))?;
+        if let Some(candidate) = chunk.candidates.and_then(|mut list| list.pop()) {
+            for text in candidate
+                .content
+                .parts
+                .into_iter()
+                .filter_map(|part| part.text)
+            {
+                if !text.is_empty() {
+                    on_delta(&text);
+                    result.content.push_str(&text);
+                }
+            }
+            if candidate.finish_reason.is_some() {
+                result.finish_reason = candidate.finish_reason;
+            }
+        }
+        if let Some(usage) = chunk.usage {
+            result.prompt_tokens = usage.prompt_tokens;
+            result.completion_tokens = usage.candidates_tokens;
+            result.total_tokens = usage.total_tokens;
+        }
+        Ok(())
+    })
+    .await?;
+
+    if result.content.is_empty() {
+        return Err("Gemini API returned no completion candidates".to_string());
+    }
+    Ok(result)
+}
+
+fn build_payload(request: AiChatRequest) -> Result<GeminiPayload, String> {
     if request.messages.is_empty() {
         return Err("AI request must contain at least one message".to_string());
     }
 
-    let (system_messages, conversation): (Vec<_>, Vec<_>) = request
-        .messages
-        .into_iter()
-        .partition(|msg| msg.role.eq_ignore_ascii_case("system"));
-    let system_instruction = if system_messages.is_empty() {
-        None
-    } else {
-        let text = system_messages
-            .into_iter()
-            .map(|msg| msg.content)
-            .collect::<Vec<_>>()
-            .join("\n\n");
-        Some(GeminiContent {
-            role: None,
-            parts: vec![GeminiPart { text }],
-        })
-    };
+    let (system, conversation) = split_system_prompt(request.messages);
+    let system_instruction = system.map(|text| GeminiContent {
+        role: None,
+        parts: vec![GeminiPart { text }],
+    });
 
     let mut contents: Vec<GeminiContent> = Vec::new();
     for msg in conversation {
@@ -122,40 +201,41 @@ pub async fn invoke_gemini_completion(
         return Err("Gemini request must contain at least one user message".to_string());
     }
 
-    let payload = GeminiPayload {
+    Ok(GeminiPayload {
         contents,
         system_instruction,
         generation_config: Some(GeminiGenerationConfig {
             temperature: request.temperature,
-            max_output_tokens: request.max_tokens,
-            response_mime_type: Some("application/json".to_string()),
+            max_output_tokens: request.max_tokens.filter(|value| *value > 0),
+            response_mime_type: request.json_mode.then(|| "application/json".to_string()),
+            stop_sequences: request.stop,
         }),
-    };
-
-    let endpoint = format!(
-        "{}/v1beta/models/{}:generateContent",
-        api_base.trim_end_matches('/'),
-        model
-    );
-    let response = HTTP_CLIENT
-        .post(endpoint)
-        .query(&[("key", api_key)])
-        .json(&payload)
-        .send()
-        .await
-        .map_err(|err| format!("failed to reach Gemini API: {err}"))?;
+    })
+}
 
-    handle_gemini_response(response).await
+/// 拼接 `{base}/v1beta/models/<name>:<method>`，模型名无论是否带 `models/` 前缀都能得到正确路径。
+fn model_endpoint(api_base: &str, model: &str, method: &str) -> String {
+    join_endpoint(
+        api_base,
+        &format!("/v1beta/models/{}:{method}", normalize_model_name(model)),
+    )
+}
+
+/// 去掉 `models/` 前缀、首尾斜杠以及误存的 `:method` 后缀；`-latest` 等别名原样保留。
+fn normalize_model_name(model: &str) -> &str {
+    let trimmed = model.trim().trim_matches('/');
+    let name = trimmed.strip_prefix("models/").unwrap_or(trimmed);
+    name.split_once(':').map_or(name, |(name, _)| name)
 }
 
 pub async fn list_gemini_models(api_base: &str, api_key: &str) -> Result<Vec<String>, String> {
-    let endpoint = format!("{}/v1beta/models", api_base.trim_end_matches('/'));
-    let response = HTTP_CLIENT
-        .get(endpoint)
-        .query(&[("key", api_key)])
-        .send()
-        .await
-        .map_err(|err| format!("failed to reach Gemini API: {err}"))?;
+    let endpoint = join_endpoint(api_base, "/v1beta/models");
+    let response = send_with_retry(
+        http_get_client().get(&endpoint).query(&[("key", api_key)]),
+        true,
+    )
+    .await
+    .map_err(|err| describe_network_error("Gemini", &endpoint, err))?;
 
     let status = response.status();
     if !status.is_success() {
@@ -163,25 +243,17 @@ pub async fn list_gemini_models(api_base: &str, api_key: &str) -> Result<Vec<Str
             .text()
             .await
             .unwrap_or_else(|_| "<failed to read body>".to_string());
-        return Err(format!("Gemini API error (status {}): {}", status, text));
+        return Err(redact(&format!(
+            "Gemini API error (status {status}): {text}"
+        )));
     }
 
-    let payload: GeminiModelList = response
-        .json()
-        .await
-        .map_err(|err| format!("failed to decode Gemini response: {err}"))?;
+    let payload: GeminiModelList = decode_json("Gemini", response).await?;
     let mut models = payload
         .models
         .unwrap_or_default()
         .into_iter()
-        .map(|model| {
-            model
-                .name
-                .split('/')
-                .last()
-                .unwrap_or(&model.name)
-                .to_string()
-        })
+        .map(|model| normalize_model_name(&model.name).to_string())
         .collect::<Vec<_>>();
     models.sort();
     Ok(models)
@@ -194,13 +266,12 @@ async fn handle_gemini_response(response: reqwest::Response) -> Result<AiChatRes
             .text()
             .await
             .unwrap_or_else(|_| "<failed to read body>".to_string());
-        return Err(format!("Gemini API error (status {}): {}", status, text));
+        return Err(redact(&format!(
+            "Gemini API error (status {status}): {text}"
+        )));
     }
 
-    let parsed: GeminiGenerateResponse = response
-        .json()
-        .await
-        .map_err(|err| format!("failed to decode Gemini response: {err}"))?;
+    let parsed: GeminiGenerateResponse = decode_json("Gemini", response).await?;
 
     let candidate_text = parsed
         .candidates
@@ -225,3 +296,44 @@ async fn handle_gemini_response(response: reqwest::Response) -> Result<AiChatRes
         total_tokens: parsed.usage.as_ref().and_then(|u| u.total_tokens),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn system_only_request() -> AiChatRequest {
+        serde_json::from_str(
+            r#"{"providerId": "gemini", "messages": [{"role": "system", "content": "be brief"}]}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn system_only_request_needs_user_message() {
+        let err = build_payload(system_only_request()).unwrap_err();
+        assert_eq!(err, "Gemini request must contain at least one user message");
+    }
+
+    #[test]
+    fn model_endpoint_accepts_prefixed_and_alias_names() {
+        let base = "https://generativelanguage.googleapis.com/";
+        let expected =
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-1.5-flash:generateContent";
+        for model in [
+            "gemini-1.5-flash",
+            "models/gemini-1.5-flash",
+            " /models/gemini-1.5-flash/ ",
+            "gemini-1.5-flash:generateContent",
+        ] {
+            assert_eq!(
+                model_endpoint(base, model, "generateContent"),
+                expected,
+                "{model}"
+            );
+        }
+        assert_eq!(
+            normalize_model_name("models/gemini-1.5-flash-latest"),
+            "gemini-1.5-flash-latest"
+        );
+    }
+}
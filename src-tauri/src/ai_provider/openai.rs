@@ -1,7 +1,14 @@
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 
-use super::{AiChatRequest, AiChatResult, AiMessage, HTTP_CLIENT};
+const DEFAULT_CHAT_PATH: &str = "/chat/completions";
+const DEFAULT_MODELS_PATH: &str = "/models";
+
+use super::{
+    decode_json, describe_network_error, http_client, http_get_client, join_endpoint,
+    read_sse_data, redact, send_with_retry, AiChatRequest, AiChatResult, AiMessage, JsonSchemaSpec,
+    ProviderScope,
+};
 
 #[derive(Debug, Serialize)]
 struct ChatCompletionPayload {
@@ -15,12 +22,53 @@ struct ChatCompletionPayload {
     response_format: Option<ResponseFormatPayload>,
     #[serde(skip_serializing_if = "Option::is_none")]
     reasoning_effort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+    /// 流式请求默认不返回用量，需显式要求在最后一个片段中附带 `usage`。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptionsPayload>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct StreamOptionsPayload {
+    include_usage: bool,
 }
 
 #[derive(Debug, Serialize)]
 struct ResponseFormatPayload {
     #[serde(rename = "type")]
     kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    json_schema: Option<JsonSchemaPayload>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonSchemaPayload {
+    name: String,
+    schema: serde_json::Value,
+    strict: bool,
+}
+
+impl ResponseFormatPayload {
+    /// 带 Schema 时发送严格的 `json_schema`，否则使用兼容性最好的 `json_object`。
+    fn from_schema(schema: Option<JsonSchemaSpec>) -> Self {
+        match schema {
+            Some(JsonSchemaSpec { name, schema }) => Self {
+                kind: "json_schema".to_string(),
+                json_schema: Some(JsonSchemaPayload {
+                    name,
+                    schema,
+                    strict: true,
+                }),
+            },
+            None => Self {
+                kind: "json_object".to_string(),
+                json_schema: None,
+            },
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -32,14 +80,19 @@ struct ChatCompletionResponse {
 
 #[derive(Debug, Deserialize)]
 struct ChatCompletionChoice {
-    message: ChoiceMessage,
+    message: Option<ChoiceMessage>,
+    /// 部分兼容服务即使非流式也以 delta 片段返回内容。
+    delta: Option<ChoiceMessage>,
     #[serde(rename = "finish_reason")]
     finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct ChoiceMessage {
-    content: String,
+    content: Option<String>,
+    refusal: Option<String>,
+    reasoning: Option<String>,
+    reasoning_content: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -81,42 +134,160 @@ pub async fn invoke_openai_completion(
     api_key: &str,
     api_base: &str,
 ) -> Result<AiChatResult, String> {
+    let scope = request.scope.clone();
+    let endpoint = build_endpoint(api_base, scope.chat_path.as_deref(), DEFAULT_CHAT_PATH);
+    let payload = build_payload(request, model, false)?;
+
+    let builder = apply_auth(
+        apply_scope(http_client().post(&endpoint), &scope).json(&payload),
+        api_key,
+    );
+    let response = send_with_retry(builder, false)
+        .await
+        .map_err(|err| describe_network_error("OpenAI", &endpoint, err))?;
+
+    handle_openai_response(response).await
+}
+
+pub async fn stream_openai_completion(
+    request: AiChatRequest,
+    model: String,
+    api_key: &str,
+    api_base: &str,
+    on_delta: &mut (dyn FnMut(&str) + Send),
+) -> Result<AiChatResult, String> {
+    let scope = request.scope.clone();
+    let endpoint = build_endpoint(api_base, scope.chat_path.as_deref(), DEFAULT_CHAT_PATH);
+    let payload = build_payload(request, model, true)?;
+
+    let builder = apply_auth(
+        apply_scope(http_client().post(&endpoint), &scope).json(&payload),
+        api_key,
+    );
+    let response = send_with_retry(builder, false)
+        .await
+        .map_err(|err| describe_network_error("OpenAI", &endpoint, err))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "<failed to read body>".to_string());
+        return Err(redact(&decode_error(status, &text)));
+    }
+
+    let mut result = AiChatResult::default();
+    read_sse_data("OpenAI", response, |data| {
+        let chunk: ChatCompletionResponse = serde_json::from_str(data)
+            .map_err(|err| format!("failed to decode OpenAI stream chunk: {err}"))?;
+        for choice in chunk.choices {
+            let delta = [choice.delta, choice.message]
+                .into_iter()
+                .flatten()
+                .find_map(|message| message.content);
+            if let Some(text) = delta.filter(|text| !text.is_empty()) {
+                on_delta(&text);
+                result.content.push_str(&text);
+            }
+            if choice.finish_reason.is_some() {
+                result.finish_reason = choice.finish_reason;
+            }
+        }
+        if chunk.model.is_some() {
+            result.model = chunk.model;
+        }
+        if let Some(usage) = chunk.usage {
+            result.prompt_tokens = usage.prompt_tokens;
+            result.completion_tokens = usage.completion_tokens;
+            result.total_tokens = usage.total_tokens;
+        }
+        Ok(())
+    })
+    .await?;
+
+    Ok(result)
+}
+
+fn build_payload(
+    request: AiChatRequest,
+    model: String,
+    stream: bool,
+) -> Result<ChatCompletionPayload, String> {
     if request.messages.is_empty() {
         return Err("AI request must contain at least one message".to_string());
     }
 
-    let endpoint = format!("{}/chat/completions", api_base.trim_end_matches('/'));
-
-    let payload = ChatCompletionPayload {
+    // 推理模型（如 deepseek-reasoner）拒绝 temperature / response_format，
+    // 其推理过程在 reasoning_content 中返回，这里只读取 content。
+    let reasoner = is_reasoner_model(&model);
+    Ok(ChatCompletionPayload {
+        temperature: request.temperature.filter(|_| !reasoner),
+        max_tokens: request.max_tokens.filter(|value| *value > 0),
+        response_format: (request.json_mode && !reasoner)
+            .then(|| ResponseFormatPayload::from_schema(request.json_schema)),
+        reasoning_effort: (!reasoner).then(|| "minimal".to_string()),
+        stream: stream.then_some(true),
+        stream_options: stream.then_some(StreamOptionsPayload {
+            include_usage: true,
+        }),
+        stop: request.stop,
         model,
         messages: request.messages,
-        temperature: request.temperature,
-        max_tokens: request.max_tokens,
-        response_format: Some(ResponseFormatPayload {
-            kind: "json_object".to_string(),
-        }),
-        reasoning_effort: Some("minimal".to_string()),
+    })
+}
+
+fn build_endpoint(api_base: &str, path: Option<&str>, default_path: &str) -> String {
+    join_endpoint(api_base, path.unwrap_or(default_path))
+}
+
+/// 仅在 API Key 非空时发送 `Authorization` 头；部分本地服务会拒绝空的 `Bearer `。
+fn apply_auth(builder: reqwest::RequestBuilder, api_key: &str) -> reqwest::RequestBuilder {
+    if api_key.trim().is_empty() {
+        builder
+    } else {
+        builder.bearer_auth(api_key)
+    }
+}
+
+/// 配置了组织 / 项目时附加对应请求头，用于归属用量与访问组织内模型。
+fn apply_scope(builder: reqwest::RequestBuilder, scope: &ProviderScope) -> reqwest::RequestBuilder {
+    let builder = match scope.organization.as_deref() {
+        Some(organization) => builder.header("OpenAI-Organization", organization),
+        None => builder,
     };
+    match scope.project.as_deref() {
+        Some(project) => builder.header("OpenAI-Project", project),
+        None => builder,
+    }
+}
 
-    let response = HTTP_CLIENT
-        .post(endpoint)
-        .bearer_auth(api_key)
-        .json(&payload)
-        .send()
-        .await
-        .map_err(|err| format!("failed to reach OpenAI API: {err}"))?;
+pub fn supports_json_schema(model: &str) -> bool {
+    let lower = model.to_ascii_lowercase();
+    let name = lower.rsplit('/').next().unwrap_or(&lower);
+    ["gpt-4o", "gpt-4.1", "gpt-5", "o3", "o4"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
 
-    handle_openai_response(response).await
+fn is_reasoner_model(model: &str) -> bool {
+    let lower = model.to_ascii_lowercase();
+    lower.contains("reasoner") || lower.contains("deepseek-r1")
 }
 
-pub async fn list_openai_models(api_base: &str, api_key: &str) -> Result<Vec<String>, String> {
-    let endpoint = format!("{}/models", api_base.trim_end_matches('/'));
-    let response = HTTP_CLIENT
-        .get(endpoint)
-        .bearer_auth(api_key)
-        .send()
+pub async fn list_openai_models(
+    api_base: &str,
+    api_key: &str,
+    scope: &ProviderScope,
+) -> Result<Vec<String>, String> {
+    let endpoint = build_endpoint(api_base, scope.models_path.as_deref(), DEFAULT_MODELS_PATH);
+    let builder = apply_auth(
+        apply_scope(http_get_client().get(&endpoint), scope),
+        api_key,
+    );
+    let response = send_with_retry(builder, true)
         .await
-        .map_err(|err| format!("failed to reach OpenAI API: {err}"))?;
+        .map_err(|err| describe_network_error("OpenAI", &endpoint, err))?;
     handle_model_list_response(response).await
 }
 
@@ -127,22 +298,16 @@ async fn handle_openai_response(response: reqwest::Response) -> Result<AiChatRes
             .text()
             .await
             .unwrap_or_else(|_| "<failed to read body>".to_string());
-        return Err(decode_error(status, &text));
+        return Err(redact(&decode_error(status, &text)));
     }
 
-    let parsed: ChatCompletionResponse = response
-        .json()
-        .await
-        .map_err(|err| format!("failed to decode OpenAI response: {err}"))?;
+    let parsed: ChatCompletionResponse = decode_json("OpenAI", response).await?;
 
-    let mut choices = parsed.choices.into_iter();
-    let first_choice = choices
-        .next()
-        .ok_or_else(|| "OpenAI API returned no completion choices".to_string())?;
+    let (content, finish_reason) = extract_choice_content(parsed.choices)?;
 
     Ok(AiChatResult {
-        content: first_choice.message.content,
-        finish_reason: first_choice.finish_reason,
+        content,
+        finish_reason,
         model: parsed.model,
         prompt_tokens: parsed.usage.as_ref().and_then(|u| u.prompt_tokens),
         completion_tokens: parsed.usage.as_ref().and_then(|u| u.completion_tokens),
@@ -150,6 +315,49 @@ async fn handle_openai_response(response: reqwest::Response) -> Result<AiChatRes
     })
 }
 
+/// 依次扫描 choices，返回第一个非空的 `message.content`（或 `delta.content`）。
+///
+/// 若所有 choice 都没有正文，则根据 refusal / reasoning 字段给出明确的错误信息。
+fn extract_choice_content(
+    choices: Vec<ChatCompletionChoice>,
+) -> Result<(String, Option<String>), String> {
+    if choices.is_empty() {
+        return Err("OpenAI API returned no completion choices".to_string());
+    }
+
+    let mut refusal = None;
+    let mut has_reasoning = false;
+    let mut last_finish_reason = None;
+    for choice in choices {
+        for message in [choice.message, choice.delta].into_iter().flatten() {
+            if let Some(content) = message.content.filter(|text| !text.trim().is_empty()) {
+                return Ok((content, choice.finish_reason));
+            }
+            if refusal.is_none() {
+                refusal = message.refusal.filter(|text| !text.trim().is_empty());
+            }
+            has_reasoning |= [message.reasoning, message.reasoning_content]
+                .iter()
+                .flatten()
+                .any(|text| !text.trim().is_empty());
+        }
+        last_finish_reason = choice.finish_reason.or(last_finish_reason);
+    }
+
+    if let Some(refusal) = refusal {
+        return Err(format!("OpenAI API refused the request: {refusal}"));
+    }
+    let finish_reason = last_finish_reason.unwrap_or_else(|| "unknown".to_string());
+    if has_reasoning {
+        return Err(format!(
+            "OpenAI API returned reasoning without a final answer (finish_reason: {finish_reason}); try raising max tokens"
+        ));
+    }
+    Err(format!(
+        "OpenAI API returned empty completion content (finish_reason: {finish_reason})"
+    ))
+}
+
 async fn handle_model_list_response(response: reqwest::Response) -> Result<Vec<String>, String> {
     let status = response.status();
     if !status.is_success() {
@@ -157,13 +365,10 @@ async fn handle_model_list_response(response: reqwest::Response) -> Result<Vec<S
             .text()
             .await
             .unwrap_or_else(|_| "<failed to read body>".to_string());
-        return Err(decode_error(status, &text));
+        return Err(redact(&decode_error(status, &text)));
     }
 
-    let payload: ModelListResponse = response
-        .json()
-        .await
-        .map_err(|err| format!("failed to decode OpenAI response: {err}"))?;
+    let payload: ModelListResponse = decode_json("OpenAI", response).await?;
 
     let mut models = payload
         .data
@@ -188,3 +393,46 @@ fn decode_error(status: StatusCode, payload: &str) -> String {
 
     format!("OpenAI API error (status {}): {}", status, payload)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat_request() -> AiChatRequest {
+        serde_json::from_str(
+            r#"{"providerId": "chatgpt", "messages": [{"role": "user", "content": "hi"}]}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn streaming_payload_requests_usage() {
+        let payload = build_payload(chat_request(), "gpt-4o-mini".to_string(), true).unwrap();
+        let json = serde_json::to_value(&payload).unwrap();
+        assert_eq!(json["stream"], true);
+        assert_eq!(json["stream_options"]["include_usage"], true);
+
+        let payload = build_payload(chat_request(), "gpt-4o-mini".to_string(), false).unwrap();
+        let json = serde_json::to_value(&payload).unwrap();
+        assert!(json.get("stream_options").is_none());
+    }
+
+    #[test]
+    fn blank_api_key_omits_authorization_header() {
+        let client = reqwest::Client::new();
+        let authorization = |api_key: &str| {
+            apply_auth(
+                client.post("http://localhost:1234/v1/chat/completions"),
+                api_key,
+            )
+            .build()
+            .unwrap()
+            .headers()
+            .get(reqwest::header::AUTHORIZATION)
+            .map(|value| value.to_str().unwrap().to_string())
+        };
+        assert_eq!(authorization(""), None);
+        assert_eq!(authorization("   "), None);
+        assert_eq!(authorization("sk-test").as_deref(), Some("Bearer sk-test"));
+    }
+}
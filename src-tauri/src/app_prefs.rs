@@ -0,0 +1,188 @@
+//! Non-AI application preferences (storage behaviour) persisted next to the AI preferences.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::models::{BodyEncoding, HashAlgorithm};
+use crate::storage::{DocumentStyle, WriteOptions, DEFAULT_BLANK_LINES_AFTER_FRONTMATTER};
+
+pub const PREFS_FILE_NAME: &str = "app_preferences.json";
+pub const MAX_REVISION_LIMIT: usize = 100;
+pub const MAX_FRONTMATTER_BLANK_LINES: usize = 3;
+const EPHEMERAL_FLAG: &str = "--ephemeral";
+const EPHEMERAL_ENV: &str = "ECHONOTE_EPHEMERAL";
+
+/// 临时模式仅由启动参数 `--ephemeral` 或环境变量 `ECHONOTE_EPHEMERAL=1` 开启，
+/// 进程内保持不变，避免运行中途切换导致数据一半在磁盘一半在内存。
+static EPHEMERAL: Lazy<bool> = Lazy::new(|| {
+    std::env::args().any(|arg| arg == EPHEMERAL_FLAG)
+        || std::env::var(EPHEMERAL_ENV).is_ok_and(|value| matches!(value.trim(), "1" | "true"))
+});
+
+/// 已读取的偏好：存储层的每次读写都要用到文件名格式与写入选项，避免每次都读盘解析；
+/// 保存时同步更新，配置文件被迁移或删除时通过 `clear_cache` 失效。临时模式下缓存即唯一副本。
+static CACHE: Lazy<Mutex<Option<AppPreferences>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AppPreferences {
+    /// 新写入正文的编码：`zstd` 压缩或明文（frontmatter 始终保持明文以便列表读取）。
+    #[serde(default)]
+    pub body_encoding: BodyEncoding,
+    /// 离线模式：开启后禁止一切网络请求，AI 相关流程改用本地回退。
+    #[serde(default)]
+    pub offline_mode: bool,
+    /// 条目文件名使用的日期格式（chrono strftime），缺省为 `%Y-%m-%d`。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename_date_format: Option<String>,
+    /// 每篇日记保留的历史版本数，0 表示关闭历史记录。
+    #[serde(default)]
+    pub revision_limit: usize,
+    /// 调试日志：记录 AI 请求摘要（不含正文与密钥）到 `logs/ai.log`。
+    #[serde(default)]
+    pub debug_logging: bool,
+    /// frontmatter 结束 `---` 后的空行数，缺省为 1。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frontmatter_blank_lines: Option<usize>,
+    /// 写入时确保文件以换行结尾。
+    #[serde(default)]
+    pub trailing_newline: bool,
+    /// 新写入条目的正文 HASH 算法，缺省为 BLAKE3；已有条目按各自记录的算法校验。
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+    /// 旧版 `ai_config.json` 迁移完成后的处理方式，缺省为重命名为 `.bak`。
+    #[serde(default)]
+    pub legacy_config_handling: LegacyConfigHandling,
+    /// 额外的只读日记目录（绝对路径），读取时与主目录按日期合并，主目录优先。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_read_roots: Vec<String>,
+}
+
+/// 旧版合并配置文件迁移后的处理方式。
+///
+/// 同步配置目录的用户可选择 `keep`：原文件保持不动，不会在各设备间产生重命名或删除的冲突；
+/// 新文件已存在时迁移会自动跳过，因此不会重复执行。代价是旧文件中的加密密钥会一直留在磁盘上
+/// 并被同步，直到用户手动删除。`delete` 直接删除原文件，同步后其他尚未迁移的设备将无法再迁移。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum LegacyConfigHandling {
+    #[default]
+    Rename,
+    Delete,
+    Keep,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+struct WrappedPreferences {
+    #[serde(rename = "appSettings")]
+    pub app_settings: Option<AppPreferences>,
+}
+
+pub fn load_preferences(app: &AppHandle) -> Result<AppPreferences, String> {
+    let cached = lock_cache()?.clone();
+    if let Some(prefs) = cached {
+        return Ok(prefs);
+    }
+    let prefs = read_preferences(app)?;
+    *lock_cache()? = Some(prefs.clone());
+    Ok(prefs)
+}
+
+/// 丢弃缓存的偏好，下次读取时重新从磁盘加载。
+pub fn clear_cache() {
+    if let Ok(mut cache) = CACHE.lock() {
+        *cache = None;
+    }
+}
+
+fn lock_cache() -> Result<std::sync::MutexGuard<'static, Option<AppPreferences>>, String> {
+    CACHE
+        .lock()
+        .map_err(|_| "failed to lock app preferences cache".to_string())
+}
+
+fn read_preferences(app: &AppHandle) -> Result<AppPreferences, String> {
+    let path = preferences_path(app)?;
+    if !path.exists() {
+        return Ok(AppPreferences::default());
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|err| format!("failed to read app preferences {}: {err}", path.display()))?;
+    if content.trim().is_empty() {
+        return Ok(AppPreferences::default());
+    }
+
+    let parsed: WrappedPreferences = serde_json::from_str(&content)
+        .map_err(|err| format!("failed to parse app preferences {}: {err}", path.display()))?;
+    Ok(parsed.app_settings.unwrap_or_default())
+}
+
+/// 保存偏好；临时模式下只更新内存中的缓存，不写入磁盘。
+pub fn save_preferences(app: &AppHandle, prefs: &AppPreferences) -> Result<(), String> {
+    if is_ephemeral() {
+        *lock_cache()? = Some(prefs.clone());
+        return Ok(());
+    }
+    let wrapped = WrappedPreferences {
+        app_settings: Some(prefs.clone()),
+    };
+    let path = preferences_path(app)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|err| format!("failed to create {}: {err}", dir.display()))?;
+    }
+    let serialized = serde_json::to_string_pretty(&wrapped)
+        .map_err(|err| format!("failed to serialize app preferences: {err}"))?;
+    fs::write(&path, serialized)
+        .map_err(|err| format!("failed to write app preferences {}: {err}", path.display()))?;
+    *lock_cache()? = Some(prefs.clone());
+    Ok(())
+}
+
+/// 将偏好转换为存储层的写入选项。
+pub fn write_options(app: &AppHandle) -> Result<WriteOptions, String> {
+    let prefs = load_preferences(app)?;
+    Ok(WriteOptions {
+        body_encoding: prefs.body_encoding,
+        revision_limit: prefs.revision_limit.min(MAX_REVISION_LIMIT),
+        document_style: DocumentStyle {
+            blank_lines_after_frontmatter: prefs
+                .frontmatter_blank_lines
+                .unwrap_or(DEFAULT_BLANK_LINES_AFTER_FRONTMATTER)
+                .min(MAX_FRONTMATTER_BLANK_LINES),
+            trailing_newline: prefs.trailing_newline,
+        },
+    })
+}
+
+/// 旧版配置迁移后的处理方式；偏好读取失败时使用默认的重命名。
+pub fn legacy_config_handling(app: &AppHandle) -> LegacyConfigHandling {
+    load_preferences(app)
+        .map(|prefs| prefs.legacy_config_handling)
+        .unwrap_or_default()
+}
+
+/// 当前是否处于临时模式：日记与密钥只保存在内存中，退出后全部丢弃。
+pub fn is_ephemeral() -> bool {
+    *EPHEMERAL
+}
+
+/// 当前是否处于离线模式；偏好读取失败时视为在线，不阻断正常流程。
+pub fn is_offline(app: &AppHandle) -> bool {
+    load_preferences(app).is_ok_and(|prefs| prefs.offline_mode)
+}
+
+pub fn preferences_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_config_dir()
+        .or_else(|_| app.path().app_data_dir())
+        .map_err(|err| format!("failed to resolve app config dir: {err}"))?;
+    Ok(dir.join(PREFS_FILE_NAME))
+}
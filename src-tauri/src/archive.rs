@@ -0,0 +1,201 @@
+//! Minimal `.tar.gz` reader/writer for archived month directories.
+//!
+//! 只处理扁平的普通文件（月份目录中的 `.md` 条目），因此实现了 ustar 格式的最小子集。
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use once_cell::sync::Lazy;
+
+const BLOCK_SIZE: usize = 512;
+const NAME_LEN: usize = 100;
+const MAX_CACHED_ARCHIVES: usize = 12;
+
+/// 归档中的 `(文件名, 内容)` 列表。
+pub type ArchiveFiles = Vec<(String, Vec<u8>)>;
+type ArchiveStamp = (Option<SystemTime>, u64);
+type ArchiveCache = HashMap<PathBuf, (ArchiveStamp, Arc<ArchiveFiles>)>;
+
+/// 已解压的归档：条目查找与日期列表会反复读取同一归档，按文件修改时间与大小判断是否失效。
+static CACHE: Lazy<Mutex<ArchiveCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// 将 `(文件名, 内容)` 列表写入 gzip 压缩的 tar 文件；先写临时文件再重命名，避免半成品覆盖。
+pub fn write_tar_gz(path: &Path, files: &[(String, Vec<u8>)]) -> Result<(), String> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    for (name, data) in files {
+        let header = build_header(name, data.len())?;
+        encoder
+            .write_all(&header)
+            .and_then(|()| encoder.write_all(data))
+            .and_then(|()| encoder.write_all(&vec![0u8; padding_for(data.len())]))
+            .map_err(|err| format!("failed to compress {name}: {err}"))?;
+    }
+    encoder
+        .write_all(&[0u8; BLOCK_SIZE * 2])
+        .map_err(|err| format!("failed to finish archive: {err}"))?;
+    let bytes = encoder
+        .finish()
+        .map_err(|err| format!("failed to finish archive: {err}"))?;
+
+    let tmp = path.with_extension("gz.tmp");
+    fs::write(&tmp, bytes).map_err(|err| format!("failed to write {}: {err}", tmp.display()))?;
+    lock_cache()?.remove(path);
+    fs::rename(&tmp, path).map_err(|err| format!("failed to replace {}: {err}", path.display()))
+}
+
+/// 与 `read_tar_gz` 相同，但复用上次解压的结果；归档文件变化后重新读取。
+pub fn read_tar_gz_cached(path: &Path) -> Result<Arc<ArchiveFiles>, String> {
+    let metadata =
+        fs::metadata(path).map_err(|err| format!("failed to open {}: {err}", path.display()))?;
+    let stamp = (metadata.modified().ok(), metadata.len());
+    let cached = lock_cache()?
+        .get(path)
+        .filter(|(cached_stamp, _)| *cached_stamp == stamp)
+        .map(|(_, files)| Arc::clone(files));
+    if let Some(files) = cached {
+        return Ok(files);
+    }
+
+    let files = Arc::new(read_tar_gz(path)?);
+    let mut cache = lock_cache()?;
+    if cache.len() >= MAX_CACHED_ARCHIVES && !cache.contains_key(path) {
+        cache.clear();
+    }
+    cache.insert(path.to_path_buf(), (stamp, Arc::clone(&files)));
+    drop(cache);
+    Ok(files)
+}
+
+fn lock_cache() -> Result<std::sync::MutexGuard<'static, ArchiveCache>, String> {
+    CACHE
+        .lock()
+        .map_err(|_| "failed to lock archive cache".to_string())
+}
+
+/// 读取 gzip 压缩的 tar 文件，返回其中的普通文件；文件名含路径分隔符的条目会被忽略。
+pub fn read_tar_gz(path: &Path) -> Result<ArchiveFiles, String> {
+    let file =
+        fs::File::open(path).map_err(|err| format!("failed to open {}: {err}", path.display()))?;
+    let mut raw = Vec::new();
+    GzDecoder::new(file)
+        .read_to_end(&mut raw)
+        .map_err(|err| format!("failed to decompress {}: {err}", path.display()))?;
+
+    let mut files = Vec::new();
+    let mut offset = 0;
+    while offset + BLOCK_SIZE <= raw.len() {
+        let header = &raw[offset..offset + BLOCK_SIZE];
+        if header.iter().all(|byte| *byte == 0) {
+            break;
+        }
+        let name_end = header[..NAME_LEN]
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(NAME_LEN);
+        let name = String::from_utf8_lossy(&header[..name_end]).to_string();
+        let size = parse_octal(&header[124..136])
+            .ok_or_else(|| format!("corrupt archive header in {}", path.display()))?;
+        let data_start = offset + BLOCK_SIZE;
+        let data_end = data_start + size;
+        if data_end > raw.len() {
+            return Err(format!("truncated archive {}", path.display()));
+        }
+        let is_regular = matches!(header[156], b'0' | 0);
+        if is_regular && !name.is_empty() && !name.contains(['/', '\\']) && name != ".." {
+            files.push((name, raw[data_start..data_end].to_vec()));
+        }
+        offset = data_end + padding_for(size);
+    }
+    Ok(files)
+}
+
+fn build_header(name: &str, size: usize) -> Result<[u8; BLOCK_SIZE], String> {
+    if name.len() >= NAME_LEN {
+        return Err(format!("file name too long for archive: {name}"));
+    }
+    let mut header = [0u8; BLOCK_SIZE];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{size:011o}\0").as_bytes());
+    let mtime = chrono::Utc::now().timestamp().max(0);
+    header[136..148].copy_from_slice(format!("{mtime:011o}\0").as_bytes());
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // 校验和按 chksum 字段全为空格计算。
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|byte| u32::from(*byte)).sum();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+    Ok(header)
+}
+
+fn parse_octal(field: &[u8]) -> Option<usize> {
+    let text = std::str::from_utf8(field).ok()?;
+    let digits = text.trim_matches(|ch: char| ch == '\0' || ch == ' ');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    usize::from_str_radix(digits, 8).ok()
+}
+
+const fn padding_for(len: usize) -> usize {
+    (BLOCK_SIZE - len % BLOCK_SIZE) % BLOCK_SIZE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_archive() -> PathBuf {
+        std::env::temp_dir().join(format!("echonote-archive-{}.tar.gz", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn long_names_round_trip_up_to_the_header_limit() {
+        let path = temp_archive();
+        let longest = format!("{}.md", "a".repeat(NAME_LEN - 4));
+        let files = vec![(longest, b"kept".to_vec())];
+        write_tar_gz(&path, &files).unwrap();
+        assert_eq!(read_tar_gz(&path).unwrap(), files);
+
+        // 超出 ustar 名称字段的文件名直接拒绝，而不是截断后写入。
+        let too_long = vec![(format!("{}.md", "a".repeat(NAME_LEN)), b"lost".to_vec())];
+        let err = write_tar_gz(&path, &too_long).unwrap_err();
+        assert!(err.contains("too long"), "{err}");
+        assert_eq!(read_tar_gz(&path).unwrap(), files);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn truncated_archives_are_rejected() {
+        let path = temp_archive();
+        let files = vec![("2024-01-01.md".to_string(), vec![b'x'; 2000])];
+        write_tar_gz(&path, &files).unwrap();
+
+        // gzip 数据被截断：解压失败。
+        let compressed = fs::read(&path).unwrap();
+        fs::write(&path, &compressed[..compressed.len() / 2]).unwrap();
+        let err = read_tar_gz(&path).unwrap_err();
+        assert!(err.contains("failed to decompress"), "{err}");
+
+        // gzip 完整但 tar 内容在条目数据中途结束。
+        let header = build_header("2024-01-01.md", 2000).unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&header).unwrap();
+        encoder.write_all(&[b'x'; 100]).unwrap();
+        fs::write(&path, encoder.finish().unwrap()).unwrap();
+        let err = read_tar_gz(&path).unwrap_err();
+        assert!(err.contains("truncated archive"), "{err}");
+        fs::remove_file(&path).unwrap();
+    }
+}
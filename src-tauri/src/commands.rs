@@ -1,18 +1,70 @@
 //! Tauri command entrypoints that bridge front-end invokes to the Rust services.
 
+use std::collections::HashMap;
+
+use serde_json::Value;
 use tauri::AppHandle;
 
-use crate::entry_service::{self, AiInvokePayload, AiModelListRequest, HeroGreetingRequest};
-use crate::models::DiaryEntry;
-use crate::security::secrets;
+use crate::ai_log;
+use crate::ai_migration;
+use crate::ai_prefs::{self, AiPreferences, EffectiveProviderContext};
+use crate::ai_provider::{AiChatRequest, ProviderCapabilities};
+use crate::app_prefs::{self, LegacyConfigHandling};
+use crate::entry_service::{
+    self, AiInvokePayload, AiModelListRequest, BatchSaveItem, BatchSaveResult, GreetingPrompts,
+    HeroGreeting, HeroGreetingRequest, JsonlExportResult, MetadataRebuildProgress, ProviderError,
+    ProviderModelList, ProviderUsage, SummaryTestResult,
+};
+use crate::hlc::{self, HlcStatus};
+use crate::models::{
+    AiLimits, ConfigPaths, DateReconcileReport, DayActivity, DiaryEntry, DuplicateEntry,
+    DuplicateMergeReport, DuplicateMergeStrategy, EntryDocumentValidation, EntryRevision,
+    HashAlgorithm, IntegrityReport, MigrationReport, MissingAiSummary, Page, RelatedEntry,
+    SearchHit, SentimentPoint, StorageInfo, TextCount, TokenEstimate, WipeReport,
+};
+use crate::security::secrets::{self, ApiKeySource, SecretRotationReport};
+
+const DEFAULT_AI_LOG_TAIL_LINES: usize = 200;
 
 #[tauri::command]
 pub async fn list_entries_by_month(
     app: AppHandle,
     year: u16,
     month: u8,
+    locale: Option<String>,
 ) -> Result<Vec<DiaryEntry>, String> {
-    entry_service::list_entries_by_month(app, year, month)
+    entry_service::list_entries_by_month(app, year, month, locale.as_deref())
+}
+
+#[tauri::command]
+pub async fn list_recent_entries(
+    app: AppHandle,
+    limit: Option<usize>,
+    locale: Option<String>,
+) -> Result<Vec<DiaryEntry>, String> {
+    entry_service::list_recent_entries(&app, limit, locale.as_deref())
+}
+
+#[tauri::command]
+pub async fn list_entries_without_ai_summary(
+    app: AppHandle,
+    limit: Option<usize>,
+) -> Result<Vec<MissingAiSummary>, String> {
+    entry_service::list_entries_without_ai_summary(&app, limit)
+}
+
+#[tauri::command]
+pub async fn related_entries(
+    app: AppHandle,
+    date: String,
+    limit: Option<usize>,
+) -> Result<Vec<RelatedEntry>, String> {
+    entry_service::related_entries(&app, &date, limit)
+}
+
+#[tauri::command]
+pub async fn year_activity(app: AppHandle, year: u16) -> Result<Vec<DayActivity>, String> {
+    entry_service::year_activity(&app, year)
 }
 
 #[tauri::command]
@@ -23,24 +75,321 @@ pub async fn get_entry_body_by_date(
     entry_service::get_entry_body_by_date(app, date)
 }
 
+#[tauri::command]
+pub async fn entry_exists(app: AppHandle, date: String) -> Result<bool, String> {
+    entry_service::entry_exists(&app, &date)
+}
+
+#[tauri::command]
+pub async fn add_read_root(app: AppHandle, path: String) -> Result<Vec<String>, String> {
+    entry_service::add_read_root(&app, &path)
+}
+
+#[tauri::command]
+pub async fn stream_month_bodies(app: AppHandle, year: u16, month: u8) -> Result<usize, String> {
+    entry_service::stream_month_bodies(&app, year, month)
+}
+
+#[tauri::command]
+pub async fn get_entry_path(app: AppHandle, date: String) -> Result<String, String> {
+    entry_service::get_entry_path(&app, &date)
+}
+
+#[tauri::command]
+pub async fn export_jsonl(
+    app: AppHandle,
+    from: Option<String>,
+    to: Option<String>,
+    target_path: String,
+    include_body: Option<bool>,
+) -> Result<JsonlExportResult, String> {
+    entry_service::export_jsonl(
+        &app,
+        from.as_deref(),
+        to.as_deref(),
+        &target_path,
+        include_body.unwrap_or(false),
+    )
+}
+
+#[tauri::command]
+pub async fn search_entries(
+    app: AppHandle,
+    query: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<Page<DiaryEntry>, String> {
+    entry_service::search_entries(&app, &query, offset, limit)
+}
+
+#[tauri::command]
+pub async fn search_month(
+    app: AppHandle,
+    year: u16,
+    month: u8,
+    query: String,
+) -> Result<Vec<SearchHit>, String> {
+    entry_service::search_month(&app, year, month, &query)
+}
+
+#[tauri::command]
+pub async fn cancel_export() -> Result<(), String> {
+    entry_service::cancel_export();
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn save_entry_by_date(
     app: AppHandle,
     date: String,
     body: String,
     ai: Option<AiInvokePayload>,
+    locale: Option<String>,
+    language: Option<String>,
+    expected_hash: Option<String>,
+) -> Result<DiaryEntry, String> {
+    entry_service::save_entry_by_date(
+        app,
+        date,
+        body,
+        ai,
+        locale.as_deref(),
+        language.as_deref(),
+        expected_hash.as_deref(),
+    )
+}
+
+#[tauri::command]
+pub async fn save_entries_batch(
+    app: AppHandle,
+    entries: Vec<BatchSaveItem>,
+    locale: Option<String>,
+) -> Result<Vec<BatchSaveResult>, String> {
+    entry_service::save_entries_batch(&app, entries, locale.as_deref())
+}
+
+#[tauri::command]
+pub async fn set_entry_language(
+    app: AppHandle,
+    date: String,
+    language: Option<String>,
+) -> Result<DiaryEntry, String> {
+    entry_service::set_entry_language(&app, &date, language.as_deref())
+}
+
+#[tauri::command]
+pub async fn set_entry_private(
+    app: AppHandle,
+    date: String,
+    private: bool,
+    locale: Option<String>,
+) -> Result<DiaryEntry, String> {
+    entry_service::set_entry_private(&app, &date, private, locale.as_deref())
+}
+
+#[tauri::command]
+pub async fn append_to_today(
+    app: AppHandle,
+    fragment: String,
+    ai: Option<AiInvokePayload>,
+    timezone: Option<String>,
+    locale: Option<String>,
 ) -> Result<DiaryEntry, String> {
-    entry_service::save_entry_by_date(app, date, body, ai)
+    entry_service::append_to_today(&app, &fragment, ai, timezone.as_deref(), locale.as_deref())
 }
 
 #[tauri::command]
 pub async fn invoke_generate_hero_greeting(
     app: AppHandle,
     request: HeroGreetingRequest,
-) -> Result<String, String> {
+) -> Result<HeroGreeting, String> {
     entry_service::generate_hero_greeting(&app, request).await
 }
 
+#[tauri::command]
+pub async fn invoke_generate_hero_greeting_stream(
+    app: AppHandle,
+    request: HeroGreetingRequest,
+) -> Result<HeroGreeting, String> {
+    entry_service::generate_hero_greeting_stream(&app, request).await
+}
+
+#[tauri::command]
+pub async fn preview_greeting_prompt(
+    app: AppHandle,
+    request: HeroGreetingRequest,
+) -> Result<GreetingPrompts, String> {
+    entry_service::preview_greeting_prompt(&app, &request)
+}
+
+#[tauri::command]
+pub async fn preview_context_summary(
+    app: AppHandle,
+    date: String,
+) -> Result<Option<String>, String> {
+    entry_service::preview_context_summary(&app, &date)
+}
+
+#[tauri::command]
+pub async fn rebuild_metadata(
+    app: AppHandle,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<MetadataRebuildProgress, String> {
+    entry_service::rebuild_metadata(&app, from.as_deref(), to.as_deref())
+}
+
+#[tauri::command]
+pub async fn verify_integrity(
+    app: AppHandle,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<IntegrityReport, String> {
+    entry_service::verify_integrity(&app, from.as_deref(), to.as_deref())
+}
+
+#[tauri::command]
+pub async fn set_body_compression(app: AppHandle, compress: bool) -> Result<usize, String> {
+    entry_service::set_body_compression(&app, compress)
+}
+
+#[tauri::command]
+pub async fn last_provider_error(provider_id: String) -> Result<Option<ProviderError>, String> {
+    entry_service::last_provider_error(&provider_id)
+}
+
+#[tauri::command]
+pub async fn get_limits(app: AppHandle) -> Result<AiLimits, String> {
+    entry_service::get_limits(&app)
+}
+
+#[tauri::command]
+pub async fn session_usage() -> Result<HashMap<String, ProviderUsage>, String> {
+    entry_service::session_usage()
+}
+
+#[tauri::command]
+pub async fn validate_base_url(provider_id: String, url: String) -> Result<String, String> {
+    entry_service::validate_base_url(&provider_id, &url)
+}
+
+#[tauri::command]
+pub async fn cleanup_empty_entries(
+    app: AppHandle,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<Vec<String>, String> {
+    entry_service::cleanup_empty_entries(&app, from.as_deref(), to.as_deref())
+}
+
+#[tauri::command]
+pub async fn count_text(text: String) -> TextCount {
+    entry_service::count_text(&text)
+}
+
+#[tauri::command]
+pub async fn estimate_tokens(text: String, model: String) -> TokenEstimate {
+    entry_service::estimate_tokens(&text, &model)
+}
+
+#[tauri::command]
+pub async fn validate_entry_document(text: String) -> Result<EntryDocumentValidation, String> {
+    entry_service::validate_entry_document(&text)
+}
+
+#[tauri::command]
+pub async fn find_duplicates(app: AppHandle) -> Result<Vec<DuplicateEntry>, String> {
+    entry_service::find_duplicates(&app)
+}
+
+#[tauri::command]
+pub async fn merge_duplicates(
+    app: AppHandle,
+    date: String,
+    strategy: DuplicateMergeStrategy,
+    dry_run: Option<bool>,
+) -> Result<DuplicateMergeReport, String> {
+    entry_service::merge_duplicates(&app, &date, strategy, dry_run.unwrap_or(true))
+}
+
+#[tauri::command]
+pub async fn archive_month(app: AppHandle, year: i32, month: u32) -> Result<usize, String> {
+    entry_service::archive_month(&app, year, month)
+}
+
+#[tauri::command]
+pub async fn unarchive_month(app: AppHandle, year: i32, month: u32) -> Result<usize, String> {
+    entry_service::unarchive_month(&app, year, month)
+}
+
+#[tauri::command]
+pub async fn reconcile_dates(
+    app: AppHandle,
+    year: i32,
+    month: u32,
+) -> Result<DateReconcileReport, String> {
+    entry_service::reconcile_dates(&app, year, month)
+}
+
+#[tauri::command]
+pub async fn storage_info(app: AppHandle) -> Result<StorageInfo, String> {
+    entry_service::storage_info(&app)
+}
+
+#[tauri::command]
+pub async fn set_hash_algorithm(app: AppHandle, algorithm: HashAlgorithm) -> Result<(), String> {
+    entry_service::set_hash_algorithm(&app, algorithm)
+}
+
+#[tauri::command]
+pub async fn set_revision_limit(app: AppHandle, limit: usize) -> Result<usize, String> {
+    entry_service::set_revision_limit(&app, limit)
+}
+
+#[tauri::command]
+pub async fn set_document_style(
+    app: AppHandle,
+    blank_lines: Option<usize>,
+    trailing_newline: bool,
+) -> Result<(), String> {
+    entry_service::set_document_style(&app, blank_lines, trailing_newline)
+}
+
+#[tauri::command]
+pub async fn list_entry_revisions(
+    app: AppHandle,
+    date: String,
+) -> Result<Vec<EntryRevision>, String> {
+    entry_service::list_entry_revisions(&app, &date)
+}
+
+#[tauri::command]
+pub async fn get_entry_revision(
+    app: AppHandle,
+    date: String,
+    revision: String,
+) -> Result<Option<String>, String> {
+    entry_service::get_entry_revision(&app, &date, &revision)
+}
+
+#[tauri::command]
+pub async fn restore_entry_revision(
+    app: AppHandle,
+    date: String,
+    revision: String,
+    locale: Option<String>,
+) -> Result<DiaryEntry, String> {
+    entry_service::restore_entry_revision(app, &date, &revision, locale.as_deref())
+}
+
+#[tauri::command]
+pub async fn set_filename_date_format(
+    app: AppHandle,
+    format: Option<String>,
+) -> Result<(), String> {
+    entry_service::set_filename_date_format(&app, format.as_deref())
+}
+
 #[tauri::command]
 pub async fn list_ai_models(
     app: AppHandle,
@@ -49,6 +398,162 @@ pub async fn list_ai_models(
     entry_service::list_ai_models(&app, request).await
 }
 
+#[tauri::command]
+pub async fn invoke_ai_chat_json(app: AppHandle, request: AiChatRequest) -> Result<Value, String> {
+    entry_service::invoke_ai_chat_json(&app, request).await
+}
+
+#[tauri::command]
+pub async fn test_summarize(
+    app: AppHandle,
+    provider_id: String,
+    sample_body: String,
+) -> Result<SummaryTestResult, String> {
+    entry_service::test_summarize(&app, &provider_id, &sample_body).await
+}
+
+#[tauri::command]
+pub async fn monthly_digest(
+    app: AppHandle,
+    year: u16,
+    month: u8,
+    provider_id: String,
+) -> Result<String, String> {
+    entry_service::monthly_digest(&app, year, month, &provider_id).await
+}
+
+#[tauri::command]
+pub async fn sentiment_trend(
+    app: AppHandle,
+    from: String,
+    to: String,
+    provider_id: Option<String>,
+) -> Result<Vec<SentimentPoint>, String> {
+    entry_service::sentiment_trend(&app, &from, &to, provider_id.as_deref()).await
+}
+
+#[tauri::command]
+pub async fn list_all_models(app: AppHandle) -> Result<HashMap<String, ProviderModelList>, String> {
+    entry_service::list_all_models(&app).await
+}
+
+#[tauri::command]
+pub async fn clear_model_cache(
+    app: AppHandle,
+    provider_id: String,
+    refetch: Option<bool>,
+) -> Result<Vec<String>, String> {
+    entry_service::clear_model_cache(&app, &provider_id, refetch.unwrap_or(false)).await
+}
+
+#[tauri::command]
+pub async fn clear_all_model_caches(app: AppHandle) -> Result<usize, String> {
+    entry_service::clear_all_model_caches(&app)
+}
+
+#[tauri::command]
+pub async fn set_offline_mode(app: AppHandle, enabled: bool) -> Result<bool, String> {
+    entry_service::set_offline_mode(&app, enabled)
+}
+
+#[tauri::command]
+pub async fn set_debug_logging(app: AppHandle, enabled: bool) -> Result<bool, String> {
+    entry_service::set_debug_logging(&app, enabled)
+}
+
+#[tauri::command]
+pub async fn read_ai_log(app: AppHandle, tail_lines: Option<usize>) -> Result<Vec<String>, String> {
+    ai_log::read_tail(&app, tail_lines.unwrap_or(DEFAULT_AI_LOG_TAIL_LINES))
+}
+
+#[tauri::command]
+pub async fn get_offline_mode(app: AppHandle) -> Result<bool, String> {
+    app_prefs::load_preferences(&app).map(|prefs| prefs.offline_mode)
+}
+
+#[tauri::command]
+pub async fn export_ai_preferences(app: AppHandle) -> Result<String, String> {
+    ai_prefs::export_preferences(&app)
+}
+
+#[tauri::command]
+pub async fn import_ai_preferences(app: AppHandle, json: String) -> Result<AiPreferences, String> {
+    ai_prefs::import_preferences(&app, &json)
+}
+
+#[tauri::command]
+pub async fn config_paths(app: AppHandle) -> Result<ConfigPaths, String> {
+    Ok(ai_migration::config_paths(&app))
+}
+
+#[tauri::command]
+pub async fn resolve_provider_context(
+    app: AppHandle,
+    provider_id: String,
+) -> Result<EffectiveProviderContext, String> {
+    ai_prefs::effective_provider_context(&app, provider_id.trim())
+}
+
+#[tauri::command]
+pub async fn provider_capabilities(
+    app: AppHandle,
+    provider_id: String,
+) -> Result<ProviderCapabilities, String> {
+    ai_prefs::resolve_capabilities(&app, provider_id.trim())
+}
+
+#[tauri::command]
+pub async fn set_model_alias(
+    app: AppHandle,
+    provider_id: String,
+    alias: String,
+    target: Option<String>,
+) -> Result<HashMap<String, String>, String> {
+    ai_prefs::set_model_alias(&app, &provider_id, &alias, target.as_deref())
+}
+
+#[tauri::command]
+pub async fn set_provider_hidden(
+    app: AppHandle,
+    provider_id: String,
+    hidden: bool,
+) -> Result<AiPreferences, String> {
+    ai_prefs::set_provider_hidden(&app, &provider_id, hidden)
+}
+
+#[tauri::command]
+pub async fn run_migration(app: AppHandle, force: Option<bool>) -> Result<MigrationReport, String> {
+    ai_migration::run_migration(&app, force.unwrap_or(false))
+}
+
+#[tauri::command]
+pub async fn set_legacy_config_handling(
+    app: AppHandle,
+    handling: LegacyConfigHandling,
+) -> Result<(), String> {
+    ai_migration::set_legacy_config_handling(&app, handling)
+}
+
+#[tauri::command]
+pub async fn hlc_status(app: AppHandle) -> Result<HlcStatus, String> {
+    hlc::status(&app)
+}
+
+#[tauri::command]
+pub async fn wipe_confirm_token() -> Result<String, String> {
+    entry_service::wipe_confirm_token()
+}
+
+#[tauri::command]
+pub async fn wipe_all_data(app: AppHandle, confirm_token: String) -> Result<WipeReport, String> {
+    entry_service::wipe_all_data(&app, &confirm_token)
+}
+
+#[tauri::command]
+pub async fn describe_hlc(hlc: String, timezone: Option<String>) -> Result<String, String> {
+    entry_service::hlc_to_local(&hlc, timezone.as_deref())
+}
+
 #[tauri::command]
 pub async fn store_api_secret(
     app: AppHandle,
@@ -67,7 +572,20 @@ pub async fn delete_api_secret(app: AppHandle, provider_id: String) -> Result<()
     secrets::delete_api_key(&app, &provider_id)
 }
 
+#[tauri::command]
+pub async fn rotate_secrets(app: AppHandle) -> Result<SecretRotationReport, String> {
+    secrets::rotate_encryption(&app)
+}
+
 #[tauri::command]
 pub async fn has_api_secret(app: AppHandle, provider_id: String) -> Result<bool, String> {
     secrets::has_api_key(&app, &provider_id)
 }
+
+#[tauri::command]
+pub async fn api_secret_source(
+    app: AppHandle,
+    provider_id: String,
+) -> Result<Option<ApiKeySource>, String> {
+    secrets::api_key_source(&app, &provider_id)
+}
@@ -1,38 +1,230 @@
 //! Diary domain services: storage, caching, and AI summary orchestration.
 
-use std::borrow::Cow;
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
-use chrono::{Duration, Local, NaiveDate, Utc};
+use chrono::{Datelike, Duration, Local, NaiveDate, Offset, Timelike, Utc};
+use chrono_tz::Tz;
 use once_cell::sync::{Lazy, OnceCell};
 use reqwest::Url;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tauri::{AppHandle, Emitter};
+use unicode_segmentation::UnicodeSegmentation;
 
+use crate::ai_log::{self, AiLogEntry};
 use crate::ai_prefs;
-use crate::ai_provider::{self, AiChatRequest, AiMessage};
-use crate::models::{DiaryEntry, EntryRecord};
+use crate::ai_provider::{self, AiChatRequest, AiChatResult, AiMessage};
+use crate::app_prefs;
+use crate::hlc;
+use crate::models::{
+    AiLimits, BodyEncoding, DateReconcileReport, DayActivity, DiaryEntry, DuplicateEntry,
+    DuplicateFile, DuplicateMergeReport, DuplicateMergeStrategy, EntryDocumentValidation,
+    EntryRecord, EntryRevision, HashAlgorithm, IntegrityReport, MissingAiSummary, Page,
+    RelatedEntry, SearchHit, SentimentPoint, StorageInfo, SummarySource, TextCount, TokenEstimate,
+    TokenEstimateMethod, WipeReport,
+};
 use crate::security::{device, secrets};
 use crate::storage::{self, StorageLayout};
 
 /// 内存缓存，Key 使用标准化后的 YYYY-MM-DD，以支持 get/list/save 的快速查询。
-static STORE: Lazy<Mutex<HashMap<String, EntryRecord>>> = Lazy::new(|| {
+///
+/// 使用读写锁：列表、正文读取等只读路径可以并发，仅写入时独占。
+static STORE: Lazy<RwLock<HashMap<String, EntryRecord>>> = Lazy::new(|| {
     let map = HashMap::new();
-    Mutex::new(map)
+    RwLock::new(map)
 });
 static STORAGE_LAYOUT: OnceCell<StorageLayout> = OnceCell::new();
+static STORAGE_INFO_CACHE: Lazy<Mutex<Option<(std::time::Instant, StorageInfo)>>> =
+    Lazy::new(|| Mutex::new(None));
+/// 本次运行期间各 Provider 的 token 用量累计，应用重启后自然清零。
+static SESSION_USAGE: Lazy<Mutex<HashMap<String, ProviderUsage>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+/// 各 Provider 最近一次 AI 调用失败的原因，仅保存在内存中，供设置页展示。
+static LAST_PROVIDER_ERRORS: Lazy<Mutex<HashMap<String, ProviderError>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+/// 月度回顾缓存：`provider:YYYY-MM` → (月度组合 hash, 回顾正文)，条目变化后自动失效。
+static MONTHLY_DIGEST_CACHE: Lazy<Mutex<HashMap<String, (String, String)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+/// 各 Provider 最近一次发起月度回顾请求的时间，用于限制请求频率。
+static MONTHLY_DIGEST_LAST_REQUEST: Lazy<Mutex<HashMap<String, std::time::Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+/// 各 Provider 最近一次试运行摘要的时间，避免反复点击测试按钮刷爆配额。
+static TEST_SUMMARY_LAST_REQUEST: Lazy<Mutex<HashMap<String, std::time::Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+/// 各 Provider 最近一次批量查询模型列表的时间，诊断页反复刷新时直接使用缓存。
+static MODEL_LIST_LAST_REQUEST: Lazy<Mutex<HashMap<String, std::time::Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+/// 串行化条目保存：`expected_hash` 比对与写入磁盘之间不能插入其他保存。
+static ENTRY_SAVE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+/// 清除全部数据前签发的确认令牌及其签发时间，使用一次后即失效。
+static WIPE_CONFIRM_TOKEN: Lazy<Mutex<Option<(String, std::time::Instant)>>> =
+    Lazy::new(|| Mutex::new(None));
+/// 情绪得分缓存：摘要的 BLAKE3 HASH → AI 给出的得分，摘要不变时重复计算无需再请求。
+static SENTIMENT_CACHE: Lazy<Mutex<HashMap<String, f32>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+/// 等待防抖或正在生成的后台摘要任务：日期 → 任务；同一天再次保存时中止旧任务。
+static PENDING_SUMMARY_REFRESH: Lazy<Mutex<RefreshRegistry<tauri::async_runtime::JoinHandle<()>>>> =
+    Lazy::new(|| Mutex::new(RefreshRegistry::default()));
+/// 导出取消标记：`cancel_export` 置位，导出循环在处理下一篇前检查。
+static EXPORT_CANCELLED: AtomicBool = AtomicBool::new(false);
+const WIPE_CONFIRM_TOKEN_TTL_SECS: u64 = 60;
 const ENTRY_METADATA_EVENT: &str = "entry-metadata-updated";
-const EMPTY_ENTRY_SUMMARY: &str = "空白日记";
-const AI_PENDING_SUMMARY: &str = "AI 摘要生成中...";
+const METADATA_REBUILD_EVENT: &str = "metadata-rebuild-progress";
+const EXPORT_PROGRESS_EVENT: &str = "export-progress";
+const EXPORT_DONE_EVENT: &str = "export-done";
+const HERO_GREETING_DELTA_EVENT: &str = "hero-greeting-delta";
+const ENTRY_BODY_EVENT: &str = "entry-body";
+const ENTRY_BODY_DONE_EVENT: &str = "entry-body-done";
+const AI_SUMMARY_RETRY_EVENT: &str = "ai-summary-retry";
+const AI_SUMMARY_FALLBACK_EVENT: &str = "ai-summary-fallback";
+// 占位文案的内置多语言映射：(locale, 空白日记, AI 摘要生成中)，与前端语言包保持一致。
+const PLACEHOLDER_TEXTS: [(&str, &str, &str); 4] = [
+    ("zh-Hans", "空白日记", "AI 摘要生成中..."),
+    ("zh-Hant", "空白日記", "AI 摘要生成中..."),
+    ("en", "Empty entry", "AI summary in progress..."),
+    ("ja", "空白の日記", "AI 要約を生成中..."),
+];
+/// 本地问候语词表：按时段（早/午/晚/深夜）与近 7 天记录数（无/少量/连续）组合。
+const LOCAL_GREETING_TEXTS: [(&str, [&str; 4], [&str; 3]); 4] = [
+    (
+        "zh-Hans",
+        ["早上好，", "下午好，", "晚上好，", "夜深了，"],
+        [
+            "今天从一句话开始记录吧。",
+            "继续写下今天的故事吧。",
+            "你已经连续记录好几天了，保持下去！",
+        ],
+    ),
+    (
+        "zh-Hant",
+        ["早安，", "午安，", "晚安，", "夜深了，"],
+        [
+            "今天從一句話開始記錄吧。",
+            "繼續寫下今天的故事吧。",
+            "你已經連續記錄好幾天了，保持下去！",
+        ],
+    ),
+    (
+        "en",
+        [
+            "Good morning!",
+            "Good afternoon!",
+            "Good evening!",
+            "Up late?",
+        ],
+        [
+            "Start today's page with a single line.",
+            "Pick up where you left off.",
+            "You've been writing all week, keep it going!",
+        ],
+    ),
+    (
+        "ja",
+        [
+            "おはようございます。",
+            "こんにちは。",
+            "こんばんは。",
+            "夜更かしですね。",
+        ],
+        [
+            "今日は一言から書き始めてみましょう。",
+            "今日の出来事も書き留めておきましょう。",
+            "毎日の記録が続いていますね。この調子で！",
+        ],
+    ),
+];
+const LOCAL_GREETING_WINDOW_DAYS: i64 = 7;
 const DATE_FORMAT: &str = "%Y-%m-%d";
+const OFFLINE_MODE_ERROR: &str = "offline mode enabled";
+const NOT_CONFIGURED_PREFIX: &str = "NOT_CONFIGURED:";
+const CONFLICT_PREFIX: &str = "CONFLICT:";
+const EPHEMERAL_MODE_ERROR: &str = "ephemeral mode: entries are not stored on disk";
 // 软上限：在内存中保留的本文+摘要记录数量，避免长时间运行占用过大内存。
 const MAX_STORE_ENTRIES: usize = 500;
-const GREETING_MAX_CONTEXT_ENTRIES: usize = 30;
 const GREETING_MAX_SUMMARY_LENGTH: usize = 180;
 const GREETING_MAX_TOKENS: u32 = 80;
+const DEFAULT_PAGE_LIMIT: usize = 50;
+const DEFAULT_RELATED_LIMIT: usize = 5;
+// 搜索片段在命中位置前后各保留的字符数。
+const SEARCH_SNIPPET_CONTEXT_CHARS: usize = 40;
+// 相同 Emoji 视作共享"标签"，在关键词得分之外额外加分。
+const RELATED_EMOJI_BONUS: f64 = 0.2;
+const RELATED_STOP_WORDS: [&str; 24] = [
+    "the", "and", "for", "with", "that", "this", "was", "were", "are", "but", "not", "you", "your",
+    "have", "has", "had", "from", "they", "them", "then", "than", "what", "when", "today",
+];
+const MAX_PAGE_LIMIT: usize = 200;
+const LIST_ALL_MODELS_TIMEOUT_SECS: u64 = 20;
+const LIST_ALL_MODELS_MIN_INTERVAL_SECS: u64 = 30;
+// 存储占用信息的缓存时长，避免设置面板反复遍历目录。
+const STORAGE_INFO_CACHE_SECS: u64 = 30;
+// 月度回顾的输入上限（字符数），条目过多时只取前面的摘要并注明省略数量。
+const MONTHLY_DIGEST_MAX_INPUT_CHARS: usize = 6000;
+const MONTHLY_DIGEST_MAX_TOKENS: u32 = 600;
+// 同一 Provider 两次回顾请求的最小间隔，命中缓存不受限制。
+const MONTHLY_DIGEST_MIN_INTERVAL_SECS: u64 = 10;
+const TEST_SUMMARY_MIN_INTERVAL_SECS: u64 = 5;
+// 情绪趋势一次最多覆盖的天数，保证批量请求的输入输出都在合理范围内。
+const SENTIMENT_MAX_RANGE_DAYS: i64 = 366;
+const SENTIMENT_TOKENS_PER_ENTRY: u32 = 16;
+const SENTIMENT_BASE_MAX_TOKENS: u32 = 64;
+const SENTIMENT_POSITIVE_WORDS: [&str; 24] = [
+    "happy",
+    "glad",
+    "great",
+    "good",
+    "love",
+    "fun",
+    "excited",
+    "relaxed",
+    "calm",
+    "proud",
+    "grateful",
+    "enjoyed",
+    "wonderful",
+    "success",
+    "开心",
+    "快乐",
+    "高兴",
+    "满足",
+    "放松",
+    "顺利",
+    "喜欢",
+    "感恩",
+    "幸福",
+    "期待",
+];
+const SENTIMENT_NEGATIVE_WORDS: [&str; 24] = [
+    "sad",
+    "tired",
+    "angry",
+    "bad",
+    "stress",
+    "stressed",
+    "anxious",
+    "worried",
+    "lonely",
+    "sick",
+    "upset",
+    "frustrated",
+    "awful",
+    "failed",
+    "难过",
+    "伤心",
+    "焦虑",
+    "疲惫",
+    "生气",
+    "烦",
+    "失望",
+    "压力",
+    "孤独",
+    "累",
+];
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct AiInvokePayload {
@@ -41,7 +233,36 @@ pub struct AiInvokePayload {
     pub prompt: Option<String>,
     #[serde(rename = "maxTokens")]
     pub max_tokens: Option<u32>,
+    /// 非摘要流程使用的温度，摘要流程不使用该值。
     pub temperature: Option<f32>,
+    /// 覆盖本次摘要的温度，缺省时使用 Provider 的 `summary_temperature`。
+    #[serde(default, rename = "summaryTemperature")]
+    pub summary_temperature: Option<f32>,
+    /// 本次保存跳过 AI，强制走本地摘要，但保留已有的 AI 摘要。
+    #[serde(default, rename = "skipAi")]
+    pub skip_ai: bool,
+    /// 设置条目的私密标记并写入 frontmatter；缺省时沿用条目已有的设置。
+    #[serde(default)]
+    pub private: Option<bool>,
+}
+
+/// 批量保存中的单篇日记。
+#[derive(Debug, Deserialize)]
+pub struct BatchSaveItem {
+    pub date: String,
+    pub body: String,
+    #[serde(default)]
+    pub ai: Option<AiInvokePayload>,
+}
+
+/// 批量保存中单篇的结果：成功时返回摘要，失败时返回错误信息。
+#[derive(Debug, Serialize)]
+pub struct BatchSaveResult {
+    pub date: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry: Option<DiaryEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -67,6 +288,164 @@ pub struct HeroGreetingRequest {
     timezone: Option<String>,
 }
 
+/// 派生元数据重建进度，同时作为命令的最终返回值。
+#[derive(Debug, Clone, Serialize)]
+pub struct MetadataRebuildProgress {
+    pub processed: usize,
+    pub total: usize,
+    pub updated: usize,
+    /// 正文与 frontmatter HASH 不一致（多为外部编辑）的日期，HASH 保持原值留待用户处理。
+    pub divergent: Vec<String>,
+}
+
+/// 问候语结果；`is_local` 为 true 表示 AI 不可用时由本地词表生成。
+#[derive(Debug, Clone, Serialize)]
+pub struct HeroGreeting {
+    pub greeting: String,
+    #[serde(rename = "isLocal")]
+    pub is_local: bool,
+}
+
+/// 摘要试运行结果：解析后的摘要与 Emoji，以及 Provider 返回的原始文本。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SummaryTestResult {
+    pub summary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+    pub truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub raw: String,
+}
+
+/// `entry-metadata-updated` 事件的负载：后台摘要写回磁盘后发送。
+///
+/// 在 `DiaryEntry` 的全部字段（`date`、`hash`、`aiSummary` 等）之外附带 `previousHash`，
+/// 即触发本次摘要的保存所返回的 HASH。前端仅当本地缓存的 HASH 与之相同时才应用更新，
+/// 多次编辑并发时可据此丢弃过期结果。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryMetadataUpdated {
+    #[serde(flatten)]
+    pub entry: DiaryEntry,
+    pub previous_hash: String,
+}
+
+/// 后台摘要重试/回退事件的负载，`error` 已脱敏。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiSummaryAttempt {
+    pub date: String,
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub error: String,
+}
+
+/// JSON Lines 导出结果：成功写入的条目数与写入错误标记的条目数。
+///
+/// `cancelled` 为 true 时导出被用户中止，未完成的文件已删除。
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonlExportResult {
+    pub written: usize,
+    pub failed: usize,
+    pub cancelled: bool,
+}
+
+/// `stream_month_bodies` 逐篇推送的正文事件。
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryBody {
+    pub date: String,
+    pub body: String,
+}
+
+/// 某月正文推送完毕的事件：成功推送与读取失败的条目数。
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryBodyDone {
+    pub year: u16,
+    pub month: u8,
+    pub emitted: usize,
+    pub failed: usize,
+}
+
+/// 导出进度事件：已处理/总条目数与当前处理的日期。
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportProgress {
+    pub processed: usize,
+    pub total: usize,
+    pub date: String,
+}
+
+/// 导出完成事件：目标文件路径与大小（字节）。
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportDone {
+    pub path: String,
+    pub bytes: u64,
+    pub written: usize,
+    pub failed: usize,
+}
+
+/// 保存冲突的详情：错误字符串形如 `CONFLICT:{json}`，前端据此取得磁盘上的当前正文进行合并。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SaveConflict {
+    date: String,
+    current_hash: String,
+    current_body: String,
+}
+
+#[derive(Serialize)]
+struct JsonlExportLine<'a> {
+    #[serde(flatten)]
+    entry: &'a DiaryEntry,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<&'a str>,
+}
+
+/// 流式问候语的增量文本，仅包含 `greeting` 字段新解析出的字符。
+#[derive(Debug, Clone, Serialize)]
+pub struct HeroGreetingDelta {
+    pub text: String,
+}
+
+/// 问候语的系统/用户提示词，生成与预览共用。
+#[derive(Debug, Clone, Serialize)]
+pub struct GreetingPrompts {
+    #[serde(rename = "systemPrompt")]
+    pub system_prompt: String,
+    #[serde(rename = "userPrompt")]
+    pub user_prompt: String,
+}
+
+/// 批量查询模型列表时单个 Provider 的结果，失败时 `error` 有值。
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProviderModelList {
+    pub models: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 单个 Provider 在本次会话中的累计用量。
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProviderUsage {
+    pub requests: u64,
+    #[serde(rename = "promptTokens")]
+    pub prompt_tokens: u64,
+    #[serde(rename = "completionTokens")]
+    pub completion_tokens: u64,
+    #[serde(rename = "totalTokens")]
+    pub total_tokens: u64,
+}
+
+/// Provider 最近一次失败的 AI 调用；`error` 已脱敏，`timestamp` 为 Unix 毫秒时间戳。
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderError {
+    /// 失败的流程：`chat`、`stream` 或 `summary`
+    pub operation: String,
+    pub error: String,
+    pub timestamp: i64,
+}
+
 /// 列出指定年月的日记条目摘要（仅 frontmatter，不含正文）
 ///
 /// 与前端 `list_entries_by_month(year, month)` 对应。
@@ -74,10 +453,12 @@ pub struct HeroGreetingRequest {
 /// 参数：
 /// - year: 年份（如 2025）
 /// - month: 月份（1-12，自然月）
+/// - locale: 界面语言，用于本地化占位摘要
 pub fn list_entries_by_month(
     app: AppHandle,
     year: u16,
     month: u8,
+    locale: Option<&str>,
 ) -> Result<Vec<DiaryEntry>, String> {
     // 校验月份范围，避免前端或调用者传入非法参数后导致日期解析 panic。
     if !(1..=12).contains(&month) {
@@ -88,7 +469,153 @@ pub fn list_entries_by_month(
     let month = month as u32;
 
     let layout = storage_layout(&app)?;
-    load_month_into_store(&layout, year, month)
+    let placeholders = Placeholders::for_locale(locale);
+    let mut entries = load_month_into_store(&layout, year, month)?;
+    for entry in &mut entries {
+        placeholders.localize(entry);
+    }
+    Ok(entries)
+}
+
+/// 获取最近的 N 篇日记元数据（按日期倒序），不限定月份。
+///
+/// 从最新的月份目录开始逐月向前读取 frontmatter，凑满 `limit` 条即停止。
+///
+/// 参数：
+/// - limit: 返回条数，缺省为 50，最大 200
+/// - locale: 界面语言，决定占位摘要的文案
+pub fn list_recent_entries(
+    app: &AppHandle,
+    limit: Option<usize>,
+    locale: Option<&str>,
+) -> Result<Vec<DiaryEntry>, String> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+    let layout = storage_layout(app)?;
+    let placeholders = Placeholders::for_locale(locale);
+
+    let mut entries = Vec::with_capacity(limit);
+    for (year, month) in storage::list_populated_months(&layout)? {
+        let mut month_entries = load_month_into_store(&layout, year, month)?;
+        month_entries.reverse();
+        entries.extend(month_entries.into_iter().take(limit - entries.len()));
+        if entries.len() >= limit {
+            break;
+        }
+    }
+    for entry in &mut entries {
+        placeholders.localize(entry);
+    }
+    Ok(entries)
+}
+
+/// 按日期倒序列出缺少 AI 摘要的条目：摘要缺失、为空、仍是生成中占位符，或来源为本地截断。
+///
+/// 旧条目没有记录摘要来源，只要摘要不是占位符就视为已有 AI 摘要。只读取 frontmatter，不加载正文，也不写入内存缓存。
+///
+/// 参数：
+/// - limit: 返回条数，缺省为 50，最大 200
+pub fn list_entries_without_ai_summary(
+    app: &AppHandle,
+    limit: Option<usize>,
+) -> Result<Vec<MissingAiSummary>, String> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+    let layout = storage_layout(app)?;
+
+    let mut missing = Vec::with_capacity(limit);
+    for (year, month) in storage::list_populated_months(&layout)? {
+        let mut summaries: Vec<DiaryEntry> = storage::load_month_entries(&layout, year, month)?
+            .into_iter()
+            .map(|record| record.summary().clone())
+            .collect();
+        summaries.sort_by(|a, b| b.date.cmp(&a.date));
+        for summary in summaries.into_iter().filter(|summary| !summary.private) {
+            let text = summary
+                .ai_summary
+                .as_deref()
+                .map(str::trim)
+                .filter(|text| !text.is_empty() && !Placeholders::is_ai_pending(text));
+            if text.is_some() && summary.summary_source != Some(SummarySource::Local) {
+                continue;
+            }
+            missing.push(MissingAiSummary {
+                local_summary: text.map(str::to_string),
+                date: summary.date,
+            });
+            if missing.len() >= limit {
+                return Ok(missing);
+            }
+        }
+    }
+    Ok(missing)
+}
+
+/// 不依赖向量嵌入，按关键词重合度查找与指定日期相关的其他日记。
+///
+/// 仅读取目标条目的正文，其余条目只使用 frontmatter 中的摘要参与打分，
+/// 以保证在大量日记下开销可控。
+///
+/// 参数：
+/// - date: 目标日期（YYYY-MM-DD）
+/// - limit: 返回条数，缺省为 5，最大 200
+pub fn related_entries(
+    app: &AppHandle,
+    date: &str,
+    limit: Option<usize>,
+) -> Result<Vec<RelatedEntry>, String> {
+    let normalized_date = normalize_date(date)?;
+    let limit = limit
+        .unwrap_or(DEFAULT_RELATED_LIMIT)
+        .clamp(1, MAX_PAGE_LIMIT);
+    let layout = storage_layout(app)?;
+    let Some(target) = storage::load_entry(&layout, &normalized_date)? else {
+        return Err(format!("entry {normalized_date} not found"));
+    };
+
+    let target_summary = target.summary();
+    let mut target_text = target.body().to_string();
+    if let Some(summary) = target_summary.ai_summary.as_deref() {
+        target_text.push('\n');
+        target_text.push_str(summary);
+    }
+    let target_terms = keyword_terms(&target_text);
+    let target_emoji = target_summary.emoji.as_deref().map(str::trim);
+
+    let mut scored = Vec::new();
+    for (year, month) in storage::list_populated_months(&layout)? {
+        for record in storage::load_month_entries(&layout, year, month)? {
+            let entry = record.summary();
+            if entry.date == normalized_date || entry.private {
+                continue;
+            }
+            let summary_text = entry
+                .ai_summary
+                .as_deref()
+                .filter(|text| {
+                    !Placeholders::is_ai_pending(text) && !Placeholders::is_empty_entry(text)
+                })
+                .unwrap_or_default();
+            let mut score = keyword_overlap(&target_terms, &keyword_terms(summary_text));
+            if target_emoji.is_some_and(|emoji| !emoji.is_empty())
+                && entry.emoji.as_deref().map(str::trim) == target_emoji
+            {
+                score += RELATED_EMOJI_BONUS;
+            }
+            if score > 0.0 {
+                scored.push(RelatedEntry {
+                    entry: entry.clone(),
+                    score,
+                });
+            }
+        }
+    }
+
+    scored.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| b.entry.date.cmp(&a.entry.date))
+    });
+    scored.truncate(limit);
+    Ok(scored)
 }
 
 /// 按日期获取日记正文内容
@@ -101,33 +628,264 @@ pub fn get_entry_body_by_date(app: AppHandle, date: String) -> Result<Option<Str
     // 统一日期格式为 %Y-%m-%d，方便与 STORE 中的 key 对比。
     let normalized_date = normalize_date(&date)?;
     let layout = storage_layout(&app)?;
+    load_body_cached(&layout, normalized_date)
+}
 
+/// 优先复用缓存中的正文，未命中时从磁盘读取并写入缓存。
+fn load_body_cached(layout: &StorageLayout, date: String) -> Result<Option<String>, String> {
     if let Some(body) = {
-        let store = STORE
-            .lock()
-            .map_err(|_| "failed to lock in-memory store".to_string())?;
-        store.get(&normalized_date).and_then(|record| {
+        let store = read_store()?;
+        store.get(&date).and_then(|record| {
             // 仅当缓存正文与摘要内的 hash 一致时复用，避免月度索引只加载 frontmatter 导致正文为空。
             let cached_body = record.body();
-            (fingerprint(cached_body) == record.summary().hash).then(|| cached_body.to_string())
+            record
+                .summary()
+                .body_matches(cached_body)
+                .then(|| cached_body.to_string())
         })
     } {
         return Ok(Some(body));
     }
 
-    if let Some(record) = storage::load_entry(&layout, &normalized_date)? {
+    if let Some(record) = storage::load_entry(layout, &date)? {
         let body = record.body().to_string();
-        let mut store = STORE
-            .lock()
-            .map_err(|_| "failed to lock in-memory store".to_string())?;
-        store.insert(normalized_date, record);
-        prune_store_capacity(&mut store);
+        cache_record(date, record)?;
         return Ok(Some(body));
     }
 
     Ok(None)
 }
 
+/// 逐篇读取某月全部条目的正文并通过 `entry-body` 事件推送，结束后发送 `entry-body-done`。
+///
+/// 供前端离线全文检索增量建立索引，避免一次性返回整月正文；已缓存的正文直接复用。
+/// 单篇读取失败时跳过并计入 `failed`，返回成功推送的条目数。
+pub fn stream_month_bodies(app: &AppHandle, year: u16, month: u8) -> Result<usize, String> {
+    if !(1..=12).contains(&month) {
+        return Err(format!("month must be between 1 and 12 (got {month})"));
+    }
+    let layout = storage_layout(app)?;
+    let mut entries = load_month_into_store(&layout, i32::from(year), u32::from(month))?;
+    entries.sort_by(|a, b| a.date.cmp(&b.date));
+
+    let mut done = EntryBodyDone {
+        year,
+        month,
+        emitted: 0,
+        failed: 0,
+    };
+    for entry in entries {
+        let body = match load_body_cached(&layout, entry.date.clone()) {
+            Ok(Some(body)) => body,
+            Ok(None) => continue,
+            Err(err) => {
+                eprintln!("[EchoNote] failed to read body of {}: {err}", entry.date);
+                done.failed += 1;
+                continue;
+            }
+        };
+        let event = EntryBody {
+            date: entry.date,
+            body,
+        };
+        if let Err(err) = app.emit(ENTRY_BODY_EVENT, &event) {
+            eprintln!("[EchoNote] failed to emit entry body: {err}");
+        }
+        done.emitted += 1;
+    }
+    if let Err(err) = app.emit(ENTRY_BODY_DONE_EVENT, &done) {
+        eprintln!("[EchoNote] failed to emit entry body done: {err}");
+    }
+    Ok(done.emitted)
+}
+
+/// 判断某天是否已有日记：先查内存缓存，再查磁盘，不读取也不缓存正文。
+pub fn entry_exists(app: &AppHandle, date: &str) -> Result<bool, String> {
+    let date = parse_date(date)?;
+    if read_store()?.contains_key(&date.format(DATE_FORMAT).to_string()) {
+        return Ok(true);
+    }
+    let layout = storage_layout(app)?;
+    storage::entry_exists(&layout, date)
+}
+
+/// 按关键字全文搜索日记（正文与摘要，不区分大小写），按日期倒序分页返回。
+///
+/// 先用缓存中的摘要判断命中，未命中时才通过正文缓存读取正文；只为当前页取回条目元数据。
+///
+/// 参数：
+/// - query: 搜索关键字，去除首尾空白后不可为空
+/// - offset: 跳过的条数，默认 0
+/// - limit: 每页条数，默认 50，最大 200
+pub fn search_entries(
+    app: &AppHandle,
+    query: &str,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<Page<DiaryEntry>, String> {
+    let needle = query.trim().to_lowercase();
+    if needle.is_empty() {
+        return Err("search query must not be empty".to_string());
+    }
+    let layout = storage_layout(app)?;
+
+    let cached_ai_summary = |date: &str| -> Result<Option<String>, String> {
+        Ok(read_store()?
+            .get(date)
+            .and_then(|record| record.summary().ai_summary.clone()))
+    };
+    let mut matched_dates = Vec::new();
+    for date in storage::list_entry_dates(&layout)?.into_iter().rev() {
+        let date_str = date.format(DATE_FORMAT).to_string();
+        let summary_hit = cached_ai_summary(&date_str)?
+            .is_some_and(|text| search_snippet(&text, &needle).is_some());
+        if summary_hit {
+            matched_dates.push(date_str);
+            continue;
+        }
+        let Some(body) = load_body_cached(&layout, date_str.clone())? else {
+            continue;
+        };
+        let ai_summary = cached_ai_summary(&date_str)?;
+        if find_search_hit(&body, ai_summary.as_deref(), &needle).is_some() {
+            matched_dates.push(date_str);
+        }
+    }
+
+    let limit = limit
+        .filter(|value| *value > 0)
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .min(MAX_PAGE_LIMIT);
+    let page = Page::slice(matched_dates, offset.unwrap_or(0), limit);
+    let mut items = Vec::with_capacity(page.items.len());
+    for date in &page.items {
+        if let Some(summary) = load_entry_summary(&layout, date)? {
+            items.push(summary);
+        }
+    }
+    Ok(Page {
+        items,
+        total: page.total,
+        has_more: page.has_more,
+    })
+}
+
+/// 在指定月份内搜索（正文与摘要，不区分大小写），按日期倒序返回命中条目及上下文片段。
+///
+/// 只读取该月的条目，正文优先复用内存缓存，比全量 `search_entries` 轻量得多。
+pub fn search_month(
+    app: &AppHandle,
+    year: u16,
+    month: u8,
+    query: &str,
+) -> Result<Vec<SearchHit>, String> {
+    if !(1..=12).contains(&month) {
+        return Err(format!("month must be between 1 and 12 (got {month})"));
+    }
+    let needle = query.trim().to_lowercase();
+    if needle.is_empty() {
+        return Err("search query must not be empty".to_string());
+    }
+    let layout = storage_layout(app)?;
+
+    let mut hits = Vec::new();
+    for entry in load_month_into_store(&layout, i32::from(year), u32::from(month))?
+        .into_iter()
+        .rev()
+    {
+        let body = load_body_cached(&layout, entry.date.clone())?.unwrap_or_default();
+        if let Some((snippet, highlight_start, highlight_length)) =
+            find_search_hit(&body, entry.ai_summary.as_deref(), &needle)
+        {
+            hits.push(SearchHit {
+                entry,
+                snippet,
+                highlight_start,
+                highlight_length,
+            });
+        }
+    }
+    Ok(hits)
+}
+
+/// 依次在正文与 AI 摘要中查找小写化的关键字，返回第一处命中的片段。
+fn find_search_hit(
+    body: &str,
+    ai_summary: Option<&str>,
+    needle: &str,
+) -> Option<(String, usize, usize)> {
+    search_snippet(body, needle)
+        .or_else(|| ai_summary.and_then(|text| search_snippet(text, needle)))
+}
+
+/// 不区分大小写地定位 `needle`（须已小写化），返回 (片段, 命中起始字符, 命中字符数)。
+///
+/// 逐字符小写化并记录偏移，保证小写后长度变化的字符也能映射回原文位置。
+fn search_snippet(text: &str, needle: &str) -> Option<(String, usize, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut lowered = String::with_capacity(text.len());
+    let mut offsets = Vec::with_capacity(chars.len());
+    for ch in &chars {
+        offsets.push(lowered.len());
+        lowered.extend(ch.to_lowercase());
+    }
+    let found = lowered.find(needle)?;
+    let start = offsets.partition_point(|offset| *offset <= found) - 1;
+    let end = offsets.partition_point(|offset| *offset < found + needle.len());
+
+    let from = start.saturating_sub(SEARCH_SNIPPET_CONTEXT_CHARS);
+    let to = (end + SEARCH_SNIPPET_CONTEXT_CHARS).min(chars.len());
+    let mut snippet = String::new();
+    if from > 0 {
+        snippet.push('…');
+    }
+    snippet.extend(
+        chars[from..to]
+            .iter()
+            .map(|ch| if ch.is_whitespace() { ' ' } else { *ch }),
+    );
+    if to < chars.len() {
+        snippet.push('…');
+    }
+    Some((snippet, start - from + usize::from(from > 0), end - start))
+}
+
+/// 返回指定年份每一天的活跃度（是否有日记、字数、心情），用于年度热力图。
+///
+/// 仅读取 frontmatter，不加载正文，也不写入内存缓存。
+///
+/// 参数：
+/// - year: 年份（如 2025）
+pub fn year_activity(app: &AppHandle, year: u16) -> Result<Vec<DayActivity>, String> {
+    let year = i32::from(year);
+    let layout = storage_layout(app)?;
+
+    let mut entries: HashMap<String, DiaryEntry> = HashMap::new();
+    for month in 1..=12 {
+        for record in storage::load_month_entries(&layout, year, month)? {
+            let summary = record.summary().clone();
+            entries.insert(summary.date.clone(), summary);
+        }
+    }
+
+    let start =
+        NaiveDate::from_ymd_opt(year, 1, 1).ok_or_else(|| format!("invalid year {year}"))?;
+    Ok(start
+        .iter_days()
+        .take_while(|date| date.year() == year)
+        .map(|date| {
+            let date_str = date.format(DATE_FORMAT).to_string();
+            let entry = entries.remove(&date_str);
+            DayActivity {
+                has_entry: entry.is_some(),
+                word_count: entry.as_ref().and_then(|e| e.word_count),
+                mood: entry.and_then(|e| e.emoji),
+                date: date_str,
+            }
+        })
+        .collect())
+}
+
 /// 根据日期保存/更新日记内容
 ///
 /// 与前端 `saveEntryByDate(date, body)` 对应，返回最新的摘要信息。
@@ -135,179 +893,2167 @@ pub fn get_entry_body_by_date(app: AppHandle, date: String) -> Result<Option<Str
 /// 参数：
 /// - date: 日期（YYYY-MM-DD）
 /// - body: 正文内容（Markdown）
+/// - locale: 界面语言，决定占位摘要的文案
+/// - language: 手动指定的条目语言，提供时覆盖自动检测并持久化
+/// - `expected_hash`: 编辑器载入时的正文 hash（新条目传空串）；与当前存储不一致时拒绝保存，
+///   返回 `CONFLICT:{json}` 错误并附带当前正文，避免静默覆盖同步或其他窗口写入的内容。
+///   为 None 时不做检查
 pub fn save_entry_by_date(
     app: AppHandle,
     date: String,
     body: String,
     ai: Option<AiInvokePayload>,
+    locale: Option<&str>,
+    language: Option<&str>,
+    expected_hash: Option<&str>,
 ) -> Result<DiaryEntry, String> {
-    let layout = storage_layout(&app)?;
-    let normalized_date = normalize_date(&date)?;
+    let ctx = SaveContext::prepare(&app, locale)?;
+    let save_guard = lock_entry_save()?;
+    let saved = persist_entry(&app, &ctx, &date, body, ai, language, expected_hash)?;
+    cache_saved_entries(std::slice::from_ref(&saved))?;
+    drop(save_guard);
 
-    let existing_summary = {
-        let store = STORE
-            .lock()
-            .map_err(|_| "failed to lock in-memory store".to_string())?;
-        store
-            .get(&normalized_date)
-            .map(|record| record.summary().clone())
-    };
+    finish_saved_entry(&app, &ctx, saved)
+}
 
-    let ai_payload = ai.and_then(sanitize_ai_payload);
-    let ai_summary_text = if ai_payload.is_some() {
-        AI_PENDING_SUMMARY.to_string()
-    } else {
-        summarize_body(&body).unwrap_or_else(|| EMPTY_ENTRY_SUMMARY.to_string())
-    };
+/// 将已写盘的条目写入缓存并按容量淘汰；调用方须仍持有保存锁。
+fn cache_saved_entries(saved: &[SavedEntry]) -> Result<(), String> {
+    let mut store = write_store()?;
+    for entry in saved {
+        cache_saved_entry(&mut store, entry);
+    }
+    prune_store_capacity(&mut store);
+    drop(store);
+    Ok(())
+}
+
+/// 一次保存多篇日记（如编辑器失焦时自动保存多个草稿），按输入顺序返回每篇的结果。
+///
+/// 单篇失败只记录在对应结果中，不影响其他条目；全部写盘后只获取一次缓存写锁。
+pub fn save_entries_batch(
+    app: &AppHandle,
+    entries: Vec<BatchSaveItem>,
+    locale: Option<&str>,
+) -> Result<Vec<BatchSaveResult>, String> {
+    let ctx = SaveContext::prepare(app, locale)?;
+    let save_guard = lock_entry_save()?;
+    let outcomes: Vec<(String, Result<SavedEntry, String>)> = entries
+        .into_iter()
+        .map(|item| {
+            let outcome = persist_entry(app, &ctx, &item.date, item.body, item.ai, None, None);
+            (item.date, outcome)
+        })
+        .collect();
+
+    {
+        let mut store = write_store()?;
+        for saved in outcomes
+            .iter()
+            .filter_map(|(_, outcome)| outcome.as_ref().ok())
+        {
+            cache_saved_entry(&mut store, saved);
+        }
+        prune_store_capacity(&mut store);
+    }
+    drop(save_guard);
+
+    Ok(outcomes
+        .into_iter()
+        .map(|(date, outcome)| {
+            match outcome.and_then(|saved| finish_saved_entry(app, &ctx, saved)) {
+                Ok(entry) => BatchSaveResult {
+                    date,
+                    entry: Some(entry),
+                    error: None,
+                },
+                Err(err) => BatchSaveResult {
+                    date,
+                    entry: None,
+                    error: Some(err),
+                },
+            }
+        })
+        .collect())
+}
+
+/// 保存流程共用的存储布局、写入选项与占位文案，批量保存时只解析一次。
+struct SaveContext {
+    layout: StorageLayout,
+    write_options: storage::WriteOptions,
+    placeholders: Placeholders,
+}
+
+impl SaveContext {
+    fn prepare(app: &AppHandle, locale: Option<&str>) -> Result<Self, String> {
+        Ok(Self {
+            layout: storage_layout(app)?,
+            write_options: app_prefs::write_options(app)?,
+            placeholders: Placeholders::for_locale(locale),
+        })
+    }
+}
+
+/// 已写盘、待更新缓存的条目；`ai_payload` 存在时需要在后台生成摘要。
+struct SavedEntry {
+    date: String,
+    summary: DiaryEntry,
+    body: String,
+    ai_payload: Option<AiInvokePayload>,
+}
+
+/// 计算摘要元数据并写入磁盘，不触碰内存缓存。
+///
+/// 调用方须持有 `lock_entry_save` 直到缓存更新完毕，`expected_hash` 比对才不会与其他保存交错。
+fn persist_entry(
+    app: &AppHandle,
+    ctx: &SaveContext,
+    date: &str,
+    body: String,
+    ai: Option<AiInvokePayload>,
+    language: Option<&str>,
+    expected_hash: Option<&str>,
+) -> Result<SavedEntry, String> {
+    let SaveContext {
+        layout,
+        write_options,
+        placeholders,
+    } = ctx;
+    let normalized_date = normalize_date(date)?;
+    let language = language.map(normalize_language_code).transpose()?.flatten();
 
-    let summary = build_summary(
-        &app,
+    // 缓存未命中时回落到磁盘，确保 hash 比对与 HLC 沿用不受缓存淘汰影响。
+    let existing_summary = load_entry_summary(layout, &normalized_date)?;
+    if let Some(expected) = expected_hash.map(str::trim) {
+        let current_hash = existing_summary
+            .as_ref()
+            .map(|entry| entry.hash.as_str())
+            .unwrap_or_default();
+        if expected != current_hash {
+            return Err(save_conflict(layout, &normalized_date, current_hash)?);
+        }
+    }
+    let private = ai
+        .as_ref()
+        .and_then(|payload| payload.private)
+        .unwrap_or_else(|| existing_summary.as_ref().is_some_and(|entry| entry.private));
+    let skip_ai = ai.as_ref().is_some_and(|payload| payload.skip_ai);
+    // 离线模式与私密条目不发起后台摘要请求，直接使用本地摘要。
+    let ai_payload = ai
+        .filter(|_| !skip_ai && !private && !app_prefs::is_offline(app))
+        .and_then(sanitize_ai_payload);
+    let choice = choose_saved_summary(
+        existing_summary.as_ref(),
+        &body,
+        ai_payload.is_some(),
+        skip_ai,
+        *placeholders,
+    );
+
+    let mut summary = build_summary(
+        app,
         existing_summary.as_ref(),
         &normalized_date,
         &body,
-        ai_summary_text,
+        choice.text,
+        choice.source,
     )?;
+    summary.summary_truncated = choice.truncated;
+    summary.summary_stale = choice.stale;
+    summary.private = private;
+    if let Some(language) = language {
+        summary.language = Some(language);
+        summary.language_manual = true;
+    }
 
-    storage::write_entry(&layout, &summary, &body)
+    storage::write_entry(layout, &summary, &body, write_options)
         .map_err(|err| format!("failed to persist entry to disk: {err}"))?;
+    Ok(SavedEntry {
+        date: normalized_date,
+        summary,
+        body,
+        ai_payload,
+    })
+}
+
+/// 保存时写入 frontmatter 的摘要及其来源标记。
+#[derive(Debug, PartialEq, Eq)]
+struct SummaryChoice {
+    text: String,
+    source: Option<SummarySource>,
+    truncated: bool,
+    stale: bool,
+}
+
+/// 决定保存时写入的摘要：
+/// - 需要 AI 时先写入“生成中”占位符并标记为本地摘要，后台任务成功后再改为 ai；
+/// - 正文未变化时沿用已有摘要及其标记，避免本地截断覆盖 AI 结果；
+/// - 显式跳过 AI 且正文已修改时沿用已有摘要，但标记为过期；
+/// - 其余情况使用本地截断摘要。
+fn choose_saved_summary(
+    existing: Option<&DiaryEntry>,
+    body: &str,
+    ai_requested: bool,
+    skip_ai: bool,
+    placeholders: Placeholders,
+) -> SummaryChoice {
+    let local = || SummaryChoice {
+        text: summarize_body(body).unwrap_or_else(|| placeholders.empty_entry.to_string()),
+        source: Some(SummarySource::Local),
+        truncated: false,
+        stale: false,
+    };
+    if ai_requested {
+        return SummaryChoice {
+            text: placeholders.ai_pending.to_string(),
+            ..local()
+        };
+    }
+    let body_unchanged = existing.is_some_and(|entry| entry.body_matches(body));
+    if !body_unchanged && !skip_ai {
+        return local();
+    }
+    match (existing, preserved_ai_summary(existing)) {
+        (Some(entry), Some(text)) => SummaryChoice {
+            text,
+            source: entry.summary_source,
+            truncated: entry.summary_truncated,
+            stale: entry.summary_stale || !body_unchanged,
+        },
+        _ => local(),
+    }
+}
 
-    let mut store = STORE
+fn lock_entry_save() -> Result<std::sync::MutexGuard<'static, ()>, String> {
+    ENTRY_SAVE_LOCK
         .lock()
-        .map_err(|_| "failed to lock in-memory store".to_string())?;
-    if let Some(record) = store.get_mut(&normalized_date) {
-        record.update(summary.clone(), body.clone());
+        .map_err(|_| "failed to lock entry save".to_string())
+}
+
+fn cache_saved_entry(store: &mut HashMap<String, EntryRecord>, saved: &SavedEntry) {
+    if let Some(record) = store.get_mut(&saved.date) {
+        record.update(saved.summary.clone(), saved.body.clone());
     } else {
         store.insert(
-            normalized_date.clone(),
-            EntryRecord::new(summary.clone(), body.clone()),
+            saved.date.clone(),
+            EntryRecord::new(saved.summary.clone(), saved.body.clone()),
         );
     }
-    prune_store_capacity(&mut store);
+}
 
+/// 缓存更新后启动后台摘要（如需要），并补上返回给前端的文件路径。
+fn finish_saved_entry(
+    app: &AppHandle,
+    ctx: &SaveContext,
+    saved: SavedEntry,
+) -> Result<DiaryEntry, String> {
+    let SavedEntry {
+        date,
+        summary,
+        body,
+        ai_payload,
+    } = saved;
     if let Some(payload) = ai_payload {
         spawn_metadata_refresh(
-            &app,
-            normalized_date.clone(),
+            app,
+            date,
             payload,
-            body.clone(),
+            body,
             summary.hash.clone(),
+            ctx.placeholders,
         );
     }
 
-    Ok(summary)
-}
+    let mut saved = summary;
+    saved.file_path = Some(
+        storage::entry_file_path(&ctx.layout, parse_date(&saved.date)?)
+            .display()
+            .to_string(),
+    );
+    Ok(saved)
+}
+
+/// 手动设置条目语言（不改动正文）；`language` 为空时清除手动设置并恢复自动检测。
+///
+/// 元数据变更同样盖上新的 HLC，同步时其他设备才会采用这次修改。
+pub fn set_entry_language(
+    app: &AppHandle,
+    date: &str,
+    language: Option<&str>,
+) -> Result<DiaryEntry, String> {
+    let normalized_date = normalize_date(date)?;
+    let language = language.map(normalize_language_code).transpose()?.flatten();
+    let layout = storage_layout(app)?;
+    let write_options = app_prefs::write_options(app)?;
+    // 与正文保存共用保存锁，避免读取到的旧正文在写回时覆盖并发保存的内容。
+    let save_guard = lock_entry_save()?;
+    let record = storage::load_entry(&layout, &normalized_date)?
+        .ok_or_else(|| format!("entry {normalized_date} not found"))?;
+
+    let mut summary = record.summary().clone();
+    summary.language_manual = language.is_some();
+    summary.language = language.or_else(|| detect_language(record.body()));
+    summary.hlc = next_hlc(app)?;
+
+    storage::write_entry(&layout, &summary, record.body(), &write_options)?;
+    cache_record(
+        normalized_date,
+        EntryRecord::new(summary.clone(), record.body().to_string()),
+    )?;
+    drop(save_guard);
+    Ok(summary)
+}
+
+/// 设置或取消条目的私密标记（不改动正文），并盖上新的 HLC 以便同步。
+///
+/// 设为私密时中止该日排队中的后台摘要，并以本地截断摘要替换已有摘要，
+/// 之后保存也不再请求 AI 摘要；`locale` 决定空正文时的占位文案。
+pub fn set_entry_private(
+    app: &AppHandle,
+    date: &str,
+    private: bool,
+    locale: Option<&str>,
+) -> Result<DiaryEntry, String> {
+    let normalized_date = normalize_date(date)?;
+    let layout = storage_layout(app)?;
+    let write_options = app_prefs::write_options(app)?;
+    let save_guard = lock_entry_save()?;
+    if private {
+        if let Ok(mut pending) = PENDING_SUMMARY_REFRESH.lock() {
+            pending.cancel(&normalized_date);
+        }
+    }
+    let record = storage::load_entry(&layout, &normalized_date)?
+        .ok_or_else(|| format!("entry {normalized_date} not found"))?;
+
+    let mut summary = record.summary().clone();
+    apply_private_flag(
+        &mut summary,
+        record.body(),
+        private,
+        Placeholders::for_locale(locale),
+    );
+    summary.hlc = next_hlc(app)?;
+
+    storage::write_entry(&layout, &summary, record.body(), &write_options)?;
+    cache_record(
+        normalized_date,
+        EntryRecord::new(summary.clone(), record.body().to_string()),
+    )?;
+    drop(save_guard);
+    Ok(summary)
+}
+
+/// 更新私密标记；设为私密时丢弃 AI 摘要（含生成中占位符），改用本地截断摘要。
+fn apply_private_flag(
+    summary: &mut DiaryEntry,
+    body: &str,
+    private: bool,
+    placeholders: Placeholders,
+) {
+    summary.private = private;
+    if private {
+        summary.ai_summary =
+            Some(summarize_body(body).unwrap_or_else(|| placeholders.empty_entry.to_string()));
+        summary.summary_source = Some(SummarySource::Local);
+        summary.summary_truncated = false;
+        summary.summary_stale = false;
+    }
+}
+
+/// 规范化手动指定的语言代码（如 `en`、`zh-Hant`）；空白视为未指定。
+fn normalize_language_code(raw: &str) -> Result<Option<String>, String> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return Ok(None);
+    }
+    let valid = trimmed.len() <= 16
+        && trimmed
+            .chars()
+            .next()
+            .is_some_and(|ch| ch.is_ascii_alphabetic())
+        && trimmed
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_');
+    if !valid {
+        return Err(format!("invalid language code \"{trimmed}\""));
+    }
+    Ok(Some(trimmed.replace('_', "-")))
+}
+
+/// 将片段追加到"今天"的日记末尾（与已有正文空一行分隔），条目不存在时新建。
+///
+/// "今天"按调用方时区计算，时区缺省或无法解析时使用本机时区，与问候语的日期规则一致。
+/// 读取、拼接与写盘都在保存锁内完成，避免并发保存的内容被追加结果覆盖。
+pub fn append_to_today(
+    app: &AppHandle,
+    fragment: &str,
+    ai: Option<AiInvokePayload>,
+    timezone: Option<&str>,
+    locale: Option<&str>,
+) -> Result<DiaryEntry, String> {
+    let fragment = fragment.trim();
+    if fragment.is_empty() {
+        return Err("fragment must not be empty".to_string());
+    }
+    let today = resolve_greeting_date(None, timezone)?
+        .format(DATE_FORMAT)
+        .to_string();
+    let ctx = SaveContext::prepare(app, locale)?;
+    let save_guard = lock_entry_save()?;
+    let body = match load_body_cached(&ctx.layout, today.clone())? {
+        Some(existing) if !existing.trim().is_empty() => {
+            format!("{}\n\n{fragment}\n", existing.trim_end())
+        }
+        _ => format!("{fragment}\n"),
+    };
+    let saved = persist_entry(app, &ctx, &today, body, ai, None, None)?;
+    cache_saved_entries(std::slice::from_ref(&saved))?;
+    drop(save_guard);
+
+    finish_saved_entry(app, &ctx, saved)
+}
+
+/// 返回指定日期条目文件的绝对路径，条目不存在时同样返回将要写入的位置。
+pub fn get_entry_path(app: &AppHandle, date: &str) -> Result<String, String> {
+    let layout = storage_layout(app)?;
+    if layout.is_ephemeral() {
+        return Err(EPHEMERAL_MODE_ERROR.to_string());
+    }
+    let date = parse_date(date)?;
+    Ok(storage::entry_file_path(&layout, date)
+        .display()
+        .to_string())
+}
+
+/// 返回存储根目录、条目文件总大小、条目数与磁盘剩余空间，结果缓存 30 秒。
+pub fn storage_info(app: &AppHandle) -> Result<StorageInfo, String> {
+    let mut cache = STORAGE_INFO_CACHE
+        .lock()
+        .map_err(|_| "failed to lock storage info cache".to_string())?;
+    if let Some((computed_at, info)) = cache.as_ref() {
+        if computed_at.elapsed().as_secs() < STORAGE_INFO_CACHE_SECS {
+            return Ok(info.clone());
+        }
+    }
+
+    let layout = storage_layout(app)?;
+    let (total_bytes, entry_count) = storage::entry_disk_usage(&layout)?;
+    let info = StorageInfo {
+        root: layout.root().display().to_string(),
+        total_bytes,
+        entry_count,
+        free_bytes: storage::available_space(&layout),
+    };
+    *cache = Some((std::time::Instant::now(), info.clone()));
+    drop(cache);
+    Ok(info)
+}
+
+/// 生成首页 Hero Greeting，由后端拼接上下文与系统提示词，前端仅传递用户偏好。
+///
+/// Provider 调用失败或处于离线模式时回落到本地问候语，并在结果中标记 `isLocal`；
+/// 未配置 Provider 时原样返回 `NOT_CONFIGURED:*`，由前端引导用户完成设置。
+pub async fn generate_hero_greeting(
+    app: &AppHandle,
+    request: HeroGreetingRequest,
+) -> Result<HeroGreeting, String> {
+    let outcome = async {
+        let call = prepare_greeting_call(app, &request)?;
+        let response = invoke_ai_chat_tracked(
+            app,
+            &call.provider_id,
+            call.request,
+            call.model,
+            &call.api_key,
+            &call.api_base,
+        )
+        .await?;
+        non_empty_greeting(&response.content)
+    }
+    .await;
+    finish_hero_greeting(app, &request, outcome)
+}
+
+/// 流式生成问候语：逐步发送 `hero-greeting-delta` 事件，结束后返回完整问候语。
+///
+/// Provider 返回的是 JSON，因此只转发 `greeting` 字段中已解析出的字符，
+/// 键名出现之前的片段先缓冲不发送。失败时同样回落到本地问候语。
+pub async fn generate_hero_greeting_stream(
+    app: &AppHandle,
+    request: HeroGreetingRequest,
+) -> Result<HeroGreeting, String> {
+    let outcome = async {
+        let call = prepare_greeting_call(app, &request)?;
+        let mut extractor = GreetingStreamExtractor::default();
+        let emitter = app.clone();
+        let mut on_delta = move |chunk: &str| {
+            if let Some(text) = extractor.push(chunk) {
+                if let Err(err) =
+                    emitter.emit(HERO_GREETING_DELTA_EVENT, &HeroGreetingDelta { text })
+                {
+                    eprintln!("[EchoNote] failed to emit greeting delta: {err}");
+                }
+            }
+        };
+        let started = std::time::Instant::now();
+        let result = ai_provider::stream_ai_chat(
+            &call.provider_id,
+            call.request,
+            call.model.clone(),
+            &call.api_key,
+            &call.api_base,
+            &mut on_delta,
+        )
+        .await;
+        ai_log::record(
+            app,
+            &AiLogEntry::new(
+                &call.provider_id,
+                "stream",
+                &call.api_base,
+                Some(&call.model),
+                started,
+                result.as_ref().map(Some).map_err(String::as_str),
+            ),
+        );
+        if let Err(err) = &result {
+            record_provider_error(&call.provider_id, "stream", err);
+        }
+        let response = result?;
+        record_session_usage(&call.provider_id, &response);
+        non_empty_greeting(&response.content)
+    }
+    .await;
+    finish_hero_greeting(app, &request, outcome)
+}
+
+fn non_empty_greeting(raw: &str) -> Result<String, String> {
+    let greeting = extract_greeting_from_response(raw);
+    if greeting.is_empty() {
+        return Err("AI greeting response is empty".to_string());
+    }
+    Ok(greeting)
+}
+
+fn finish_hero_greeting(
+    app: &AppHandle,
+    request: &HeroGreetingRequest,
+    outcome: Result<String, String>,
+) -> Result<HeroGreeting, String> {
+    match outcome {
+        Ok(greeting) => Ok(HeroGreeting {
+            greeting,
+            is_local: false,
+        }),
+        Err(err) if is_not_configured(&err) => Err(err),
+        Err(err) => {
+            eprintln!(
+                "[EchoNote] AI greeting unavailable, using local greeting: {}",
+                ai_provider::redact(&err)
+            );
+            Ok(HeroGreeting {
+                greeting: build_local_greeting(app, request)?,
+                is_local: true,
+            })
+        }
+    }
+}
+
+/// 按时段与近 7 天的记录数生成确定性的本地问候语，不依赖任何网络请求。
+fn build_local_greeting(app: &AppHandle, request: &HeroGreetingRequest) -> Result<String, String> {
+    let target_date = resolve_greeting_date(request.date.as_deref(), request.timezone.as_deref())?;
+    let hour = parse_timezone(request.timezone.as_deref()).map_or_else(
+        || Local::now().hour(),
+        |tz| Utc::now().with_timezone(&tz).hour(),
+    );
+    let layout = storage_layout(app)?;
+    let window_start = target_date - Duration::days(LOCAL_GREETING_WINDOW_DAYS - 1);
+    let recent_entries = storage::list_entry_dates(&layout)?
+        .into_iter()
+        .filter(|date| (window_start..=target_date).contains(date))
+        .count();
+
+    let locale = request
+        .locale
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .or_else(system_locale);
+    let resolved = resolve_placeholder_locale(locale.as_deref());
+    let (_, salutations, encouragements) = LOCAL_GREETING_TEXTS
+        .iter()
+        .find(|(id, _, _)| *id == resolved)
+        .copied()
+        .unwrap_or(LOCAL_GREETING_TEXTS[0]);
+
+    let salutation = match hour {
+        5..=10 => salutations[0],
+        11..=17 => salutations[1],
+        18..=22 => salutations[2],
+        _ => salutations[3],
+    };
+    let encouragement = match recent_entries {
+        0 => encouragements[0],
+        1..=3 => encouragements[1],
+        _ => encouragements[2],
+    };
+    let separator = if resolved == "en" { " " } else { "" };
+    Ok(format!("{salutation}{separator}{encouragement}"))
+}
+
+/// 预览 `generate_hero_greeting` 将发送的系统/用户提示词（含近期摘要上下文），不调用 Provider。
+pub fn preview_greeting_prompt(
+    app: &AppHandle,
+    request: &HeroGreetingRequest,
+) -> Result<GreetingPrompts, String> {
+    // 与实际生成一致：未配置的 Provider 不会发送任何提示词，因此同样返回 `NOT_CONFIGURED:*`。
+    let ConfiguredProvider {
+        ctx: provider_ctx, ..
+    } = require_configured_provider(app, Some(&request.provider_id))?;
+    build_greeting_prompts(app, request, &provider_ctx)
+}
+
+/// 重新计算指定日期范围内条目的派生元数据（`hash`、`language`、`word_count`），不改动 AI 摘要与 HLC。
+///
+/// 元数据未变化的条目不会重写，因此可重复执行；每处理一条都会发送进度事件。
+/// 正文与 frontmatter HASH 不一致的条目保留原 HASH，并在 `divergent` 中报告。
+///
+/// 参数：
+/// - from / to: 可选的起止日期（YYYY-MM-DD，含边界），缺省时处理全部条目
+pub fn rebuild_metadata(
+    app: &AppHandle,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<MetadataRebuildProgress, String> {
+    let (from, to) = parse_date_range(from, to)?;
+    let layout = storage_layout(app)?;
+    let write_options = app_prefs::write_options(app)?;
+    let hash_algo = configured_hash_algorithm(app);
+
+    let dates: Vec<NaiveDate> = storage::list_entry_dates(&layout)?
+        .into_iter()
+        .filter(|date| from.map_or(true, |start| *date >= start))
+        .filter(|date| to.map_or(true, |end| *date <= end))
+        .collect();
+    let mut progress = MetadataRebuildProgress {
+        processed: 0,
+        total: dates.len(),
+        updated: 0,
+        divergent: Vec::new(),
+    };
+
+    for date in dates {
+        let date_str = date.format(DATE_FORMAT).to_string();
+        if let Some(record) = storage::load_entry(&layout, &date_str)? {
+            let previous = record.summary();
+            let mut summary = previous.clone();
+            // 只为校验通过的正文换算 HASH；不一致时重新盖章会掩盖外部修改。
+            if previous.body_matches(record.body()) {
+                summary.hash = hash_algo.digest(record.body());
+                summary.hash_algo = hash_algo;
+            } else {
+                progress.divergent.push(date_str.clone());
+            }
+            if !summary.language_manual {
+                summary.language = detect_language(record.body());
+            }
+            summary.word_count = Some(count_words(record.body()));
+
+            if summary.hash != previous.hash
+                || summary.hash_algo != previous.hash_algo
+                || summary.language != previous.language
+                || summary.word_count != previous.word_count
+            {
+                storage::write_entry(&layout, &summary, record.body(), &write_options)?;
+                cache_record(
+                    date_str,
+                    EntryRecord::new(summary, record.body().to_string()),
+                )?;
+                progress.updated += 1;
+            }
+        }
+
+        progress.processed += 1;
+        app.emit(METADATA_REBUILD_EVENT, &progress)
+            .map_err(|err| format!("failed to emit rebuild progress: {err}"))?;
+    }
+
+    Ok(progress)
+}
+
+/// 将指定日期范围内的条目逐行导出为 JSON Lines（frontmatter 字段，可选附带正文）。
+///
+/// 每写完一行即刷新到磁盘，内存中最多只保留一条记录；单条读取或序列化失败时写入
+/// `{"date", "error"}` 标记行并继续。
+///
+/// 参数：
+/// - from / to: 可选的起止日期（YYYY-MM-DD，含边界），缺省时导出全部条目
+/// - `target_path`: 导出文件路径，已存在时覆盖
+/// - `include_body`: 是否附带正文
+pub fn export_jsonl(
+    app: &AppHandle,
+    from: Option<&str>,
+    to: Option<&str>,
+    target_path: &str,
+    include_body: bool,
+) -> Result<JsonlExportResult, String> {
+    if app_prefs::is_ephemeral() {
+        return Err(format!("{EPHEMERAL_MODE_ERROR}; export is disabled"));
+    }
+    let (from, to) = parse_date_range(from, to)?;
+    let target = PathBuf::from(target_path.trim());
+    if target.as_os_str().is_empty() {
+        return Err("export path is required".to_string());
+    }
+    let layout = storage_layout(app)?;
+
+    let file = fs::File::create(&target)
+        .map_err(|err| format!("failed to create {}: {err}", target.display()))?;
+    let mut writer = BufWriter::new(file);
+    let mut result = JsonlExportResult {
+        written: 0,
+        failed: 0,
+        cancelled: false,
+    };
+    EXPORT_CANCELLED.store(false, Ordering::SeqCst);
+
+    let dates: Vec<NaiveDate> = storage::list_entry_dates(&layout)?
+        .into_iter()
+        .filter(|date| from.map_or(true, |start| *date >= start))
+        .filter(|date| to.map_or(true, |end| *date <= end))
+        .collect();
+    let total = dates.len();
+    for (index, date) in dates.into_iter().enumerate() {
+        if EXPORT_CANCELLED.swap(false, Ordering::SeqCst) {
+            drop(writer);
+            fs::remove_file(&target)
+                .map_err(|err| format!("failed to remove {}: {err}", target.display()))?;
+            result.cancelled = true;
+            return Ok(result);
+        }
+        let date_str = date.format(DATE_FORMAT).to_string();
+        let progress = ExportProgress {
+            processed: index + 1,
+            total,
+            date: date_str.clone(),
+        };
+        if let Err(err) = app.emit(EXPORT_PROGRESS_EVENT, &progress) {
+            eprintln!("[EchoNote] failed to emit export progress: {err}");
+        }
+        let line = storage::load_entry(&layout, &date_str).and_then(|record| {
+            let Some(record) = record else {
+                return Ok(None);
+            };
+            serde_json::to_string(&JsonlExportLine {
+                entry: record.summary(),
+                body: include_body.then(|| record.body()),
+            })
+            .map(Some)
+            .map_err(|err| format!("failed to serialize entry: {err}"))
+        });
+        let line = match line {
+            Ok(Some(line)) => {
+                result.written += 1;
+                line
+            }
+            Ok(None) => continue,
+            Err(err) => {
+                result.failed += 1;
+                serde_json::json!({ "date": date_str, "error": err }).to_string()
+            }
+        };
+        writeln!(writer, "{line}")
+            .and_then(|()| writer.flush())
+            .map_err(|err| format!("failed to write {}: {err}", target.display()))?;
+    }
+    drop(writer);
+
+    let done = ExportDone {
+        path: target.display().to_string(),
+        bytes: fs::metadata(&target).map_or(0, |meta| meta.len()),
+        written: result.written,
+        failed: result.failed,
+    };
+    if let Err(err) = app.emit(EXPORT_DONE_EVENT, &done) {
+        eprintln!("[EchoNote] failed to emit export done: {err}");
+    }
+    Ok(result)
+}
+
+/// 请求中止正在进行的导出，导出会在处理下一篇日记前停止并删除未完成的文件。
+pub fn cancel_export() {
+    EXPORT_CANCELLED.store(true, Ordering::SeqCst);
+}
+
+/// 签发清除全部数据所需的一次性确认令牌，有效期 `WIPE_CONFIRM_TOKEN_TTL_SECS` 秒。
+pub fn wipe_confirm_token() -> Result<String, String> {
+    let token = uuid::Uuid::new_v4().to_string();
+    *WIPE_CONFIRM_TOKEN
+        .lock()
+        .map_err(|_| "failed to lock wipe token".to_string())? =
+        Some((token.clone(), std::time::Instant::now()));
+    Ok(token)
+}
+
+/// 校验确认令牌后删除全部本地数据：条目与历史版本、偏好、密钥、`device_id`、HLC 状态、
+/// AI 日志，并清空内存中的缓存。
+///
+/// 密钥与 `device_id` 会先置零再删除。令牌无论校验是否通过都会被消耗。
+pub fn wipe_all_data(app: &AppHandle, confirm_token: &str) -> Result<WipeReport, String> {
+    let issued = WIPE_CONFIRM_TOKEN
+        .lock()
+        .map_err(|_| "failed to lock wipe token".to_string())?
+        .take();
+    let valid = issued.is_some_and(|(token, issued_at)| {
+        token == confirm_token.trim() && issued_at.elapsed().as_secs() < WIPE_CONFIRM_TOKEN_TTL_SECS
+    });
+    if !valid {
+        return Err(
+            "invalid or expired wipe confirmation token; request a new one first".to_string(),
+        );
+    }
+
+    let layout = storage_layout(app)?;
+    // 先中止排队中的后台摘要，避免它们在清除后把条目重新写回磁盘。
+    if let Ok(mut pending) = PENDING_SUMMARY_REFRESH.lock() {
+        pending.abort_all();
+    }
+    let mut removed: Vec<PathBuf> = storage::wipe_entries(&layout)?;
+    removed.extend(secrets::wipe(app)?);
+    removed.extend(device::wipe(app)?);
+    removed.extend(hlc::reset(app)?);
+    removed.extend(ai_log::clear(app)?);
+    if !app_prefs::is_ephemeral() {
+        for path in [
+            app_prefs::preferences_path(app)?,
+            ai_prefs::preferences_path(app)?,
+        ] {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .map_err(|err| format!("failed to remove {}: {err}", path.display()))?;
+                removed.push(path);
+            }
+        }
+    }
+
+    app_prefs::clear_cache();
+    ai_prefs::clear_ephemeral_preferences();
+    write_store()?.clear();
+    if let Ok(mut cache) = STORAGE_INFO_CACHE.lock() {
+        *cache = None;
+    }
+    if let Ok(mut usage) = SESSION_USAGE.lock() {
+        usage.clear();
+    }
+    if let Ok(mut digests) = MONTHLY_DIGEST_CACHE.lock() {
+        digests.clear();
+    }
+    if let Ok(mut errors) = LAST_PROVIDER_ERRORS.lock() {
+        errors.clear();
+    }
+    for slot in [
+        &MONTHLY_DIGEST_LAST_REQUEST,
+        &TEST_SUMMARY_LAST_REQUEST,
+        &MODEL_LIST_LAST_REQUEST,
+    ] {
+        if let Ok(mut requests) = slot.lock() {
+            requests.clear();
+        }
+    }
+
+    Ok(WipeReport {
+        removed: removed
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect(),
+    })
+}
+
+/// 统计任意文本的字数，供编辑器实时显示；`words` 与保存时写入的 `wordCount` 完全一致。
+pub fn count_text(text: &str) -> TextCount {
+    let (chars, cjk_chars) = text.chars().fold((0u32, 0u32), |(chars, cjk), ch| {
+        (
+            chars.saturating_add(1),
+            cjk.saturating_add(u32::from(is_cjk_char(ch))),
+        )
+    });
+    TextCount {
+        words: count_words(text),
+        chars,
+        cjk_chars,
+    }
+}
+
+/// 发送前在本地粗略估算文本的 token 数。
+///
+/// `model` 能识别为 `OpenAI` 系列模型（可带 `openai/` 等路由前缀）时用 tiktoken 计数，
+/// 其余模型按字符启发式估算：非 CJK 字符每 4 个约 1 token，CJK 字符每 1.5 个约 1 token。
+pub fn estimate_tokens(text: &str, model: &str) -> TokenEstimate {
+    let model_name = model.trim().rsplit('/').next().unwrap_or_default();
+    if let Some((encoding, tokens)) = tiktoken_count(model_name, text) {
+        return TokenEstimate {
+            tokens: u32::try_from(tokens).unwrap_or(u32::MAX),
+            method: TokenEstimateMethod::Tiktoken,
+            encoding: Some(encoding.to_string()),
+        };
+    }
+
+    let TextCount {
+        chars, cjk_chars, ..
+    } = count_text(text);
+    let other_chars = f64::from(chars - cjk_chars);
+    let estimate = (other_chars / 4.0 + f64::from(cjk_chars) / 1.5).ceil();
+    TokenEstimate {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        tokens: estimate as u32,
+        method: TokenEstimateMethod::Heuristic,
+        encoding: None,
+    }
+}
+
+/// 按模型名选择 tiktoken 编码并计数，返回编码名与 token 数；编码表只在首次使用时加载并常驻内存。
+fn tiktoken_count(model: &str, text: &str) -> Option<(&'static str, usize)> {
+    use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+    if model.is_empty() {
+        return None;
+    }
+    let (encoding, bpe) = match get_tokenizer(model)? {
+        Tokenizer::O200kBase => ("o200k_base", tiktoken_rs::o200k_base_singleton()),
+        Tokenizer::Cl100kBase => ("cl100k_base", tiktoken_rs::cl100k_base_singleton()),
+        Tokenizer::P50kBase => ("p50k_base", tiktoken_rs::p50k_base_singleton()),
+        Tokenizer::P50kEdit => ("p50k_edit", tiktoken_rs::p50k_edit_singleton()),
+        Tokenizer::R50kBase | Tokenizer::Gpt2 => ("r50k_base", tiktoken_rs::r50k_base_singleton()),
+    };
+    let tokens = bpe.lock().encode_with_special_tokens(text).len();
+    Some((encoding, tokens))
+}
+
+/// 校验粘贴/导入的条目文档，不写入存储；解析规则与加载时完全一致，失败时返回具体原因。
+pub fn validate_entry_document(text: &str) -> Result<EntryDocumentValidation, String> {
+    let record = storage::parse_document(text)?;
+    Ok(EntryDocumentValidation {
+        body_length: record.body().chars().count(),
+        entry: record.summary().clone(),
+    })
+}
+
+/// 将指定月份打包为 `YYYY-MM.tar.gz` 并删除散文件，返回归档的条目数。
+///
+/// 归档后的月份仍可正常浏览；再次保存该月的日记时会自动解档。
+pub fn archive_month(app: &AppHandle, year: i32, month: u32) -> Result<usize, String> {
+    if !(1..=12).contains(&month) {
+        return Err(format!("invalid month {month}"));
+    }
+    let layout = storage_layout(app)?;
+    storage::archive_month(&layout, year, month)
+}
+
+/// 将归档的月份恢复为散文件，返回恢复的条目数；月份未归档时返回 0。
+pub fn unarchive_month(app: &AppHandle, year: i32, month: u32) -> Result<usize, String> {
+    if !(1..=12).contains(&month) {
+        return Err(format!("invalid month {month}"));
+    }
+    let layout = storage_layout(app)?;
+    storage::unarchive_month(&layout, year, month)
+}
+
+/// 重新读取指定日期范围内的条目，按各自记录的 HASH 算法重新计算正文 HASH 并与 frontmatter 比对。
+///
+/// 只读操作，不改写任何文件，也不刷新内存缓存。
+///
+/// 参数：
+/// - from / to: 可选的起止日期（YYYY-MM-DD，含边界），缺省时检查全部条目
+pub fn verify_integrity(
+    app: &AppHandle,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<IntegrityReport, String> {
+    let (from, to) = parse_date_range(from, to)?;
+    let layout = storage_layout(app)?;
+
+    let mut report = IntegrityReport::default();
+    let dates = storage::list_entry_dates(&layout)?
+        .into_iter()
+        .filter(|date| from.map_or(true, |start| *date >= start))
+        .filter(|date| to.map_or(true, |end| *date <= end));
+    for date in dates {
+        let date_str = date.format(DATE_FORMAT).to_string();
+        match storage::load_entry(&layout, &date_str) {
+            Ok(Some(record)) => {
+                report.checked += 1;
+                if !record.summary().body_matches(record.body()) {
+                    report.divergent.push(date_str);
+                }
+            }
+            Ok(None) => {}
+            Err(err) => report.unreadable.push(format!("{date_str}: {err}")),
+        }
+    }
+    Ok(report)
+}
+
+/// 删除正文为空（仅含空白）的条目并同步清理内存缓存，返回被删除的日期。
+///
+/// 只要正文有任何非空白字符就会保留；读取失败或位于额外只读根目录的条目同样跳过，避免误删。
+pub fn cleanup_empty_entries(
+    app: &AppHandle,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let (from, to) = parse_date_range(from, to)?;
+    let layout = storage_layout(app)?;
+
+    let mut removed = Vec::new();
+    let dates = storage::list_entry_dates(&layout)?
+        .into_iter()
+        .filter(|date| from.map_or(true, |start| *date >= start))
+        .filter(|date| to.map_or(true, |end| *date <= end));
+    for date in dates {
+        let date_str = date.format(DATE_FORMAT).to_string();
+        let Ok(Some(record)) = storage::load_entry(&layout, &date_str) else {
+            continue;
+        };
+        if !record.body().trim().is_empty() {
+            continue;
+        }
+        if let Some(root) = storage::read_only_root_of(&layout, date)? {
+            eprintln!(
+                "[EchoNote] kept empty entry {date_str}: stored in read-only root {}",
+                root.display()
+            );
+            continue;
+        }
+        if storage::delete_entry(&layout, date)? {
+            removed.push(date_str);
+        }
+    }
+
+    if !removed.is_empty() {
+        let mut store = write_store()?;
+        for date in &removed {
+            store.remove(date);
+        }
+    }
+    Ok(removed)
+}
+
+/// 扫描所有散文件，列出有多个文件指向同一 frontmatter 日期的条目。
+pub fn find_duplicates(app: &AppHandle) -> Result<Vec<DuplicateEntry>, String> {
+    let layout = storage_layout(app)?;
+    let mut duplicates = Vec::new();
+    for (date, paths) in storage::find_duplicate_files(&layout)? {
+        let mut files = Vec::new();
+        for path in paths {
+            let summary = storage::load_entry_file(&path)?.summary().clone();
+            files.push(DuplicateFile {
+                path: path.display().to_string(),
+                hlc: summary.hlc,
+                word_count: summary.word_count,
+            });
+        }
+        duplicates.push(DuplicateEntry {
+            date: date.format(DATE_FORMAT).to_string(),
+            files,
+        });
+    }
+    Ok(duplicates)
+}
+
+/// 按策略合并同一日期的重复文件；`dry_run` 为 true 时只返回将发生的变化，不修改磁盘。
+///
+/// 合并结果通过 `save_entry_by_date` 写入规范路径（沿用其 HLC 与摘要处理），随后删除其余文件。
+pub fn merge_duplicates(
+    app: &AppHandle,
+    date: &str,
+    strategy: DuplicateMergeStrategy,
+    dry_run: bool,
+) -> Result<DuplicateMergeReport, String> {
+    let normalized_date = normalize_date(date)?;
+    let target_date = parse_date(&normalized_date)?;
+    let layout = storage_layout(app)?;
+    let paths = storage::find_duplicate_files(&layout)?
+        .remove(&target_date)
+        .ok_or_else(|| format!("no duplicate files for {normalized_date}"))?;
+
+    let mut records = paths
+        .iter()
+        .map(|path| storage::load_entry_file(path).map(|record| (path.clone(), record)))
+        .collect::<Result<Vec<_>, String>>()?;
+    records.sort_by(|(_, a), (_, b)| a.summary().hlc.cmp(&b.summary().hlc));
+
+    let body = match strategy {
+        DuplicateMergeStrategy::KeepNewestHlc => records
+            .last()
+            .map(|(_, record)| record.body().to_string())
+            .unwrap_or_default(),
+        DuplicateMergeStrategy::Concatenate => {
+            let mut parts: Vec<&str> = Vec::new();
+            for (_, record) in &records {
+                let part = record.body().trim();
+                if !part.is_empty() && !parts.contains(&part) {
+                    parts.push(part);
+                }
+            }
+            parts.join("\n\n")
+        }
+    };
+
+    let target_path = storage::entry_file_path(&layout, target_date);
+    // 归档中与目标同名的条目会在写入时被解档并覆盖，不能再当作多余文件删除。
+    let removed: Vec<PathBuf> = paths
+        .into_iter()
+        .filter(|path| storage::loose_entry_path(&layout, path) != target_path)
+        .collect();
+    let report = DuplicateMergeReport {
+        date: normalized_date.clone(),
+        applied: !dry_run,
+        target_path: target_path.display().to_string(),
+        removed: removed
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect(),
+        hash: configured_hash_algorithm(app).digest(&body),
+        word_count: count_words(&body),
+    };
+    if dry_run {
+        return Ok(report);
+    }
+
+    // 落选的文件在删除前存入历史版本，合并结果不理想时仍可恢复。写入合并结果可能解档
+    // 并按偏好清理历史，因此先读出原始内容，写入完成后再存档。
+    let snapshots = removed
+        .iter()
+        .map(|path| storage::read_entry_content(path))
+        .collect::<Result<Vec<_>, String>>()?;
+    write_store()?.remove(&normalized_date);
+    save_entry_by_date(
+        app.clone(),
+        normalized_date.clone(),
+        body,
+        None,
+        None,
+        None,
+        None,
+    )?;
+    let revision_limit = app_prefs::write_options(app)?
+        .revision_limit
+        .max(removed.len() + 1);
+    for content in &snapshots {
+        storage::snapshot_entry(&layout, target_date, content, revision_limit)?;
+    }
+    for path in &removed {
+        storage::remove_entry_file(&layout, path)?;
+    }
+    write_store()?.remove(&normalized_date);
+    Ok(report)
+}
+
+/// 将指定月份中文件名与 frontmatter 日期不一致的条目重命名为 frontmatter 日期。
+///
+/// 重命名后清空内存缓存，确保后续读取使用新的文件位置。
+pub fn reconcile_dates(
+    app: &AppHandle,
+    year: i32,
+    month: u32,
+) -> Result<DateReconcileReport, String> {
+    if !(1..=12).contains(&month) {
+        return Err(format!("invalid month {month}"));
+    }
+    let layout = storage_layout(app)?;
+    let report = storage::reconcile_month_dates(&layout, year, month)?;
+    if !report.renamed.is_empty() {
+        write_store()?.clear();
+    }
+    Ok(report)
+}
+
+/// 设置条目文件名的日期格式（如 `%Y_%m_%d`、`%d-%m-%Y`），传入 None 恢复默认格式。
+///
+/// 已有文件不会立即重命名：读取时兼容默认格式，下次保存时按新格式写入。
+pub fn set_filename_date_format(app: &AppHandle, format: Option<&str>) -> Result<(), String> {
+    let format = format.map(str::trim).filter(|value| !value.is_empty());
+    if let Some(format) = format {
+        storage::validate_filename_date_format(format)?;
+    }
+    let mut prefs = app_prefs::load_preferences(app)?;
+    prefs.filename_date_format = format.map(str::to_string);
+    app_prefs::save_preferences(app, &prefs)
+}
+
+/// 添加一个只读的日记目录，返回当前全部额外目录。
+///
+/// 目录中的条目按日期与主目录合并展示（同一天以主目录为准）；编辑后的内容写入主目录，原目录不会被修改。
+pub fn add_read_root(app: &AppHandle, path: &str) -> Result<Vec<String>, String> {
+    let layout = storage_layout(app)?;
+    if layout.is_ephemeral() {
+        return Err(EPHEMERAL_MODE_ERROR.to_string());
+    }
+    let path = path.trim();
+    if path.is_empty() {
+        return Err("read root path must not be empty".to_string());
+    }
+    let root = fs::canonicalize(path).map_err(|err| format!("failed to resolve {path}: {err}"))?;
+    if !root.is_dir() {
+        return Err(format!("{} is not a directory", root.display()));
+    }
+    let primary = fs::canonicalize(layout.root()).unwrap_or_else(|_| layout.root().to_path_buf());
+    if root.starts_with(&primary) || primary.starts_with(&root) {
+        return Err(format!(
+            "{} overlaps the primary storage directory",
+            root.display()
+        ));
+    }
+
+    let mut prefs = app_prefs::load_preferences(app)?;
+    let root = root.display().to_string();
+    if !prefs.extra_read_roots.contains(&root) {
+        prefs.extra_read_roots.push(root);
+        app_prefs::save_preferences(app, &prefs)?;
+    }
+    Ok(prefs.extra_read_roots)
+}
+
+/// 切换正文压缩偏好，并将已有条目迁移为对应格式，返回实际改写的条目数。
+///
+/// 先改写已有条目再保存偏好：改写中途失败时偏好保持原值，重试即可继续迁移。
+pub fn set_body_compression(app: &AppHandle, compress: bool) -> Result<usize, String> {
+    let layout = storage_layout(app)?;
+    let body_encoding = if compress {
+        BodyEncoding::Zstd
+    } else {
+        BodyEncoding::Plain
+    };
+    let options = storage::WriteOptions {
+        body_encoding,
+        ..app_prefs::write_options(app)?
+    };
+    let rewritten = storage::rewrite_entry_bodies(&layout, &options)?;
+
+    let mut prefs = app_prefs::load_preferences(app)?;
+    prefs.body_encoding = body_encoding;
+    app_prefs::save_preferences(app, &prefs)?;
+    Ok(rewritten)
+}
+
+/// 设置每篇日记保留的历史版本数，0 表示关闭历史记录（已有版本保留）。
+pub fn set_revision_limit(app: &AppHandle, limit: usize) -> Result<usize, String> {
+    let mut prefs = app_prefs::load_preferences(app)?;
+    prefs.revision_limit = limit.min(app_prefs::MAX_REVISION_LIMIT);
+    app_prefs::save_preferences(app, &prefs)?;
+    Ok(prefs.revision_limit)
+}
+
+/// 设置新写入条目的正文 HASH 算法；已有条目保持原算法，可通过重建元数据统一。
+pub fn set_hash_algorithm(app: &AppHandle, algorithm: HashAlgorithm) -> Result<(), String> {
+    let mut prefs = app_prefs::load_preferences(app)?;
+    prefs.hash_algorithm = algorithm;
+    app_prefs::save_preferences(app, &prefs)
+}
+
+/// 设置条目文档的排版风格（frontmatter 后的空行数、结尾换行），仅影响之后的写入。
+pub fn set_document_style(
+    app: &AppHandle,
+    blank_lines: Option<usize>,
+    trailing_newline: bool,
+) -> Result<(), String> {
+    let mut prefs = app_prefs::load_preferences(app)?;
+    prefs.frontmatter_blank_lines =
+        blank_lines.map(|lines| lines.min(app_prefs::MAX_FRONTMATTER_BLANK_LINES));
+    prefs.trailing_newline = trailing_newline;
+    app_prefs::save_preferences(app, &prefs)
+}
+
+/// 列出指定日期的历史版本（新到旧）。
+pub fn list_entry_revisions(app: &AppHandle, date: &str) -> Result<Vec<EntryRevision>, String> {
+    let layout = storage_layout(app)?;
+    storage::list_entry_revisions(&layout, parse_date(date)?)
+}
+
+/// 读取指定历史版本的正文，版本不存在时返回 None。
+pub fn get_entry_revision(
+    app: &AppHandle,
+    date: &str,
+    revision: &str,
+) -> Result<Option<String>, String> {
+    let layout = storage_layout(app)?;
+    Ok(
+        storage::load_entry_revision(&layout, parse_date(date)?, revision)?
+            .map(|record| record.body().to_string()),
+    )
+}
+
+/// 将日记恢复为指定历史版本；当前版本会先被归档，因此恢复操作本身也可撤销。
+pub fn restore_entry_revision(
+    app: AppHandle,
+    date: &str,
+    revision: &str,
+    locale: Option<&str>,
+) -> Result<DiaryEntry, String> {
+    let body = get_entry_revision(&app, date, revision)?
+        .ok_or_else(|| format!("revision {revision} of {date} not found"))?;
+    save_entry_by_date(app, date.to_string(), body, None, locale, None, None)
+}
+
+/// 返回 Provider 最近一次 AI 调用失败的原因，本次会话内没有失败时返回 None。
+pub fn last_provider_error(provider_id: &str) -> Result<Option<ProviderError>, String> {
+    let errors = LAST_PROVIDER_ERRORS
+        .lock()
+        .map_err(|_| "failed to lock provider errors".to_string())?;
+    Ok(errors.get(provider_id.trim()).cloned())
+}
+
+fn record_provider_error(provider_id: &str, operation: &str, error: &str) {
+    if let Ok(mut errors) = LAST_PROVIDER_ERRORS.lock() {
+        errors.insert(
+            provider_id.to_string(),
+            ProviderError {
+                operation: operation.to_string(),
+                error: ai_provider::redact(error),
+                timestamp: Utc::now().timestamp_millis(),
+            },
+        );
+    }
+}
+
+/// 返回 AI 请求的体积上限及可配置范围。
+pub fn get_limits(app: &AppHandle) -> Result<AiLimits, String> {
+    Ok(AiLimits {
+        max_request_bytes: ai_prefs::max_request_bytes(app)?,
+        min_allowed: ai_prefs::MIN_MAX_REQUEST_BYTES,
+        max_allowed: ai_prefs::MAX_MAX_REQUEST_BYTES,
+    })
+}
+
+/// 返回本次会话按 Provider 汇总的 token 用量，覆盖摘要与问候等全部 AI 调用。
+pub fn session_usage() -> Result<HashMap<String, ProviderUsage>, String> {
+    let usage = SESSION_USAGE
+        .lock()
+        .map_err(|_| "failed to lock session usage".to_string())?;
+    Ok(usage.clone())
+}
+
+/// 校验并规范化 Base URL（不发起网络请求），空值返回该 Provider 的默认地址。
+pub fn validate_base_url(provider_id: &str, url: &str) -> Result<String, String> {
+    sanitize_api_base_url(Some(url.to_string()), provider_id.trim())
+}
+
+/// 查询指定 Base URL + API Key 的可用模型（API Key 来自本地后端存储）
+pub async fn list_ai_models(
+    app: &AppHandle,
+    request: AiModelListRequest,
+) -> Result<Vec<String>, String> {
+    let provider_id = require_provider_id(Some(&request.provider_id))?.to_string();
+
+    // 离线模式下仅返回缓存的模型列表。
+    if app_prefs::is_offline(app) {
+        return cached_model_list(app, &provider_id)?.ok_or_else(|| OFFLINE_MODE_ERROR.to_string());
+    }
+
+    let provider_ctx = ai_prefs::resolve_provider_context(app, &provider_id)?;
+    apply_network_preferences(&provider_ctx);
+    let base_url = sanitize_api_base_url(Some(provider_ctx.base_url.clone()), &provider_id)?;
+    let api_key = load_provider_api_key(app, &provider_id)?;
+
+    let started = std::time::Instant::now();
+    let result = ai_provider::list_provider_models(
+        &provider_id,
+        base_url.trim_end_matches('/'),
+        &api_key,
+        &provider_ctx.scope,
+    )
+    .await;
+    ai_log::record(
+        app,
+        &AiLogEntry::new(
+            &provider_id,
+            "models",
+            &base_url,
+            None,
+            started,
+            result.as_ref().map(|_| None).map_err(String::as_str),
+        ),
+    );
+    match result {
+        Ok(models) => {
+            ai_prefs::persist_model_list(app, &provider_id, &models)?;
+            Ok(models)
+        }
+        Err(err) => cached_model_list(app, &provider_id)?.ok_or(err),
+    }
+}
+
+/// 并发查询所有已配置 API Key 的 Provider 的模型列表，返回 Provider id → 结果。
+///
+/// 每个 Provider 只发起一次请求，互不阻塞；整体超过超时时间仍未返回的 Provider
+/// 记为超时错误并取消其请求。单个 Provider 失败时仍按 `list_ai_models` 的规则回退到缓存；
+/// 同一 Provider 在最小间隔内重复查询时直接返回缓存，没有缓存则记为限流错误。
+pub async fn list_all_models(
+    app: &AppHandle,
+) -> Result<HashMap<String, ProviderModelList>, String> {
+    let prefs = ai_prefs::load_preferences(app)?;
+    let mut provider_ids: Vec<String> = prefs
+        .providers
+        .keys()
+        .cloned()
+        .chain(prefs.active_provider_id)
+        .filter(|id| id != "noai" && !prefs.hidden_providers.contains(id))
+        .collect();
+    provider_ids.sort();
+    provider_ids.dedup();
+
+    let mut tasks = Vec::new();
+    let mut results = HashMap::new();
+    for provider_id in provider_ids {
+        if ai_prefs::requires_api_key(&provider_id) && !secrets::has_api_key(app, &provider_id)? {
+            continue;
+        }
+        if let Err(err) = throttle_provider_request(
+            &MODEL_LIST_LAST_REQUEST,
+            &provider_id,
+            LIST_ALL_MODELS_MIN_INTERVAL_SECS,
+            "model list",
+        ) {
+            let entry = cached_model_list(app, &provider_id)?.map_or_else(
+                || ProviderModelList {
+                    models: Vec::new(),
+                    error: Some(err),
+                },
+                |models| ProviderModelList {
+                    models,
+                    error: None,
+                },
+            );
+            results.insert(provider_id, entry);
+            continue;
+        }
+        let app = app.clone();
+        let request = AiModelListRequest {
+            provider_id: provider_id.clone(),
+        };
+        let handle =
+            tauri::async_runtime::spawn(async move { list_ai_models(&app, request).await });
+        tasks.push((provider_id, handle));
+    }
+
+    let deadline =
+        tokio::time::Instant::now() + std::time::Duration::from_secs(LIST_ALL_MODELS_TIMEOUT_SECS);
+    for (provider_id, mut handle) in tasks {
+        let outcome = match tokio::time::timeout_at(deadline, &mut handle).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(err)) => Err(format!("model listing task failed: {err}")),
+            Err(_) => {
+                handle.abort();
+                Err(format!(
+                    "model listing timed out after {LIST_ALL_MODELS_TIMEOUT_SECS}s"
+                ))
+            }
+        };
+        let entry = match outcome {
+            Ok(models) => ProviderModelList {
+                models,
+                error: None,
+            },
+            Err(err) => ProviderModelList {
+                models: Vec::new(),
+                error: Some(err),
+            },
+        };
+        results.insert(provider_id, entry);
+    }
+    Ok(results)
+}
+
+fn cached_model_list(app: &AppHandle, provider_id: &str) -> Result<Option<Vec<String>>, String> {
+    let prefs = ai_prefs::load_preferences(app)?;
+    Ok(prefs
+        .providers
+        .get(provider_id)
+        .and_then(|slot| slot.model_list.clone()))
+}
+
+/// 切换离线模式并持久化，返回切换后的状态。
+pub fn set_offline_mode(app: &AppHandle, enabled: bool) -> Result<bool, String> {
+    let mut prefs = app_prefs::load_preferences(app)?;
+    prefs.offline_mode = enabled;
+    app_prefs::save_preferences(app, &prefs)?;
+    Ok(enabled)
+}
+
+/// 读取磁盘上的当前正文并编码为 `CONFLICT:{json}` 错误字符串。
+fn save_conflict(layout: &StorageLayout, date: &str, current_hash: &str) -> Result<String, String> {
+    let current_body = storage::load_entry(layout, date)?
+        .map(|record| record.body().to_string())
+        .unwrap_or_default();
+    let conflict = SaveConflict {
+        date: date.to_string(),
+        current_hash: current_hash.to_string(),
+        current_body,
+    };
+    let payload = serde_json::to_string(&conflict)
+        .map_err(|err| format!("failed to serialize save conflict: {err}"))?;
+    Ok(format!("{CONFLICT_PREFIX}{payload}"))
+}
+
+/// AI 功能缺少的配置项，错误字符串形如 `NOT_CONFIGURED:api_key`，前端可据此引导用户完成设置。
+#[derive(Debug, Clone, Copy)]
+enum NotConfigured {
+    Provider,
+    ApiKey,
+    Model,
+}
+
+impl NotConfigured {
+    const fn code(self) -> &'static str {
+        match self {
+            Self::Provider => "provider",
+            Self::ApiKey => "api_key",
+            Self::Model => "model",
+        }
+    }
+
+    fn into_error(self) -> String {
+        format!("{NOT_CONFIGURED_PREFIX}{}", self.code())
+    }
+}
+
+/// 通过配置检查的 Provider：偏好上下文、API Key 与规范化后的 Base URL。
+struct ConfiguredProvider {
+    ctx: ai_prefs::ProviderContext,
+    api_key: String,
+    api_base: String,
+}
+
+/// 统一检查 AI 调用所需的配置：依次校验 Provider、模型与 API Key，缺失时返回 `NOT_CONFIGURED:*`。
+fn require_configured_provider(
+    app: &AppHandle,
+    provider_id: Option<&str>,
+) -> Result<ConfiguredProvider, String> {
+    let provider_id = require_provider_id(provider_id)?;
+    let ctx = ai_prefs::resolve_provider_context(app, provider_id)?;
+    if ctx.model.trim().is_empty() {
+        return Err(NotConfigured::Model.into_error());
+    }
+    let api_key = load_provider_api_key(app, provider_id)?;
+    apply_network_preferences(&ctx);
+    let api_base = sanitize_api_base_url(Some(ctx.base_url.clone()), provider_id)?;
+    Ok(ConfiguredProvider {
+        ctx,
+        api_key,
+        api_base,
+    })
+}
+
+/// 校验 Provider id：缺失、空白或 `noai` 时返回 `NOT_CONFIGURED:provider`。
+fn require_provider_id(provider_id: Option<&str>) -> Result<&str, String> {
+    provider_id
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && *id != "noai")
+        .ok_or_else(|| NotConfigured::Provider.into_error())
+}
+
+/// 配置缺失的错误不会因重试或回退而恢复，应原样交给前端。
+fn is_not_configured(err: &str) -> bool {
+    err.starts_with(NOT_CONFIGURED_PREFIX)
+}
+
+/// 读取 Provider 的 API Key；无需密钥的本地 Provider 未配置时返回空字符串。
+fn load_provider_api_key(app: &AppHandle, provider_id: &str) -> Result<String, String> {
+    match secrets::load_api_key(app, provider_id)?.filter(|key| !key.trim().is_empty()) {
+        Some(key) => Ok(key),
+        None if !ai_prefs::requires_api_key(provider_id) => Ok(String::new()),
+        None => Err(NotConfigured::ApiKey.into_error()),
+    }
+}
+
+/// 切换 AI 请求调试日志并持久化，返回切换后的状态。
+pub fn set_debug_logging(app: &AppHandle, enabled: bool) -> Result<bool, String> {
+    let mut prefs = app_prefs::load_preferences(app)?;
+    prefs.debug_logging = enabled;
+    app_prefs::save_preferences(app, &prefs)?;
+    Ok(enabled)
+}
+
+/// 离线模式下拒绝任何网络请求，统一返回 `offline mode enabled`。
+fn ensure_online(app: &AppHandle) -> Result<(), String> {
+    if app_prefs::is_offline(app) {
+        return Err(OFFLINE_MODE_ERROR.to_string());
+    }
+    Ok(())
+}
+
+/// 以 JSON 模式调用 AI，并返回解析后的结构化结果（自动去除 Markdown 代码块包裹）。
+///
+/// 未在请求中指定的温度与 token 上限沿用该 Provider 的偏好设置；请求不含 system 消息时
+/// 自动前置 Provider 的 `chatSystemPrompt`。
+pub async fn invoke_ai_chat_json(
+    app: &AppHandle,
+    mut request: AiChatRequest,
+) -> Result<Value, String> {
+    let provider_id = request.provider_id.trim().to_string();
+    let ConfiguredProvider {
+        ctx: provider_ctx,
+        api_key,
+        api_base,
+    } = require_configured_provider(app, Some(&provider_id))?;
+    ensure_online(app)?;
+
+    request.json_mode = true;
+    request.scope = provider_ctx.scope.clone();
+    request.stop = provider_ctx.stop.clone();
+    // 调用方已提供 system 消息时不覆盖，否则前置 Provider 配置的默认系统提示词。
+    if let Some(prompt) = provider_ctx.chat_system_prompt.clone().filter(|_| {
+        !request
+            .messages
+            .iter()
+            .any(|msg| msg.role.eq_ignore_ascii_case("system"))
+    }) {
+        request.messages.insert(
+            0,
+            AiMessage {
+                role: "system".to_string(),
+                content: prompt,
+            },
+        );
+    }
+    request.temperature = Some(
+        request
+            .temperature
+            .map_or(provider_ctx.temperature, |value| value.clamp(0.0, 2.0)),
+    );
+    if request.max_tokens.is_none() {
+        request.max_tokens = provider_ctx.max_tokens;
+    }
+    if request.fallback_max_tokens.is_none() {
+        request.fallback_max_tokens = Some(provider_ctx.fallback_max_tokens);
+    }
+
+    let response = invoke_ai_chat_tracked(
+        app,
+        &provider_id,
+        request,
+        provider_ctx.model,
+        &api_key,
+        &api_base,
+    )
+    .await?;
+    let block = strip_code_fence_block(&response.content);
+    if block.is_empty() {
+        return Err("AI response is empty".to_string());
+    }
+    serde_json::from_str(block).map_err(|err| format!("AI response is not valid JSON: {err}"))
+}
+
+/// 汇总指定月份的 AI 摘要，请 Provider 写一段连贯的"月度回顾"（纯文本）。
+///
+/// 结果按月份内条目的组合 hash 缓存，条目或摘要未变化时直接返回缓存，不再请求 Provider。
+pub async fn monthly_digest(
+    app: &AppHandle,
+    year: u16,
+    month: u8,
+    provider_id: &str,
+) -> Result<String, String> {
+    if !(1..=12).contains(&month) {
+        return Err(format!("month must be between 1 and 12 (got {month})"));
+    }
+    let layout = storage_layout(app)?;
+    let entries = load_month_into_store(&layout, i32::from(year), u32::from(month))?;
+    let summaries: Vec<(&str, &str)> = entries
+        .iter()
+        // 旧条目没有记录摘要来源，只排除明确为本地截断的摘要，占位符在下面单独过滤。
+        .filter(|entry| entry.summary_source != Some(SummarySource::Local) && !entry.private)
+        .filter_map(|entry| {
+            let summary = entry.ai_summary.as_deref()?.trim();
+            (!summary.is_empty() && !Placeholders::is_ai_pending(summary))
+                .then_some((entry.date.as_str(), summary))
+        })
+        .collect();
+    if summaries.is_empty() {
+        return Err(format!("no AI summaries found for {year}-{month:02}"));
+    }
+
+    let provider_id = provider_id.trim();
+    let cache_key = format!("{provider_id}:{year}-{month:02}");
+    let combined_hash = fingerprint(
+        &entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{}:{}:{}",
+                    entry.date,
+                    entry.hash,
+                    entry.ai_summary.as_deref().unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    );
+    if let Some((_, digest)) = MONTHLY_DIGEST_CACHE
+        .lock()
+        .map_err(|_| "failed to lock monthly digest cache".to_string())?
+        .get(&cache_key)
+        .filter(|(hash, _)| *hash == combined_hash)
+    {
+        return Ok(digest.clone());
+    }
+
+    let ConfiguredProvider {
+        ctx: provider_ctx,
+        api_key,
+        api_base,
+    } = require_configured_provider(app, Some(provider_id))?;
+    ensure_online(app)?;
+    throttle_provider_request(
+        &MONTHLY_DIGEST_LAST_REQUEST,
+        provider_id,
+        MONTHLY_DIGEST_MIN_INTERVAL_SECS,
+        "monthly digest",
+    )?;
+
+    let request = AiChatRequest {
+        provider_id: provider_id.to_string(),
+        messages: build_monthly_digest_prompt(year, month, &summaries),
+        temperature: Some(provider_ctx.temperature),
+        max_tokens: Some(MONTHLY_DIGEST_MAX_TOKENS),
+        fallback_max_tokens: Some(MONTHLY_DIGEST_MAX_TOKENS),
+        json_mode: false,
+        scope: provider_ctx.scope.clone(),
+        json_schema: None,
+        // 停止序列面向 JSON 输出，不用于自由格式的月度回顾。
+        stop: Vec::new(),
+    };
+    let response = invoke_ai_chat_tracked(
+        app,
+        provider_id,
+        request,
+        provider_ctx.model,
+        &api_key,
+        &api_base,
+    )
+    .await?;
+    let digest = strip_code_fence_block(&response.content).trim().to_string();
+    if digest.is_empty() {
+        return Err("AI response is empty".to_string());
+    }
+
+    MONTHLY_DIGEST_CACHE
+        .lock()
+        .map_err(|_| "failed to lock monthly digest cache".to_string())?
+        .insert(cache_key, (combined_hash, digest.clone()));
+    Ok(digest)
+}
+
+/// 按 Provider 限制请求频率：距上次请求不足 `min_interval_secs` 时返回需等待的秒数，否则记录本次时间。
+fn throttle_provider_request(
+    slot: &Mutex<HashMap<String, std::time::Instant>>,
+    provider_id: &str,
+    min_interval_secs: u64,
+    label: &str,
+) -> Result<(), String> {
+    let mut last_requests = slot
+        .lock()
+        .map_err(|_| format!("failed to lock {label} rate limit"))?;
+    let min_interval = std::time::Duration::from_secs(min_interval_secs);
+    if let Some(elapsed) = last_requests
+        .get(provider_id)
+        .map(std::time::Instant::elapsed)
+        .filter(|elapsed| *elapsed < min_interval)
+    {
+        let wait = min_interval.saturating_sub(elapsed).as_secs().max(1);
+        return Err(format!(
+            "{label} requested too frequently; retry in {wait}s"
+        ));
+    }
+    last_requests.insert(provider_id.to_string(), std::time::Instant::now());
+    drop(last_requests);
+    Ok(())
+}
+
+/// 以示例正文完整运行一次摘要流程（构建提示词 → 调用 Provider → 解析），用于验证提示词、模型与密钥。
+///
+/// 不读写任何条目与存储（token 用量仍计入会话统计）；同一 Provider 的测试请求有最小间隔限制。
+pub async fn test_summarize(
+    app: &AppHandle,
+    provider_id: &str,
+    sample_body: &str,
+) -> Result<SummaryTestResult, String> {
+    let provider_id = provider_id.trim();
+    if sample_body.trim().is_empty() {
+        return Err("sample body must not be empty".to_string());
+    }
+    throttle_provider_request(
+        &TEST_SUMMARY_LAST_REQUEST,
+        provider_id,
+        TEST_SUMMARY_MIN_INTERVAL_SECS,
+        "test summary",
+    )?;
+    let ai = AiInvokePayload {
+        provider_id: Some(provider_id.to_string()),
+        prompt: None,
+        max_tokens: None,
+        temperature: None,
+        summary_temperature: None,
+        skip_ai: false,
+        private: None,
+    };
+    let today = Local::now().date_naive().format(DATE_FORMAT).to_string();
+    let (result, response) = request_ai_summary_response(app, &today, &ai, sample_body).await?;
+    Ok(SummaryTestResult {
+        summary: result.summary,
+        emoji: result.emoji,
+        truncated: result.truncated,
+        model: response.model,
+        raw: response.content,
+    })
+}
+
+/// 计算 `[from, to]` 区间内每天摘要的情绪得分（-1..1），按日期升序返回，供趋势图使用。
+///
+/// 未缓存的摘要合并为一次 AI 请求评分，结果按摘要 HASH 缓存；Provider 未配置、离线或
+/// 请求失败时，改用本地词典估算。没有摘要的日期不返回。
+pub async fn sentiment_trend(
+    app: &AppHandle,
+    from: &str,
+    to: &str,
+    provider_id: Option<&str>,
+) -> Result<Vec<SentimentPoint>, String> {
+    let from = parse_date(from)?;
+    let to = parse_date(to)?;
+    if from > to {
+        return Err("from must not be after to".to_string());
+    }
+    if (to - from).num_days() >= SENTIMENT_MAX_RANGE_DAYS {
+        return Err(format!(
+            "date range must not exceed {SENTIMENT_MAX_RANGE_DAYS} days"
+        ));
+    }
+
+    let layout = storage_layout(app)?;
+    let mut summaries: Vec<(String, String)> = Vec::new();
+    // 私密条目只用本地词典评分，不发送给 AI。
+    let mut private_dates: HashSet<String> = HashSet::new();
+    let mut cursor = NaiveDate::from_ymd_opt(from.year(), from.month(), 1)
+        .ok_or_else(|| format!("invalid date {from}"))?;
+    while cursor <= to {
+        for entry in load_month_into_store(&layout, cursor.year(), cursor.month())? {
+            let in_range = parse_date(&entry.date).is_ok_and(|date| date >= from && date <= to);
+            let summary = entry
+                .ai_summary
+                .as_deref()
+                .map(str::trim)
+                .filter(|text| !text.is_empty() && !Placeholders::is_ai_pending(text));
+            if let (true, Some(summary)) = (in_range, summary) {
+                if entry.private {
+                    private_dates.insert(entry.date.clone());
+                }
+                summaries.push((entry.date.clone(), summary.to_string()));
+            }
+        }
+        cursor = cursor
+            .checked_add_months(chrono::Months::new(1))
+            .ok_or_else(|| "date range overflow".to_string())?;
+    }
+    summaries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let cached: HashMap<String, f32> = {
+        let cache = SENTIMENT_CACHE
+            .lock()
+            .map_err(|_| "failed to lock sentiment cache".to_string())?;
+        summaries
+            .iter()
+            .filter_map(|(_, summary)| {
+                let key = fingerprint(summary);
+                cache.get(&key).map(|score| (key, *score))
+            })
+            .collect()
+    };
+    let uncached: Vec<(&str, &str)> = summaries
+        .iter()
+        .filter(|(date, summary)| {
+            !private_dates.contains(date) && !cached.contains_key(&fingerprint(summary))
+        })
+        .map(|(date, summary)| (date.as_str(), summary.as_str()))
+        .collect();
 
-/// 生成首页 Hero Greeting，由后端拼接上下文与系统提示词，前端仅传递用户偏好。
-pub async fn generate_hero_greeting(
-    app: &AppHandle,
-    request: HeroGreetingRequest,
-) -> Result<String, String> {
-    let provider_id = request.provider_id.trim();
-    if provider_id.is_empty() || provider_id == "noai" {
-        return Err("AI provider is required".to_string());
+    let mut ai_scores = HashMap::new();
+    if !uncached.is_empty() {
+        match request_sentiment_scores(app, provider_id, &uncached).await {
+            Ok(scores) => ai_scores = scores,
+            Err(err) => {
+                eprintln!("[EchoNote] sentiment scoring falls back to local lexicon: {err}");
+            }
+        }
     }
 
-    let provider_ctx = ai_prefs::resolve_provider_context(app, provider_id)?;
-    let api_key = secrets::load_api_key(app, provider_id)?
-        .ok_or_else(|| "API Key is required for AI provider".to_string())?;
-    let api_base = sanitize_api_base_url(Some(provider_ctx.base_url.clone()), provider_id)?;
-    let model = provider_ctx.model.clone();
-
-    let target_date = resolve_greeting_date(request.date.as_deref())?;
-    let timezone = resolve_timezone_label(request.timezone.as_deref());
-    let language = resolve_language_label(request.locale.as_deref());
-    let layout = storage_layout(app)?;
-    let history_context = collect_recent_ai_summaries(&layout, target_date)?;
+    let mut cache = SENTIMENT_CACHE
+        .lock()
+        .map_err(|_| "failed to lock sentiment cache".to_string())?;
+    let points = summaries
+        .into_iter()
+        .map(|(date, summary)| {
+            let key = fingerprint(&summary);
+            let ai_score = cached.get(&key).copied().or_else(|| {
+                let score = ai_scores.get(&date).copied()?;
+                cache.insert(key, score);
+                Some(score)
+            });
+            match ai_score {
+                Some(score) => SentimentPoint {
+                    date,
+                    score,
+                    source: SummarySource::Ai,
+                },
+                None => SentimentPoint {
+                    score: local_sentiment_score(&summary),
+                    date,
+                    source: SummarySource::Local,
+                },
+            }
+        })
+        .collect();
+    drop(cache);
+    Ok(points)
+}
 
-    let system_prompt =
-        build_greeting_system_prompt(target_date, &timezone, language, history_context.as_slice());
-    let user_prompt = build_greeting_user_prompt(
-        request
-            .user_prompt
-            .as_deref()
-            .or_else(|| Some(provider_ctx.greeting_prompt.as_str())),
-    );
-    let temperature = request
-        .temperature
-        .map(|value| value.clamp(0.0, 2.0))
-        .unwrap_or(provider_ctx.temperature);
-    let max_tokens = request
-        .max_tokens
-        .filter(|value| *value > 0)
-        .unwrap_or(provider_ctx.max_tokens)
-        .min(GREETING_MAX_TOKENS);
+/// 以一次 JSON 请求为所有摘要评分，返回 日期 → 得分（已限制在 -1..1）。
+async fn request_sentiment_scores(
+    app: &AppHandle,
+    provider_id: Option<&str>,
+    summaries: &[(&str, &str)],
+) -> Result<HashMap<String, f32>, String> {
+    let ConfiguredProvider {
+        ctx: provider_ctx,
+        api_key,
+        api_base,
+    } = require_configured_provider(app, provider_id)?;
+    ensure_online(app)?;
+    let provider_id = provider_id.unwrap_or_default().trim();
 
-    let ai_request = AiChatRequest {
+    let count = u32::try_from(summaries.len()).unwrap_or(u32::MAX);
+    let max_tokens = count
+        .saturating_mul(SENTIMENT_TOKENS_PER_ENTRY)
+        .saturating_add(SENTIMENT_BASE_MAX_TOKENS);
+    let lines = summaries
+        .iter()
+        .map(|(date, summary)| format!("{date}: {}", normalize_greeting_summary(summary)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let request = AiChatRequest {
         provider_id: provider_id.to_string(),
         messages: vec![
             AiMessage {
-                role: "system".into(),
-                content: system_prompt,
+                role: "system".to_string(),
+                content: "Rate the sentiment of each diary summary from -1 (very negative) to 1 (very positive), 0 being neutral. Respond with JSON only: {\"scores\":[{\"date\":\"YYYY-MM-DD\",\"score\":0.0}]}, one item per input line.".to_string(),
             },
             AiMessage {
-                role: "user".into(),
-                content: user_prompt,
+                role: "user".to_string(),
+                content: lines,
             },
         ],
-        temperature: Some(temperature),
+        temperature: Some(0.0),
         max_tokens: Some(max_tokens),
+        fallback_max_tokens: Some(max_tokens),
+        json_mode: true,
+        scope: provider_ctx.scope.clone(),
+        json_schema: None,
+        stop: provider_ctx.stop.clone(),
     };
+    let response = invoke_ai_chat_tracked(
+        app,
+        provider_id,
+        request,
+        provider_ctx.model,
+        &api_key,
+        &api_base,
+    )
+    .await?;
+    parse_sentiment_scores(&response.content)
+}
 
-    let response =
-        ai_provider::invoke_ai_chat(provider_id, ai_request, model, &api_key, &api_base).await?;
-    let greeting = extract_greeting_from_response(&response.content);
-    if greeting.is_empty() {
-        return Err("AI greeting response is empty".to_string());
+/// 解析 `{"scores":[{date, score}]}`，也接受直接返回的数组；无法识别的项被忽略。
+fn parse_sentiment_scores(content: &str) -> Result<HashMap<String, f32>, String> {
+    let value: Value = serde_json::from_str(strip_code_fence_block(content))
+        .map_err(|err| format!("failed to parse sentiment response: {err}"))?;
+    let items = value
+        .get("scores")
+        .unwrap_or(&value)
+        .as_array()
+        .ok_or_else(|| "sentiment response does not contain a scores array".to_string())?;
+    #[allow(clippy::cast_possible_truncation)]
+    let scores = items
+        .iter()
+        .filter_map(|item| {
+            let date = normalize_date(item.get("date")?.as_str()?).ok()?;
+            let score = item.get("score")?.as_f64()?;
+            score
+                .is_finite()
+                .then(|| (date, score.clamp(-1.0, 1.0) as f32))
+        })
+        .collect();
+    Ok(scores)
+}
+
+/// 基于中英文情绪词典的本地估算：(积极词数 - 消极词数) / 总命中数，无命中时为 0。
+fn local_sentiment_score(text: &str) -> f32 {
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let count = |lexicon: &[&str]| -> usize {
+        lexicon
+            .iter()
+            .map(|term| {
+                if term.is_ascii() {
+                    words.iter().filter(|word| *word == term).count()
+                } else {
+                    lower.matches(term).count()
+                }
+            })
+            .sum()
+    };
+    let positive = count(&SENTIMENT_POSITIVE_WORDS);
+    let negative = count(&SENTIMENT_NEGATIVE_WORDS);
+    let total = positive + negative;
+    if total == 0 {
+        return 0.0;
     }
-    Ok(greeting)
+    #[allow(clippy::cast_precision_loss)]
+    let score = (positive as f32 - negative as f32) / total as f32;
+    score
 }
 
-/// 查询指定 Base URL + API Key 的可用模型（API Key 来自本地后端存储）
-pub async fn list_ai_models(
+/// 构建月度回顾提示词；摘要总长超过上限时截断，并告知模型省略了多少条。
+fn build_monthly_digest_prompt(year: u16, month: u8, summaries: &[(&str, &str)]) -> Vec<AiMessage> {
+    let mut lines = Vec::new();
+    let mut used = 0;
+    for (date, summary) in summaries {
+        let line = format!("{date}: {}", normalize_greeting_summary(summary));
+        let len = line.chars().count();
+        if used + len > MONTHLY_DIGEST_MAX_INPUT_CHARS && !lines.is_empty() {
+            break;
+        }
+        used += len;
+        lines.push(line);
+    }
+    let omitted = summaries.len() - lines.len();
+    if omitted > 0 {
+        lines.push(format!("({omitted} more entries omitted)"));
+    }
+
+    vec![
+        AiMessage {
+            role: "system".to_string(),
+            content: "You write a short \"month in review\" for a personal diary. Combine the daily summaries into one cohesive paragraph covering recurring themes, notable events and overall mood. Plain text only: no title, no list, no JSON, no markdown. Write in the same language as the summaries.".to_string(),
+        },
+        AiMessage {
+            role: "user".to_string(),
+            content: format!("Month: {year}-{month:02}\nDaily summaries:\n{}", lines.join("\n")),
+        },
+    ]
+}
+
+/// 清除指定 Provider 缓存的模型列表；`refetch` 为 true 时立即重新拉取并返回新列表。
+pub async fn clear_model_cache(
     app: &AppHandle,
-    request: AiModelListRequest,
+    provider_id: &str,
+    refetch: bool,
 ) -> Result<Vec<String>, String> {
-    let provider_id = request.provider_id;
-    if provider_id == "noai" {
-        return Err("AI provider is disabled".to_string());
+    let provider_id = provider_id.trim();
+    if provider_id.is_empty() {
+        return Err("AI provider is required".to_string());
     }
 
-    let provider_ctx = ai_prefs::resolve_provider_context(app, &provider_id)?;
-    let base_url = sanitize_api_base_url(Some(provider_ctx.base_url.clone()), &provider_id)?;
-    let api_key = secrets::load_api_key(app, &provider_id)?
-        .ok_or_else(|| "API Key is required to list models".to_string())?;
-
-    match ai_provider::list_provider_models(&provider_id, base_url.trim_end_matches('/'), &api_key)
-        .await
-    {
-        Ok(models) => {
-            ai_prefs::persist_model_list(app, &provider_id, &models)?;
-            Ok(models)
-        }
-        Err(err) => {
-            let prefs = ai_prefs::load_preferences(app)?;
-            if let Some(cache) = prefs
-                .providers
-                .get(&provider_id)
-                .and_then(|slot| slot.model_list.clone())
-            {
-                return Ok(cache);
-            }
-            Err(err)
-        }
+    ai_prefs::clear_model_list(app, Some(provider_id))?;
+    if !refetch {
+        return Ok(Vec::new());
     }
+    list_ai_models(
+        app,
+        AiModelListRequest {
+            provider_id: provider_id.to_string(),
+        },
+    )
+    .await
+}
+
+/// 清除所有 Provider 缓存的模型列表，返回被清除的数量。
+pub fn clear_all_model_caches(app: &AppHandle) -> Result<usize, String> {
+    ai_prefs::clear_model_list(app, None)
+}
+
+fn build_greeting_prompts(
+    app: &AppHandle,
+    request: &HeroGreetingRequest,
+    provider_ctx: &ai_prefs::ProviderContext,
+) -> Result<GreetingPrompts, String> {
+    let target_date = resolve_greeting_date(request.date.as_deref(), request.timezone.as_deref())?;
+    let timezone = resolve_timezone_label(request.timezone.as_deref());
+    let layout = storage_layout(app)?;
+    // 未指定 locale 时依次参考近期日记的主要语言、系统语言，最后回落到简体中文。
+    let requested_locale = request
+        .locale
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string);
+    let locale = match requested_locale {
+        Some(locale) => Some(locale),
+        None => dominant_recent_language(&layout, target_date, provider_ctx.greeting_context_days)?
+            .or_else(system_locale),
+    };
+    let language = resolve_language_label(locale.as_deref());
+    let history_context = collect_recent_ai_summaries(
+        &layout,
+        target_date,
+        provider_ctx.greeting_context_days,
+        provider_ctx.greeting_ai_summaries_only,
+    )?;
+
+    let system_prompt =
+        build_greeting_system_prompt(target_date, &timezone, language, history_context.as_slice());
+    let user_prompt = build_greeting_user_prompt(
+        request
+            .user_prompt
+            .as_deref()
+            .or(Some(provider_ctx.greeting_prompt.as_str())),
+    );
+    Ok(GreetingPrompts {
+        system_prompt,
+        user_prompt,
+    })
 }
 
-fn resolve_greeting_date(raw: Option<&str>) -> Result<NaiveDate, String> {
+/// 未显式传入日期时，按调用方时区计算"今天"；时区无法解析时回落到本机时区。
+fn resolve_greeting_date(raw: Option<&str>, timezone: Option<&str>) -> Result<NaiveDate, String> {
     if let Some(date_str) = raw {
         return parse_date(date_str);
     }
-    Ok(Local::now().date_naive())
+    Ok(parse_timezone(timezone).map_or_else(
+        || Local::now().date_naive(),
+        |tz| Utc::now().with_timezone(&tz).date_naive(),
+    ))
+}
+
+/// 解析 IANA 时区名称（如 `Asia/Shanghai`）。
+fn parse_timezone(raw: Option<&str>) -> Option<Tz> {
+    raw.map(str::trim)
+        .filter(|value| !value.is_empty())
+        .and_then(|value| value.parse::<Tz>().ok())
 }
 
 fn resolve_timezone_label(raw: Option<&str>) -> String {
-    let offset_minutes = Local::now().offset().local_minus_utc();
-    let offset_label = format_timezone_offset(offset_minutes);
+    let offset_seconds = parse_timezone(raw).map_or_else(
+        || Local::now().offset().local_minus_utc(),
+        |tz| {
+            Utc::now()
+                .with_timezone(&tz)
+                .offset()
+                .fix()
+                .local_minus_utc()
+        },
+    );
+    let offset_label = format_timezone_offset(offset_seconds / 60);
     if let Some(value) = raw {
         let trimmed = value.trim();
         if !trimmed.is_empty() {
@@ -317,6 +3063,17 @@ fn resolve_timezone_label(raw: Option<&str>) -> String {
     offset_label
 }
 
+/// 将 HLC 中的物理时间戳转换为指定时区的 RFC 3339 时间；时区无法解析时使用本机时区。
+pub fn hlc_to_local(hlc: &str, timezone: Option<&str>) -> Result<String, String> {
+    let millis = hlc::timestamp_millis(hlc)?;
+    let instant = chrono::DateTime::<Utc>::from_timestamp_millis(millis)
+        .ok_or_else(|| format!("HLC timestamp out of range: {hlc}"))?;
+    Ok(parse_timezone(timezone).map_or_else(
+        || instant.with_timezone(&Local).to_rfc3339(),
+        |tz| instant.with_timezone(&tz).to_rfc3339(),
+    ))
+}
+
 fn format_timezone_offset(total_minutes: i32) -> String {
     let sign = if total_minutes >= 0 { '+' } else { '-' };
     let minutes = total_minutes.abs();
@@ -325,35 +3082,60 @@ fn format_timezone_offset(total_minutes: i32) -> String {
     format!("UTC{sign}{hours:02}:{mins:02}")
 }
 
+/// 收集近期摘要作为问候语上下文；`ai_only` 为 true 时跳过本地截断的摘要。
 fn collect_recent_ai_summaries(
     layout: &StorageLayout,
     today: NaiveDate,
+    days: usize,
+    ai_only: bool,
 ) -> Result<Vec<String>, String> {
     let mut rows = Vec::new();
-    for offset in 0..GREETING_MAX_CONTEXT_ENTRIES {
+    for offset in 0..days {
         let Some(target_date) = today.checked_sub_signed(Duration::days(offset as i64)) else {
             break;
         };
         let date_str = target_date.format(DATE_FORMAT).to_string();
-        if let Some(entry) = load_entry_summary(layout, &date_str)? {
-            if let Some(ai_summary) = entry.ai_summary {
-                let trimmed = ai_summary.trim();
-                if trimmed.is_empty() || trimmed == AI_PENDING_SUMMARY {
-                    continue;
-                }
-                let normalized = normalize_greeting_summary(trimmed);
-                rows.push(format!("{date_str}: {normalized}"));
-            }
+        if let Some(line) = load_entry_summary(layout, &date_str)?
+            .and_then(|entry| greeting_context_line(&entry, ai_only))
+        {
+            rows.push(line);
         }
     }
     Ok(rows)
 }
 
+/// 条目在问候上下文中的一行（`日期: 规范化摘要`）；私密、无摘要或被 `ai_only` 排除时返回 None。
+fn greeting_context_line(entry: &DiaryEntry, ai_only: bool) -> Option<String> {
+    if entry.private || (ai_only && entry.summary_source == Some(SummarySource::Local)) {
+        return None;
+    }
+    let trimmed = entry.ai_summary.as_deref()?.trim();
+    if trimmed.is_empty() || Placeholders::is_ai_pending(trimmed) {
+        return None;
+    }
+    Some(format!(
+        "{}: {}",
+        entry.date,
+        normalize_greeting_summary(trimmed)
+    ))
+}
+
+/// 预览条目摘要在问候上下文中的实际形式（规范化空白并截断到上限），与发送给 AI 的内容完全一致。
+///
+/// 条目不存在、为私密条目、没有摘要，或因"仅使用 AI 摘要"设置而被排除时返回 None。
+pub fn preview_context_summary(app: &AppHandle, date: &str) -> Result<Option<String>, String> {
+    let normalized_date = normalize_date(date)?;
+    let layout = storage_layout(app)?;
+    let ai_only = ai_prefs::load_advanced(app)?
+        .greeting_ai_summaries_only
+        .unwrap_or(false);
+    Ok(load_entry_summary(&layout, &normalized_date)?
+        .and_then(|entry| greeting_context_line(&entry, ai_only)))
+}
+
 fn load_entry_summary(layout: &StorageLayout, date: &str) -> Result<Option<DiaryEntry>, String> {
     if let Some(summary) = {
-        let store = STORE
-            .lock()
-            .map_err(|_| "failed to lock in-memory store".to_string())?;
+        let store = read_store()?;
         store.get(date).map(|record| record.summary().clone())
     } {
         return Ok(Some(summary));
@@ -361,11 +3143,7 @@ fn load_entry_summary(layout: &StorageLayout, date: &str) -> Result<Option<Diary
 
     if let Some(record) = storage::load_entry(layout, date)? {
         let summary = record.summary().clone();
-        let mut store = STORE
-            .lock()
-            .map_err(|_| "failed to lock in-memory store".to_string())?;
-        store.insert(date.to_string(), record);
-        prune_store_capacity(&mut store);
+        cache_record(date.to_string(), record)?;
         return Ok(Some(summary));
     }
 
@@ -422,15 +3200,261 @@ fn build_greeting_user_prompt(preference: Option<&str>) -> String {
     }
 }
 
+/// 将 locale 或 `detect_language` 的语言代码映射为提示词中的语言名称，按主语言前缀匹配。
 fn resolve_language_label(locale: Option<&str>) -> &'static str {
-    match locale.unwrap_or("zh-Hans") {
+    let normalized = locale
+        .unwrap_or("zh-Hans")
+        .trim()
+        .replace('_', "-")
+        .to_ascii_lowercase();
+    let primary = normalized.split('-').next().unwrap_or_default();
+    match primary {
         "en" => "English",
         "ja" => "Japanese",
-        "zh-Hant" => "Traditional Chinese",
+        "ko" => "Korean",
+        "fr" => "French",
+        "de" => "German",
+        "es" => "Spanish",
+        "zh" if is_traditional_chinese(&normalized) => "Traditional Chinese",
         _ => "Simplified Chinese",
     }
 }
 
+/// 统计近期条目 frontmatter 中出现最多的 `language`。
+fn dominant_recent_language(
+    layout: &StorageLayout,
+    today: NaiveDate,
+    days: usize,
+) -> Result<Option<String>, String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for offset in (0..).take(days) {
+        let Some(target_date) = today.checked_sub_signed(Duration::days(offset)) else {
+            break;
+        };
+        let date_str = target_date.format(DATE_FORMAT).to_string();
+        if let Some(language) =
+            load_entry_summary(layout, &date_str)?.and_then(|entry| entry.language)
+        {
+            *counts.entry(language).or_default() += 1;
+        }
+    }
+    Ok(counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+        .map(|(language, _)| language))
+}
+
+/// 读取系统 locale（`LC_ALL` / `LC_MESSAGES` / `LANG`），如 `en_US.UTF-8` → `en-US`。
+fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .map(|value| {
+            value
+                .split(['.', '@'])
+                .next()
+                .unwrap_or_default()
+                .replace('_', "-")
+        })
+        .find(|value| !value.is_empty() && value != "C" && value != "POSIX")
+}
+
+/// 一次问候语调用所需的全部参数，普通与流式生成共用。
+struct GreetingCall {
+    provider_id: String,
+    request: AiChatRequest,
+    model: String,
+    api_key: String,
+    api_base: String,
+}
+
+fn prepare_greeting_call(
+    app: &AppHandle,
+    request: &HeroGreetingRequest,
+) -> Result<GreetingCall, String> {
+    let provider_id = request.provider_id.trim();
+    let ConfiguredProvider {
+        ctx: provider_ctx,
+        api_key,
+        api_base,
+    } = require_configured_provider(app, Some(provider_id))?;
+    ensure_online(app)?;
+
+    let prompts = build_greeting_prompts(app, request, &provider_ctx)?;
+    let temperature = request
+        .temperature
+        .map_or(provider_ctx.temperature, |value| value.clamp(0.0, 2.0));
+    // 问候语始终限制在 GREETING_MAX_TOKENS 内，"不限制"时同样使用该上限。
+    let max_tokens = match request.max_tokens {
+        Some(0) => None,
+        Some(value) => Some(value),
+        None => provider_ctx.max_tokens,
+    }
+    .map_or(GREETING_MAX_TOKENS, |value| value.min(GREETING_MAX_TOKENS));
+
+    Ok(GreetingCall {
+        provider_id: provider_id.to_string(),
+        request: AiChatRequest {
+            provider_id: provider_id.to_string(),
+            messages: vec![
+                AiMessage {
+                    role: "system".into(),
+                    content: prompts.system_prompt,
+                },
+                AiMessage {
+                    role: "user".into(),
+                    content: prompts.user_prompt,
+                },
+            ],
+            temperature: Some(temperature),
+            max_tokens: Some(max_tokens),
+            fallback_max_tokens: Some(provider_ctx.fallback_max_tokens),
+            json_mode: true,
+            scope: provider_ctx.scope,
+            json_schema: None,
+            stop: provider_ctx.stop,
+        },
+        model: provider_ctx.greeting_model,
+        api_key,
+        api_base,
+    })
+}
+
+/// 从流式 JSON 片段中增量提取 `greeting` 字段的值。
+///
+/// 找到字符串值的起始位置后只解码新到达的片段，已解码的部分不再重复处理；
+/// 末尾不完整的转义序列留在缓冲区等待下一个片段。
+/// 若响应并非 JSON（首个字符不是 `{` 或代码块），则按纯文本直接转发。
+#[derive(Default)]
+struct GreetingStreamExtractor {
+    buffer: String,
+    state: GreetingStreamState,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum GreetingStreamState {
+    /// 尚未确定响应格式，或尚未读到 `"greeting": "`。
+    #[default]
+    Searching,
+    /// 正在解码字符串值，缓冲区只保存尚未解码的片段。
+    InValue,
+    /// 纯文本响应，片段原样转发。
+    PlainText,
+    /// 字符串值已结束，忽略之后的内容。
+    Done,
+}
+
+impl GreetingStreamExtractor {
+    fn push(&mut self, chunk: &str) -> Option<String> {
+        let fresh = match self.state {
+            GreetingStreamState::Done => return None,
+            GreetingStreamState::PlainText => chunk.to_string(),
+            GreetingStreamState::InValue => {
+                self.buffer.push_str(chunk);
+                self.decode_value()
+            }
+            GreetingStreamState::Searching => {
+                self.buffer.push_str(chunk);
+                let head = self.buffer.trim_start();
+                if head.is_empty() {
+                    return None;
+                }
+                if !head.starts_with('{') && !head.starts_with('`') {
+                    self.state = GreetingStreamState::PlainText;
+                    let text = head.to_string();
+                    self.buffer.clear();
+                    text
+                } else {
+                    let value_start = find_json_string_value(&self.buffer, "greeting")?;
+                    self.buffer.drain(..value_start);
+                    self.state = GreetingStreamState::InValue;
+                    self.decode_value()
+                }
+            }
+        };
+        Some(fresh).filter(|text| !text.is_empty())
+    }
+
+    fn decode_value(&mut self) -> String {
+        let (decoded, consumed, closed) = decode_partial_json_string(&self.buffer);
+        self.buffer.drain(..consumed);
+        if closed {
+            self.state = GreetingStreamState::Done;
+            self.buffer.clear();
+        }
+        decoded
+    }
+}
+
+/// 返回 `"key": "` 之后字符串内容的起始字节位置；键或起始引号尚未到达时返回 None。
+fn find_json_string_value(buffer: &str, key: &str) -> Option<usize> {
+    let needle = format!("\"{key}\"");
+    let key_end = buffer.find(&needle)? + needle.len();
+    let rest = &buffer[key_end..];
+    let after_colon = rest.trim_start().strip_prefix(':')?;
+    let value = after_colon.trim_start().strip_prefix('"')?;
+    Some(buffer.len() - value.len())
+}
+
+/// 解码可能被截断的 JSON 字符串内容，返回解码结果、已消费的字节数以及是否遇到结束引号；
+/// 末尾不完整的转义序列不计入已消费字节。
+fn decode_partial_json_string(raw: &str) -> (String, usize, bool) {
+    let mut decoded = String::new();
+    let mut chars = raw.chars();
+    let consumed = |chars: &std::str::Chars<'_>| raw.len() - chars.as_str().len();
+    let mut complete = 0;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => return (decoded, consumed(&chars), true),
+            '\\' => {
+                let Some(escaped) = chars.next() else {
+                    break;
+                };
+                let replacement = match escaped {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'u' => {
+                        let Some(code) = read_hex_escape(&mut chars) else {
+                            break;
+                        };
+                        if (0xD800..0xDC00).contains(&code) {
+                            // 代理对需要紧随其后的低位 \uXXXX。
+                            let low = chars
+                                .next()
+                                .zip(chars.next())
+                                .filter(|pair| *pair == ('\\', 'u'))
+                                .and_then(|_| read_hex_escape(&mut chars));
+                            let Some(low) = low else {
+                                break;
+                            };
+                            let combined = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            char::from_u32(combined).unwrap_or('\u{fffd}')
+                        } else {
+                            char::from_u32(code).unwrap_or('\u{fffd}')
+                        }
+                    }
+                    other => other,
+                };
+                decoded.push(replacement);
+            }
+            other => decoded.push(other),
+        }
+        complete = consumed(&chars);
+    }
+    (decoded, complete, false)
+}
+
+fn read_hex_escape(chars: &mut std::str::Chars<'_>) -> Option<u32> {
+    let digits: String = chars.by_ref().take(4).collect();
+    if digits.len() < 4 {
+        return None;
+    }
+    u32::from_str_radix(&digits, 16).ok()
+}
+
 fn extract_greeting_from_response(raw: &str) -> String {
     let trimmed = raw.trim();
     if trimmed.is_empty() {
@@ -438,7 +3462,7 @@ fn extract_greeting_from_response(raw: &str) -> String {
     }
 
     let block = strip_code_fence_block(trimmed);
-    if let Ok(value) = serde_json::from_str::<Value>(block.as_ref()) {
+    if let Ok(value) = serde_json::from_str::<Value>(block) {
         if let Some(text) = value.as_str() {
             let candidate = text.trim();
             if !candidate.is_empty() {
@@ -461,20 +3485,33 @@ fn extract_greeting_from_response(raw: &str) -> String {
     trimmed.to_string()
 }
 
+/// 构建摘要请求；`prefix` / `suffix` 为已归一化的单行自定义说明，
+/// 分别置于 JSON 规则前后，规则本身与日记内容的位置保持不变。
 fn build_summary_prompt(
     date: impl AsRef<str>,
     body: impl AsRef<str>,
     custom_prompt: Option<&str>,
+    prefix: Option<&str>,
+    suffix: Option<&str>,
+    language: Option<&str>,
 ) -> Vec<AiMessage> {
     let user_custom = custom_prompt.unwrap_or(ai_prefs::DEFAULT_PROMPT);
+    let language_rule = language.map_or_else(
+        || "Use the diary author's language and writing style".to_string(),
+        |label| format!("Write in {label}, matching the author's writing style"),
+    );
+    let prefix = prefix.map_or_else(String::new, |text| format!("{text}\n"));
+    let suffix = suffix.map_or_else(String::new, |text| {
+        format!("4. Style (never changes the JSON format): {text}\n")
+    });
 
     let system_prompt = format!(
-        r#"Output JSON: {{"emoji":"<1-symbol>","summary":"<≤60 chars>"}}.
+        r#"{prefix}Output JSON: {{"emoji":"<1-symbol>","summary":"<≤60 chars>"}}.
 Rules:
 1. Emoji: Reflect diary content OR current season/holiday (based on Date).
-2. Summary: Use the diary author's language and writing style. No fabrication.
+2. Summary: {language_rule}. No fabrication.
 3. JSON only. No markdown or explanations.
-Date: {}
+{suffix}Date: {}
 Diary: {}"#,
         date.as_ref(),
         body.as_ref()
@@ -492,13 +3529,92 @@ Diary: {}"#,
     ]
 }
 
-static LOGICAL_COUNTER: AtomicU64 = AtomicU64::new(0);
+/// 按界面语言解析的占位文案。
+#[derive(Debug, Clone, Copy)]
+struct Placeholders {
+    empty_entry: &'static str,
+    ai_pending: &'static str,
+}
+
+impl Placeholders {
+    fn for_locale(locale: Option<&str>) -> Self {
+        let resolved = resolve_placeholder_locale(locale);
+        let (_, empty_entry, ai_pending) = PLACEHOLDER_TEXTS
+            .iter()
+            .find(|(id, _, _)| *id == resolved)
+            .copied()
+            .unwrap_or(PLACEHOLDER_TEXTS[0]);
+        Self {
+            empty_entry,
+            ai_pending,
+        }
+    }
+
+    /// 任一语言的"生成中"占位符都视为待定摘要。
+    fn is_ai_pending(text: &str) -> bool {
+        PLACEHOLDER_TEXTS
+            .iter()
+            .any(|(_, _, ai_pending)| *ai_pending == text)
+    }
+
+    fn is_empty_entry(text: &str) -> bool {
+        PLACEHOLDER_TEXTS
+            .iter()
+            .any(|(_, empty_entry, _)| *empty_entry == text)
+    }
+
+    /// 将以其他语言写入的占位摘要替换为当前语言。
+    fn localize(self, entry: &mut DiaryEntry) {
+        let Some(summary) = entry.ai_summary.as_deref() else {
+            return;
+        };
+        if Self::is_ai_pending(summary) {
+            entry.ai_summary = Some(self.ai_pending.to_string());
+        } else if Self::is_empty_entry(summary) {
+            entry.ai_summary = Some(self.empty_entry.to_string());
+        }
+    }
+}
+
+/// 按语言前缀归一化 locale（zh-TW/zh-HK 视为繁体），未知语言回落到简体中文。
+fn resolve_placeholder_locale(locale: Option<&str>) -> &'static str {
+    let lower = locale
+        .map(str::trim)
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if lower.starts_with("zh") {
+        return if is_traditional_chinese(&lower) {
+            "zh-Hant"
+        } else {
+            "zh-Hans"
+        };
+    }
+    if lower.starts_with("en") {
+        return "en";
+    }
+    if lower.starts_with("ja") {
+        return "ja";
+    }
+    "zh-Hans"
+}
+
+/// 判断小写 locale 是否为繁体中文（zh-Hant / zh-TW / zh-HK / zh-MO）。
+fn is_traditional_chinese(locale: &str) -> bool {
+    locale
+        .split(['-', '_'])
+        .skip(1)
+        .any(|part| matches!(part, "hant" | "tw" | "hk" | "mo"))
+}
 
 fn storage_layout(app_handle: &AppHandle) -> Result<StorageLayout, String> {
-    STORAGE_LAYOUT
+    let layout = STORAGE_LAYOUT
         .get_or_try_init(|| storage::StorageLayout::prepare(app_handle))
-        .map(|layout| layout.clone())
-        .map_err(|err| err)
+        .cloned()?;
+    // 文件名格式可随时修改，每次按最新偏好应用。
+    let prefs = app_prefs::load_preferences(app_handle).unwrap_or_default();
+    Ok(layout
+        .with_filename_date_format(prefs.filename_date_format.as_deref())
+        .with_extra_roots(&prefs.extra_read_roots))
 }
 
 /// 尝试按照固定格式解析日期，错误信息中包含原始输入，方便前端调试。
@@ -507,6 +3623,21 @@ fn parse_date(date: &str) -> Result<NaiveDate, String> {
         .map_err(|err| format!("invalid date \"{date}\": {err}"))
 }
 
+/// 解析可选的起止日期（含边界）；两端都提供且起始晚于结束时返回错误，避免静默得到空结果。
+fn parse_date_range(
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<(Option<NaiveDate>, Option<NaiveDate>), String> {
+    let from = from.map(parse_date).transpose()?;
+    let to = to.map(parse_date).transpose()?;
+    if let (Some(start), Some(end)) = (from, to) {
+        if start > end {
+            return Err("from must not be after to".to_string());
+        }
+    }
+    Ok((from, to))
+}
+
 /// 返回标准 YYYY-MM-DD 字符串，持久化 key 与排序都依赖该结果。
 fn normalize_date(date: &str) -> Result<String, String> {
     Ok(parse_date(date)?.format(DATE_FORMAT).to_string())
@@ -518,26 +3649,55 @@ fn build_summary(
     date: &str,
     body: &str,
     ai_summary: String,
+    summary_source: Option<SummarySource>,
 ) -> Result<DiaryEntry, String> {
+    let hash_algo = configured_hash_algorithm(app);
     Ok(DiaryEntry {
         hlc: existing
             .map(|entry| entry.hlc.clone())
             .unwrap_or(next_hlc(app)?),
-        hash: fingerprint(body),
+        hash: hash_algo.digest(body),
+        hash_algo,
+        body_encoding: BodyEncoding::Plain,
         date: date.to_string(),
         emoji: existing.and_then(|entry| entry.emoji.clone()),
         ai_summary: Some(ai_summary),
-        language: detect_language(body),
+        // 手动指定的语言跨保存沿用，直到用户清除。
+        language: existing
+            .filter(|entry| entry.language_manual)
+            .map_or_else(|| detect_language(body), |entry| entry.language.clone()),
+        language_manual: existing.is_some_and(|entry| entry.language_manual),
+        word_count: Some(count_words(body)),
+        summary_source,
+        summary_truncated: false,
+        summary_stale: false,
+        private: existing.is_some_and(|entry| entry.private),
+        file_path: None,
+        filename_date: None,
     })
 }
 
+/// 返回可沿用的已有摘要；生成中的占位符不视为有效摘要。
+fn preserved_ai_summary(existing: Option<&DiaryEntry>) -> Option<String> {
+    existing
+        .and_then(|entry| entry.ai_summary.as_deref())
+        .map(str::trim)
+        .filter(|summary| !summary.is_empty() && !Placeholders::is_ai_pending(summary))
+        .map(str::to_string)
+}
+
 fn next_hlc(app: &AppHandle) -> Result<String, String> {
-    let timestamp = Utc::now().timestamp_millis();
-    let logical = LOGICAL_COUNTER.fetch_add(1, Ordering::SeqCst);
-    let device_id = device::device_id(app)?;
-    Ok(format!("{timestamp}-{logical}-{device_id}"))
+    hlc::next(app)
 }
 
+/// 新写入条目使用的 HASH 算法；偏好读取失败时使用默认的 BLAKE3。
+fn configured_hash_algorithm(app: &AppHandle) -> HashAlgorithm {
+    app_prefs::load_preferences(app)
+        .map(|prefs| prefs.hash_algorithm)
+        .unwrap_or_default()
+}
+
+/// 缓存 Key 等内部用途的 BLAKE3 指纹，与条目 frontmatter 的 HASH 算法无关。
 fn fingerprint(body: &str) -> String {
     let hash = blake3::hash(body.as_bytes());
     hash.to_hex().to_string()
@@ -550,9 +3710,7 @@ fn load_month_into_store(
 ) -> Result<Vec<DiaryEntry>, String> {
     let mut entries = Vec::new();
     let records = storage::load_month_entries(layout, year, month)?;
-    let mut store = STORE
-        .lock()
-        .map_err(|_| "failed to lock in-memory store".to_string())?;
+    let mut store = write_store()?;
 
     for record in records {
         let summary = record.summary().clone();
@@ -560,6 +3718,7 @@ fn load_month_into_store(
         store.insert(summary.date.clone(), record);
     }
     prune_store_capacity(&mut store);
+    drop(store);
 
     entries.sort_by(|a, b| match a.date.cmp(&b.date) {
         std::cmp::Ordering::Equal => a.hlc.cmp(&b.hlc),
@@ -569,23 +3728,98 @@ fn load_month_into_store(
     Ok(entries)
 }
 
-fn sanitize_ai_payload(mut ai: AiInvokePayload) -> Option<AiInvokePayload> {
-    let provider = ai.provider_id.as_ref()?.trim();
-    if provider.is_empty() || provider == "noai" {
-        return None;
+fn sanitize_ai_payload(mut ai: AiInvokePayload) -> Option<AiInvokePayload> {
+    let provider = ai.provider_id.as_ref()?.trim();
+    if provider.is_empty() || provider == "noai" {
+        return None;
+    }
+    ai.provider_id = Some(provider.to_string());
+    ai.prompt = ai
+        .prompt
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty());
+    ai.temperature = ai.temperature.map(|value| value.clamp(0.0, 2.0));
+    ai.summary_temperature = ai.summary_temperature.map(|value| value.clamp(0.0, 2.0));
+    Some(ai)
+}
+
+/// 每次发起 AI 请求前同步连接池设置，偏好修改后无需重启即可生效。
+fn apply_network_preferences(ctx: &ai_prefs::ProviderContext) {
+    ai_provider::configure_http_client(ai_provider::HttpClientConfig {
+        pool_idle_timeout_secs: ctx.pool_idle_timeout_secs,
+        pool_max_idle_per_host: ctx.pool_max_idle_per_host,
+    });
+    ai_provider::configure_retry_policy(ctx.retry_policy);
+    ai_provider::configure_request_limit(ctx.max_request_bytes);
+}
+
+fn default_api_base_for(provider_id: &str) -> &'static str {
+    ai_prefs::default_api_base_for(provider_id)
+}
+
+/// 可被后续任务取代的后台任务句柄。
+trait AbortHandle {
+    fn abort(&self);
+}
+
+impl AbortHandle for tauri::async_runtime::JoinHandle<()> {
+    fn abort(&self) {
+        Self::abort(self);
+    }
+}
+
+/// 按日期登记的后台摘要任务；同一天再次登记时中止旧任务，只保留最后一次保存。
+///
+/// 任务由调用方传入的 `spawn` 启动，测试中可替换为不依赖运行时与时钟的实现。
+struct RefreshRegistry<H> {
+    next_id: u64,
+    pending: HashMap<String, (u64, H)>,
+}
+
+impl<H> Default for RefreshRegistry<H> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl<H: AbortHandle> RefreshRegistry<H> {
+    /// 中止同一天尚未完成的任务，并以新编号启动任务，返回该编号。
+    fn schedule(&mut self, key: String, spawn: impl FnOnce(u64) -> H) -> u64 {
+        if let Some((_, previous)) = self.pending.remove(&key) {
+            previous.abort();
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.insert(key, (id, spawn(id)));
+        id
+    }
+
+    /// 任务结束时移除登记；若已被更新的任务取代则保持不变。
+    fn finish(&mut self, key: &str, id: u64) {
+        if self
+            .pending
+            .get(key)
+            .is_some_and(|(current, _)| *current == id)
+        {
+            self.pending.remove(key);
+        }
+    }
+
+    /// 中止并移除某天排队中的任务。
+    fn cancel(&mut self, key: &str) {
+        if let Some((_, handle)) = self.pending.remove(key) {
+            handle.abort();
+        }
     }
-    ai.provider_id = Some(provider.to_string());
-    ai.prompt = ai
-        .prompt
-        .map(|p| p.trim().to_string())
-        .filter(|p| !p.is_empty());
-    ai.max_tokens = ai.max_tokens.filter(|value| *value > 0);
-    ai.temperature = ai.temperature.map(|value| value.clamp(0.0, 2.0));
-    Some(ai)
-}
 
-fn default_api_base_for(provider_id: &str) -> &'static str {
-    ai_prefs::default_api_base_for(provider_id)
+    fn abort_all(&mut self) {
+        for (_, (_, handle)) in self.pending.drain() {
+            handle.abort();
+        }
+    }
 }
 
 fn spawn_metadata_refresh(
@@ -594,13 +3828,53 @@ fn spawn_metadata_refresh(
     ai: AiInvokePayload,
     body: String,
     expected_hash: String,
+    placeholders: Placeholders,
 ) {
     let app_handle = app.clone();
-    tauri::async_runtime::spawn(async move {
-        if let Err(err) = regenerate_entry_metadata(app_handle, date, ai, body, expected_hash).await
-        {
-            eprintln!("[EchoNote] metadata refresh failed: {err}");
-        }
+    let debounce = ai_prefs::summary_debounce(app);
+    schedule_debounced(
+        &PENDING_SUMMARY_REFRESH,
+        date.clone(),
+        debounce,
+        move || async move {
+            let result =
+                regenerate_entry_metadata(app_handle, date, ai, body, expected_hash, placeholders)
+                    .await;
+            if let Err(err) = result {
+                eprintln!(
+                    "[EchoNote] metadata refresh failed: {}",
+                    ai_provider::redact(&err)
+                );
+            }
+        },
+    );
+}
+
+/// 等待防抖窗口后运行 `task`；窗口内同一 `key` 再次登记时中止旧任务，只运行最后一次。
+fn schedule_debounced<F, Fut>(
+    registry: &'static Mutex<RefreshRegistry<tauri::async_runtime::JoinHandle<()>>>,
+    key: String,
+    debounce: std::time::Duration,
+    task: F,
+) where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    // 持锁完成"中止旧任务 + 登记新任务"，保证同一天只有最后一次保存的正文会被摘要。
+    let Ok(mut pending) = registry.lock() else {
+        eprintln!("[EchoNote] summary refresh registry poisoned, skipping refresh");
+        return;
+    };
+    pending.schedule(key.clone(), |refresh_id| {
+        tauri::async_runtime::spawn(async move {
+            if !debounce.is_zero() {
+                tokio::time::sleep(debounce).await;
+            }
+            task().await;
+            if let Ok(mut pending) = registry.lock() {
+                pending.finish(&key, refresh_id);
+            }
+        })
     });
 }
 
@@ -610,72 +3884,143 @@ async fn regenerate_entry_metadata(
     ai: AiInvokePayload,
     body: String,
     expected_hash: String,
+    placeholders: Placeholders,
 ) -> Result<(), String> {
     let layout = storage_layout(&app)?;
+    let outcome = request_summary_with_retries(&app, &date, &ai, &body).await;
 
-    // Retry logic: try up to 3 times (initial + 2 retries)
-    let mut attempts = 0;
-    let max_attempts = 3;
-    let mut summary_result = Err("Initial".to_string());
-
-    while attempts < max_attempts {
-        summary_result = request_ai_summary(&app, &date, &ai, &body).await;
-        if summary_result.is_ok() {
-            break;
-        }
-        attempts += 1;
-        if attempts < max_attempts {
+    let (generated, source) = match outcome {
+        Ok(res) => (res, SummarySource::Ai),
+        Err(event) => {
             eprintln!(
-                "[EchoNote] AI summary failed (attempt {}/{}), retrying...",
-                attempts, max_attempts
+                "[EchoNote] AI summary failed after {} attempts: {}. Falling back to local summary.",
+                event.attempt, event.error
             );
-        }
-    }
-
-    let AiSummaryResult {
-        summary: ai_summary,
-        emoji: ai_emoji,
-    } = match summary_result {
-        Ok(res) => res,
-        Err(err) => {
-            eprintln!("[EchoNote] AI summary failed after {} attempts: {}. Falling back to local summary.", attempts, err);
-            let local_summary =
-                summarize_body(&body).unwrap_or_else(|| EMPTY_ENTRY_SUMMARY.to_string());
-            AiSummaryResult {
-                summary: local_summary,
-                emoji: None,
+            if let Err(emit_err) = app.emit(AI_SUMMARY_FALLBACK_EVENT, &event) {
+                eprintln!("[EchoNote] failed to emit summary fallback event: {emit_err}");
             }
+            let local_summary =
+                summarize_body(&body).unwrap_or_else(|| placeholders.empty_entry.to_string());
+            (
+                AiSummaryResult {
+                    summary: local_summary,
+                    emoji: None,
+                    truncated: false,
+                },
+                SummarySource::Local,
+            )
         }
     };
 
-    let (updated_summary, persisted_body) = {
-        let mut store = STORE
-            .lock()
-            .map_err(|_| "failed to lock in-memory store".to_string())?;
-        let Some(record) = store.get_mut(&date) else {
-            return Ok(());
-        };
+    let write_options = app_prefs::write_options(&app)?;
+    let Some(updated_summary) = persist_generated_summary(
+        &layout,
+        &write_options,
+        &date,
+        &expected_hash,
+        body,
+        generated,
+        source,
+    )?
+    else {
+        return Ok(());
+    };
+    let event = EntryMetadataUpdated {
+        entry: updated_summary,
+        previous_hash: expected_hash,
+    };
+    app.emit(ENTRY_METADATA_EVENT, &event)
+        .map_err(|err| format!("failed to emit metadata event: {err}"))?;
+    Ok(())
+}
 
-        if record.summary().hash != expected_hash {
-            return Ok(());
-        }
+/// 在保存锁内核对正文 hash 并写回生成的摘要，缓存未命中时从磁盘读取条目。
+///
+/// 正文已变化，或摘要生成期间条目被设为私密时丢弃本次结果并返回 None；条目已不存在时返回错误。
+fn persist_generated_summary(
+    layout: &StorageLayout,
+    write_options: &storage::WriteOptions,
+    date: &str,
+    expected_hash: &str,
+    body: String,
+    generated: AiSummaryResult,
+    source: SummarySource,
+) -> Result<Option<DiaryEntry>, String> {
+    let save_guard = lock_entry_save()?;
+    let mut summary = load_entry_summary(layout, date)?
+        .ok_or_else(|| format!("entry {date} not found, discarding generated summary"))?;
+    if summary.hash != expected_hash || summary.private {
+        return Ok(None);
+    }
 
-        let mut summary = record.summary().clone();
-        summary.ai_summary = Some(ai_summary);
-        if let Some(new_emoji) = ai_emoji {
-            summary.emoji = Some(new_emoji);
-        }
+    summary.ai_summary = Some(generated.summary);
+    summary.summary_source = Some(source);
+    summary.summary_truncated = generated.truncated;
+    summary.summary_stale = false;
+    if let Some(new_emoji) = generated.emoji {
+        summary.emoji = Some(new_emoji);
+    }
+    if !summary.language_manual {
         summary.language = detect_language(&body);
+    }
+    summary.word_count = Some(count_words(&body));
 
-        record.update(summary.clone(), body.clone());
+    storage::write_entry(layout, &summary, &body, write_options)?;
+    cache_record(date.to_string(), EntryRecord::new(summary.clone(), body))?;
+    drop(save_guard);
+    Ok(Some(summary))
+}
 
-        (summary, body)
-    };
+/// 按偏好的尝试次数（含首次）请求 AI 摘要，每次失败都会通知前端；全部失败时返回最后一次的失败事件。
+async fn request_summary_with_retries(
+    app: &AppHandle,
+    date: &str,
+    ai: &AiInvokePayload,
+    body: &str,
+) -> Result<AiSummaryResult, AiSummaryAttempt> {
+    let max_attempts = ai
+        .provider_id
+        .as_deref()
+        .and_then(|provider_id| ai_prefs::resolve_provider_context(app, provider_id).ok())
+        .map_or(ai_prefs::DEFAULT_SUMMARY_RETRY_ATTEMPTS, |ctx| {
+            ctx.summary_retry_attempts
+        });
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        // 空白摘要视同失败，走重试与本地回退，避免把空的 ai_summary 写入磁盘。
+        match request_ai_summary(app, date, ai, body)
+            .await
+            .and_then(non_empty_summary)
+        {
+            Ok(result) => return Ok(result),
+            Err(err) => {
+                if let Some(provider_id) = ai.provider_id.as_deref() {
+                    record_provider_error(provider_id, "summary", &err);
+                }
+                let event = AiSummaryAttempt {
+                    date: date.to_string(),
+                    attempt,
+                    max_attempts,
+                    error: ai_provider::redact(&err),
+                };
+                // 离线模式与配置缺失等不可重试的错误直接回退。
+                if attempt >= max_attempts || !is_retryable_summary_error(&err) {
+                    return Err(event);
+                }
+                eprintln!(
+                    "[EchoNote] AI summary failed (attempt {attempt}/{max_attempts}), retrying..."
+                );
+                if let Err(emit_err) = app.emit(AI_SUMMARY_RETRY_EVENT, &event) {
+                    eprintln!("[EchoNote] failed to emit summary retry event: {emit_err}");
+                }
+            }
+        }
+    }
+}
 
-    storage::write_entry(&layout, &updated_summary, &persisted_body)?;
-    app.emit(ENTRY_METADATA_EVENT, &updated_summary)
-        .map_err(|err| format!("failed to emit metadata event: {err}"))?;
-    Ok(())
+fn is_retryable_summary_error(err: &str) -> bool {
+    err != OFFLINE_MODE_ERROR && !is_not_configured(err)
 }
 
 async fn request_ai_summary(
@@ -684,14 +4029,57 @@ async fn request_ai_summary(
     ai: &AiInvokePayload,
     body: &str,
 ) -> Result<AiSummaryResult, String> {
-    let provider_id = ai
-        .provider_id
-        .as_ref()
-        .ok_or_else(|| "AI provider is required".to_string())?;
-    let provider_ctx = ai_prefs::resolve_provider_context(app, provider_id)?;
-    let api_key = secrets::load_api_key(app, provider_id)?
-        .ok_or_else(|| "API Key is required for AI provider".to_string())?;
-    let api_base = sanitize_api_base_url(Some(provider_ctx.base_url.clone()), provider_id)?;
+    request_ai_summary_response(app, date, ai, body)
+        .await
+        .map(|(result, _)| result)
+}
+
+/// 组装摘要请求：温度与 token 上限优先取本次调用的覆盖值，其次是 Provider 偏好。
+fn summary_request(
+    provider_id: &str,
+    ai: &AiInvokePayload,
+    provider_ctx: &ai_prefs::ProviderContext,
+    messages: Vec<AiMessage>,
+) -> AiChatRequest {
+    // 显式传入 0 表示不限制，未传入时沿用偏好设置。
+    let max_tokens = match ai.max_tokens {
+        Some(0) => None,
+        Some(value) => Some(value),
+        None => provider_ctx.max_tokens,
+    };
+    // 摘要使用独立的低温度，不受 `invoke_ai_chat_json` 等流程的温度影响。
+    let temperature = ai
+        .summary_temperature
+        .map_or(provider_ctx.summary_temperature, |value| {
+            value.clamp(0.0, 2.0)
+        });
+    AiChatRequest {
+        provider_id: provider_id.to_string(),
+        messages,
+        temperature: Some(temperature),
+        max_tokens,
+        fallback_max_tokens: Some(provider_ctx.fallback_max_tokens),
+        json_mode: true,
+        scope: provider_ctx.scope.clone(),
+        json_schema: provider_ctx.structured_outputs.then(summary_json_schema),
+        stop: provider_ctx.stop.clone(),
+    }
+}
+
+/// 请求摘要并同时返回解析结果与 Provider 的原始响应。
+async fn request_ai_summary_response(
+    app: &AppHandle,
+    date: &str,
+    ai: &AiInvokePayload,
+    body: &str,
+) -> Result<(AiSummaryResult, AiChatResult), String> {
+    let provider_id = require_provider_id(ai.provider_id.as_deref())?;
+    let ConfiguredProvider {
+        ctx: provider_ctx,
+        api_key,
+        api_base,
+    } = require_configured_provider(app, Some(provider_id))?;
+    ensure_online(app)?;
 
     let model = provider_ctx.model.clone();
     let prompt = ai
@@ -701,31 +4089,140 @@ async fn request_ai_summary(
         .filter(|text| !text.is_empty())
         .map(|text| text.to_string())
         .unwrap_or_else(|| provider_ctx.prompt.clone());
-    let max_tokens = ai
-        .max_tokens
-        .filter(|value| *value > 0)
-        .unwrap_or(provider_ctx.max_tokens);
-    let temperature = ai
-        .temperature
-        .map(|value| value.clamp(0.0, 2.0))
-        .unwrap_or(provider_ctx.temperature);
+    // 用户手动指定了条目语言时，要求摘要使用该语言。
+    let manual_language = read_store()?
+        .get(date)
+        .map(EntryRecord::summary)
+        .filter(|summary| summary.language_manual)
+        .and_then(|summary| summary.language.clone());
 
-    let request = AiChatRequest {
-        provider_id: provider_id.to_string(),
-        messages: build_summary_prompt(date, body, Some(&prompt)),
-        temperature: Some(temperature),
-        max_tokens: Some(max_tokens),
+    let language_label = manual_language
+        .as_deref()
+        .map(|code| resolve_language_label(Some(code)));
+    let build_messages = |body: &str| {
+        build_summary_prompt(
+            date,
+            body,
+            Some(&prompt),
+            provider_ctx.summary_prompt_prefix.as_deref(),
+            provider_ctx.summary_prompt_suffix.as_deref(),
+            language_label,
+        )
     };
 
-    let response =
-        ai_provider::invoke_ai_chat(provider_id, request, model, &api_key, &api_base).await?;
-    Ok(parse_ai_summary_response(&response.content))
+    let mut request = summary_request(provider_id, ai, &provider_ctx, build_messages(body));
+    // 超出请求体积上限时截断正文，使摘要仍能基于开头部分生成；提示词本身超限则交由调用层报错。
+    let limit = usize::try_from(provider_ctx.max_request_bytes).unwrap_or(usize::MAX);
+    if ai_provider::estimate_request_bytes(&request) > limit {
+        request.messages = build_messages("");
+        let overhead = ai_provider::estimate_request_bytes(&request);
+        let kept = truncate_to_json_bytes(body, limit.saturating_sub(overhead));
+        eprintln!(
+            "[EchoNote] summary input for {date} truncated to {} of {} bytes to fit the request limit",
+            kept.len(),
+            body.len()
+        );
+        request.messages = build_messages(kept);
+    }
+
+    let mut response = invoke_ai_chat_tracked(
+        app,
+        provider_id,
+        request.clone(),
+        model.clone(),
+        &api_key,
+        &api_base,
+    )
+    .await?;
+    // 输出被 token 上限截断时，按偏好加倍上限重试一次；仍被截断则标记给前端。
+    if is_length_finish(response.finish_reason.as_deref()) && provider_ctx.retry_truncated_summaries
+    {
+        request.max_tokens = request.max_tokens.map(|value| value.saturating_mul(2));
+        request.fallback_max_tokens = request
+            .fallback_max_tokens
+            .map(|value| value.saturating_mul(2));
+        response =
+            invoke_ai_chat_tracked(app, provider_id, request, model, &api_key, &api_base).await?;
+    }
+
+    let mut result = parse_ai_summary_response(&response.content, provider_ctx.max_emoji_graphemes);
+    result.truncated = is_length_finish(response.finish_reason.as_deref());
+    Ok((result, response))
+}
+
+/// 按 JSON 转义后的字节数截取前缀，保证截断点落在字符边界上。
+fn truncate_to_json_bytes(text: &str, budget: usize) -> &str {
+    let mut used = 0;
+    for (index, ch) in text.char_indices() {
+        let escaped = match ch {
+            '"' | '\\' | '\n' | '\r' | '\t' | '\u{08}' | '\u{0C}' => 2,
+            ch if ch < ' ' => 6,
+            ch => ch.len_utf8(),
+        };
+        if used + escaped > budget {
+            return &text[..index];
+        }
+        used += escaped;
+    }
+    text
+}
+
+/// 各 Provider 表示"达到 token 上限"的结束原因：OpenAI `length`、Gemini `MAX_TOKENS`、Claude `max_tokens`。
+fn is_length_finish(reason: Option<&str>) -> bool {
+    reason.is_some_and(|reason| {
+        reason.eq_ignore_ascii_case("length") || reason.eq_ignore_ascii_case("max_tokens")
+    })
+}
+
+/// 调用 AI 并将返回的 usage 计入会话统计，同时写入调试日志；统计失败不影响调用结果。
+async fn invoke_ai_chat_tracked(
+    app: &AppHandle,
+    provider_id: &str,
+    request: AiChatRequest,
+    model: String,
+    api_key: &str,
+    api_base: &str,
+) -> Result<ai_provider::AiChatResult, String> {
+    let started = std::time::Instant::now();
+    let model_label = model.clone();
+    let result = ai_provider::invoke_ai_chat(provider_id, request, model, api_key, api_base).await;
+    ai_log::record(
+        app,
+        &AiLogEntry::new(
+            provider_id,
+            "chat",
+            api_base,
+            Some(&model_label),
+            started,
+            result.as_ref().map(Some).map_err(String::as_str),
+        ),
+    );
+    if let Err(err) = &result {
+        record_provider_error(provider_id, "chat", err);
+    }
+    let response = result?;
+    record_session_usage(provider_id, &response);
+    Ok(response)
+}
+
+fn record_session_usage(provider_id: &str, response: &ai_provider::AiChatResult) {
+    if let Ok(mut usage) = SESSION_USAGE.lock() {
+        let slot = usage.entry(provider_id.to_string()).or_default();
+        let prompt = u64::from(response.prompt_tokens.unwrap_or(0));
+        let completion = u64::from(response.completion_tokens.unwrap_or(0));
+        slot.requests += 1;
+        slot.prompt_tokens += prompt;
+        slot.completion_tokens += completion;
+        slot.total_tokens += response.total_tokens.map_or(prompt + completion, u64::from);
+    }
 }
 
 #[derive(Debug, Clone)]
 struct AiSummaryResult {
     summary: String,
     emoji: Option<String>,
+    /// Provider 因 token 上限截断了输出。
+    truncated: bool,
 }
 
 #[derive(Deserialize)]
@@ -734,31 +4231,59 @@ struct AiSummaryJsonPayload {
     emoji: Option<String>,
 }
 
-fn parse_ai_summary_response(raw: &str) -> AiSummaryResult {
+/// 摘要输出的严格 Schema，与 `AiSummaryJsonPayload` 的字段一致。
+fn summary_json_schema() -> ai_provider::JsonSchemaSpec {
+    ai_provider::JsonSchemaSpec {
+        name: "diary_summary".to_string(),
+        schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "emoji": { "type": "string" },
+                "summary": { "type": "string" }
+            },
+            "required": ["emoji", "summary"],
+            "additionalProperties": false
+        }),
+    }
+}
+
+fn non_empty_summary(result: AiSummaryResult) -> Result<AiSummaryResult, String> {
+    if result.summary.trim().is_empty() {
+        return Err("AI returned an empty summary".to_string());
+    }
+    Ok(result)
+}
+
+fn parse_ai_summary_response(raw: &str, max_emoji_graphemes: usize) -> AiSummaryResult {
     let trimmed = raw.trim();
     if trimmed.is_empty() {
         return AiSummaryResult {
             summary: String::new(),
             emoji: None,
+            truncated: false,
         };
     }
 
-    if let Some(result) = parse_ai_summary_json(trimmed) {
+    if let Some(result) = parse_ai_summary_json(trimmed, max_emoji_graphemes) {
         return result;
     }
 
-    parse_ai_summary_fallback(trimmed)
+    parse_ai_summary_fallback(trimmed, max_emoji_graphemes)
 }
 
-fn parse_ai_summary_json(raw: &str) -> Option<AiSummaryResult> {
+fn parse_ai_summary_json(raw: &str, max_emoji_graphemes: usize) -> Option<AiSummaryResult> {
     let block = strip_code_fence_block(raw);
-    let payload: AiSummaryJsonPayload = serde_json::from_str(block.as_ref()).ok()?;
-    let summary = sanitize_summary_text(payload.summary, block.as_ref());
-    let emoji = sanitize_emoji_text(payload.emoji);
-    Some(AiSummaryResult { summary, emoji })
+    let payload: AiSummaryJsonPayload = serde_json::from_str(block).ok()?;
+    let summary = sanitize_summary_text(payload.summary, block);
+    let emoji = sanitize_emoji_text(payload.emoji, max_emoji_graphemes);
+    Some(AiSummaryResult {
+        summary,
+        emoji,
+        truncated: false,
+    })
 }
 
-fn parse_ai_summary_fallback(raw: &str) -> AiSummaryResult {
+fn parse_ai_summary_fallback(raw: &str, max_emoji_graphemes: usize) -> AiSummaryResult {
     if let Some((idx, width)) = find_summary_delimiter(raw) {
         let emoji_candidate = raw[..idx].trim().trim_start_matches('$').trim();
         let summary_candidate = raw[idx + width..].trim();
@@ -767,35 +4292,47 @@ fn parse_ai_summary_fallback(raw: &str) -> AiSummaryResult {
         } else {
             summary_candidate
         };
-        let emoji = sanitize_emoji_text(Some(emoji_candidate.to_string()));
+        let emoji = sanitize_emoji_text(Some(emoji_candidate.to_string()), max_emoji_graphemes);
         return AiSummaryResult {
             summary: summary_text.to_string(),
             emoji,
+            truncated: false,
         };
     }
 
     AiSummaryResult {
         summary: raw.to_string(),
         emoji: None,
+        truncated: false,
     }
 }
 
-fn strip_code_fence_block(input: &str) -> Cow<'_, str> {
+/// 去掉包裹在回复外层的 Markdown 代码块，返回其中的内容。
+///
+/// 开头一行是围栏及可选的语言标记（如 ```` ```json ````）；闭合围栏须独占一行且反引号数量
+/// 不少于开头，因此内容中的行内 ```` ``` ```` 不会被误判，闭合围栏之后的说明文字会被丢弃。
+/// 缺少闭合围栏时返回开头之后的全部内容；不以围栏开头的回复原样返回。
+fn strip_code_fence_block(input: &str) -> &str {
     let trimmed = input.trim();
-    if !trimmed.starts_with("```") {
-        return Cow::Borrowed(trimmed);
+    let fence_len = trimmed.bytes().take_while(|byte| *byte == b'`').count();
+    if fence_len < 3 {
+        return trimmed;
     }
+    let Some((_, rest)) = trimmed.split_once('\n') else {
+        return trimmed;
+    };
 
-    let mut parts = trimmed.splitn(2, '\n');
-    let _ = parts.next();
-    if let Some(rest) = parts.next() {
-        if let Some(end_idx) = rest.rfind("```") {
-            return Cow::Owned(rest[..end_idx].trim().to_string());
+    let mut end = rest.len();
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        let candidate = line.trim();
+        if candidate.len() >= fence_len && candidate.bytes().all(|byte| byte == b'`') {
+            end = offset;
+            break;
         }
-        return Cow::Owned(rest.trim().to_string());
+        offset += line.len();
     }
-
-    Cow::Borrowed(trimmed)
+    rest[..end].trim()
 }
 
 fn sanitize_summary_text(value: Option<String>, fallback: &str) -> String {
@@ -811,11 +4348,12 @@ fn sanitize_summary_text(value: Option<String>, fallback: &str) -> String {
         .unwrap_or_else(|| fallback.trim().to_string())
 }
 
-fn sanitize_emoji_text(value: Option<String>) -> Option<String> {
+/// 按字素簇计数，国旗与 ZWJ 组合 Emoji（如 👨‍👩‍👧‍👦）虽由多个码点组成，仍只算 1 个。
+fn sanitize_emoji_text(value: Option<String>, max_graphemes: usize) -> Option<String> {
     value.and_then(|text| {
         let trimmed = text.trim();
-        let char_count = trimmed.chars().count();
-        if trimmed.is_empty() || char_count > 8 {
+        let grapheme_count = trimmed.graphemes(true).count();
+        if trimmed.is_empty() || grapheme_count > max_graphemes {
             None
         } else {
             Some(trimmed.to_string())
@@ -856,6 +4394,83 @@ fn summarize_body(body: &str) -> Option<String> {
     Some(summary)
 }
 
+/// 统计字数：CJK 表意字符、假名与谚文逐字计数，其余文本按空白分词。
+fn count_words(body: &str) -> u32 {
+    let mut count: u32 = 0;
+    let mut in_word = false;
+    for ch in body.chars() {
+        if is_cjk_char(ch) {
+            count = count.saturating_add(1);
+            in_word = false;
+        } else if ch.is_whitespace() {
+            in_word = false;
+        } else if !in_word {
+            count = count.saturating_add(1);
+            in_word = true;
+        }
+    }
+    count
+}
+
+/// 提取关键词词频：拉丁文字按单词（长度 ≥ 3，去除常见停用词），CJK 文字按相邻二字组。
+fn keyword_terms(text: &str) -> HashMap<String, u32> {
+    let mut terms: HashMap<String, u32> = HashMap::new();
+    let mut word = String::new();
+    let mut previous_cjk: Option<char> = None;
+    let flush_word = |word: &mut String, terms: &mut HashMap<String, u32>| {
+        if word.chars().count() >= 3 && !RELATED_STOP_WORDS.contains(&word.as_str()) {
+            *terms.entry(word.clone()).or_default() += 1;
+        }
+        word.clear();
+    };
+
+    for ch in text.chars() {
+        if is_cjk_char(ch) {
+            flush_word(&mut word, &mut terms);
+            if let Some(prev) = previous_cjk {
+                *terms.entry(format!("{prev}{ch}")).or_default() += 1;
+            }
+            previous_cjk = Some(ch);
+        } else if ch.is_alphanumeric() {
+            previous_cjk = None;
+            word.extend(ch.to_lowercase());
+        } else {
+            previous_cjk = None;
+            flush_word(&mut word, &mut terms);
+        }
+    }
+    flush_word(&mut word, &mut terms);
+    terms
+}
+
+/// 词频重合度：共享词频之和除以候选词频总数的平方根，避免长摘要天然占优。
+fn keyword_overlap(target: &HashMap<String, u32>, candidate: &HashMap<String, u32>) -> f64 {
+    let candidate_total: u32 = candidate.values().sum();
+    if candidate_total == 0 {
+        return 0.0;
+    }
+    let shared: u32 = candidate
+        .iter()
+        .filter_map(|(term, count)| {
+            target
+                .get(term)
+                .map(|target_count| (*count).min(*target_count))
+        })
+        .sum();
+    f64::from(shared) / f64::from(candidate_total).sqrt()
+}
+
+const fn is_cjk_char(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{3040}'..='\u{30ff}'
+            | '\u{3400}'..='\u{4dbf}'
+            | '\u{4e00}'..='\u{9fff}'
+            | '\u{ac00}'..='\u{d7af}'
+            | '\u{f900}'..='\u{faff}'
+    )
+}
+
 fn detect_language(body: &str) -> Option<String> {
     let trimmed = body.trim();
     if trimmed.is_empty() {
@@ -873,7 +4488,26 @@ fn detect_language(body: &str) -> Option<String> {
         .count();
     let ratio = ascii_letters as f32 / total as f32;
     if ratio > 0.6 {
-        Some("en".to_string())
+        return Some("en".to_string());
+    }
+
+    // 按文字区块区分中日韩：日文几乎总会夹带假名，韩文以谚文为主。
+    let kana = trimmed
+        .chars()
+        .filter(|ch| matches!(ch, '\u{3040}'..='\u{30ff}'))
+        .count();
+    let hangul = trimmed
+        .chars()
+        .filter(|ch| matches!(ch, '\u{ac00}'..='\u{d7af}' | '\u{1100}'..='\u{11ff}'))
+        .count();
+    let han = trimmed
+        .chars()
+        .filter(|ch| matches!(ch, '\u{4e00}'..='\u{9fff}'))
+        .count();
+    if hangul > 0 && hangul >= han {
+        Some("ko".to_string())
+    } else if kana > 0 {
+        Some("ja".to_string())
     } else {
         Some("zh".to_string())
     }
@@ -886,12 +4520,58 @@ fn sanitize_api_base_url(raw: Option<String>, provider_id: &str) -> Result<Strin
     }
 
     let parsed = Url::parse(value.trim()).map_err(|err| format!("invalid AI base URL: {err}"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!(
+            "AI base URL must use http or https (got \"{}\")",
+            parsed.scheme()
+        ));
+    }
     parsed
         .host_str()
+        .filter(|host| !host.is_empty())
         .ok_or_else(|| "AI base URL is missing host".to_string())?;
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        return Err(
+            "AI base URL must not embed credentials; store the API key instead".to_string(),
+        );
+    }
+    if parsed.fragment().is_some() {
+        return Err("AI base URL must not contain a fragment".to_string());
+    }
+
+    // 查询参数（如 Azure 的 `?api-version=`）原样保留，请求时由 `join_endpoint` 放回接口路径之后。
+    let query = parsed
+        .query()
+        .filter(|query| !query.is_empty())
+        .map(str::to_string);
+    let mut base = parsed;
+    base.set_query(None);
+    let base = String::from(base).trim_end_matches('/').to_string();
+    Ok(match query {
+        Some(query) => format!("{base}?{query}"),
+        None => base,
+    })
+}
+
+fn read_store() -> Result<RwLockReadGuard<'static, HashMap<String, EntryRecord>>, String> {
+    STORE
+        .read()
+        .map_err(|_| "failed to lock in-memory store".to_string())
+}
+
+fn write_store() -> Result<RwLockWriteGuard<'static, HashMap<String, EntryRecord>>, String> {
+    STORE
+        .write()
+        .map_err(|_| "failed to lock in-memory store".to_string())
+}
 
-    let url_string: String = parsed.into();
-    Ok(url_string.trim_end_matches('/').to_string())
+/// 写入单条缓存记录并按容量上限裁剪，写锁只在本函数内持有。
+fn cache_record(date: String, record: EntryRecord) -> Result<(), String> {
+    let mut store = write_store()?;
+    store.insert(date, record);
+    prune_store_capacity(&mut store);
+    drop(store);
+    Ok(())
 }
 
 fn prune_store_capacity(store: &mut HashMap<String, EntryRecord>) {
@@ -918,3 +4598,349 @@ fn prune_store_capacity(store: &mut HashMap<String, EntryRecord>) {
         store.remove(&key);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIOR_AI_SUMMARY: &str = "A calm day spent reading by the window.";
+
+    fn ai_summarized_entry(body: &str) -> DiaryEntry {
+        let mut entry: DiaryEntry =
+            serde_yaml::from_str("hlc: test\nhash: ''\ndate: 2024-05-01\n").unwrap();
+        entry.hash = entry.hash_algo.digest(body);
+        entry.ai_summary = Some(PRIOR_AI_SUMMARY.to_string());
+        entry.summary_source = Some(SummarySource::Ai);
+        entry
+    }
+
+    fn placeholders() -> Placeholders {
+        Placeholders::for_locale(Some("en"))
+    }
+
+    #[test]
+    fn skip_ai_save_keeps_prior_ai_summary() {
+        let existing = ai_summarized_entry("Read all afternoon.");
+        let choice = choose_saved_summary(
+            Some(&existing),
+            "Read all afternoon.",
+            false,
+            true,
+            placeholders(),
+        );
+        assert_eq!(choice.text, PRIOR_AI_SUMMARY);
+        assert_eq!(choice.source, Some(SummarySource::Ai));
+        assert!(!choice.stale);
+
+        // 没有可沿用的摘要时，跳过 AI 直接写入未过期的本地摘要。
+        let mut unsummarized = ai_summarized_entry("Read all afternoon.");
+        unsummarized.ai_summary = None;
+        unsummarized.summary_source = None;
+        for existing in [None, Some(&unsummarized)] {
+            let body = "Read all afternoon, then cooked dinner.";
+            let choice = choose_saved_summary(existing, body, false, true, placeholders());
+            assert_eq!(Some(choice.text), summarize_body(body));
+            assert_eq!(choice.source, Some(SummarySource::Local));
+            assert!(!choice.truncated);
+            assert!(!choice.stale);
+        }
+    }
+
+    #[test]
+    fn skip_ai_save_with_edited_body_marks_summary_stale() {
+        let existing = ai_summarized_entry("Read all afternoon.");
+        let choice = choose_saved_summary(Some(&existing), "ok", false, true, placeholders());
+        assert_eq!(choice.text, PRIOR_AI_SUMMARY);
+        assert_eq!(choice.source, Some(SummarySource::Ai));
+        assert!(choice.stale);
+    }
+
+    #[test]
+    fn legacy_summary_without_source_counts_as_ai() {
+        let mut legacy = ai_summarized_entry("Read all afternoon.");
+        legacy.summary_source = None;
+        assert!(greeting_context_line(&legacy, true).is_some());
+
+        legacy.summary_source = Some(SummarySource::Local);
+        assert!(greeting_context_line(&legacy, true).is_none());
+        assert!(greeting_context_line(&legacy, false).is_some());
+    }
+
+    #[test]
+    fn blank_ai_summary_is_rejected_for_local_fallback() {
+        for raw in ["", "  \n "] {
+            assert!(non_empty_summary(parse_ai_summary_response(raw, 1)).is_err());
+        }
+        let parsed = parse_ai_summary_response(r#"{"summary": "A calm day."}"#, 1);
+        assert_eq!(non_empty_summary(parsed).unwrap().summary, "A calm day.");
+    }
+
+    #[test]
+    fn composite_emoji_count_as_one_grapheme() {
+        for emoji in ["👨\u{200d}👩\u{200d}👧\u{200d}👦", "🇯🇵"] {
+            assert_eq!(
+                sanitize_emoji_text(Some(format!(" {emoji} ")), 1).as_deref(),
+                Some(emoji)
+            );
+        }
+        assert_eq!(sanitize_emoji_text(Some("🇯🇵🌸".to_string()), 1), None);
+        assert_eq!(
+            sanitize_emoji_text(Some("🇯🇵🌸".to_string()), 2).as_deref(),
+            Some("🇯🇵🌸")
+        );
+    }
+
+    #[test]
+    fn private_entry_never_reaches_greeting_context() {
+        let mut entry = ai_summarized_entry("Read all afternoon.");
+        assert!(greeting_context_line(&entry, false).is_some());
+        entry.private = true;
+        assert_eq!(greeting_context_line(&entry, false), None);
+        assert_eq!(greeting_context_line(&entry, true), None);
+    }
+
+    #[test]
+    fn missing_configuration_is_not_retried() {
+        assert_eq!(
+            require_provider_id(Some(" noai ")),
+            Err(NotConfigured::Provider.into_error())
+        );
+        assert_eq!(require_provider_id(Some(" openai ")), Ok("openai"));
+        assert!(!is_retryable_summary_error(
+            &NotConfigured::ApiKey.into_error()
+        ));
+        assert!(!is_retryable_summary_error(OFFLINE_MODE_ERROR));
+        assert!(is_retryable_summary_error("request timed out"));
+    }
+
+    #[test]
+    fn base_url_keeps_query_string() {
+        assert_eq!(
+            sanitize_api_base_url(
+                Some(
+                    "https://res.openai.azure.com/openai/deployments/gpt4o/?api-version=2024-06-01"
+                        .to_string()
+                ),
+                "chatgpt",
+            )
+            .unwrap(),
+            "https://res.openai.azure.com/openai/deployments/gpt4o?api-version=2024-06-01"
+        );
+        assert!(
+            sanitize_api_base_url(Some("https://host/v1#frag".to_string()), "chatgpt").is_err()
+        );
+    }
+
+    fn stream_greeting(chunks: &[&str]) -> Vec<String> {
+        let mut extractor = GreetingStreamExtractor::default();
+        chunks
+            .iter()
+            .filter_map(|chunk| extractor.push(chunk))
+            .collect()
+    }
+
+    #[test]
+    fn greeting_stream_emits_only_new_text() {
+        let deltas = stream_greeting(&[
+            "{\"gree",
+            "ting\": \"Good mor",
+            "ning \\u2600",
+            "\\uFE0F\\",
+            "n see you\"",
+            ", \"extra\": \"ignored\"}",
+        ]);
+        assert_eq!(
+            deltas,
+            ["Good mor", "ning \u{2600}", "\u{FE0F}", "\n see you"]
+        );
+    }
+
+    #[test]
+    fn greeting_stream_forwards_plain_text() {
+        let deltas = stream_greeting(&["  ", "Hello", " there"]);
+        assert_eq!(deltas, ["Hello", " there"]);
+    }
+
+    #[test]
+    fn date_range_rejects_reversed_bounds() {
+        assert!(parse_date_range(Some("2024-05-02"), Some("2024-05-01")).is_err());
+        assert!(parse_date_range(Some("2024-05-01"), Some("2024-05-01")).is_ok());
+        assert!(parse_date_range(None, Some("2024-05-01")).is_ok());
+    }
+
+    #[test]
+    fn unchanged_body_save_without_ai_keeps_summary() {
+        let existing = ai_summarized_entry("Read all afternoon.");
+        let choice = choose_saved_summary(
+            Some(&existing),
+            "Read all afternoon.",
+            false,
+            false,
+            placeholders(),
+        );
+        assert_eq!(choice.text, PRIOR_AI_SUMMARY);
+        assert_eq!(choice.source, Some(SummarySource::Ai));
+    }
+
+    #[test]
+    fn changed_body_save_without_ai_uses_local_summary() {
+        let existing = ai_summarized_entry("Read all afternoon.");
+        let choice = choose_saved_summary(
+            Some(&existing),
+            "Went for a long walk.",
+            false,
+            false,
+            placeholders(),
+        );
+        assert_eq!(choice.text, "Went for a long walk.");
+        assert_eq!(choice.source, Some(SummarySource::Local));
+        assert!(!choice.stale);
+    }
+
+    #[test]
+    fn store_serves_concurrent_reads_during_writes() {
+        const WRITERS: i64 = 4;
+        const PER_WRITER: i64 = 50;
+        let start = NaiveDate::from_ymd_opt(2999, 1, 1).unwrap();
+        let keys: Vec<String> = (0..WRITERS * PER_WRITER)
+            .map(|offset| {
+                (start + Duration::days(offset))
+                    .format(DATE_FORMAT)
+                    .to_string()
+            })
+            .collect();
+
+        std::thread::scope(|scope| {
+            for chunk in keys.chunks(usize::try_from(PER_WRITER).unwrap()) {
+                scope.spawn(move || {
+                    for key in chunk {
+                        let mut summary = ai_summarized_entry("stress");
+                        summary.date.clone_from(key);
+                        cache_record(key.clone(), EntryRecord::new(summary, "stress".to_string()))
+                            .unwrap();
+                    }
+                });
+            }
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..20 {
+                        let store = read_store().unwrap();
+                        for key in &keys {
+                            if let Some(record) = store.get(key) {
+                                assert_eq!(&record.summary().date, key);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        let mut store = write_store().unwrap();
+        let missing: Vec<&String> = keys
+            .iter()
+            .filter(|key| store.remove(*key).is_none())
+            .collect();
+        drop(store);
+        assert!(missing.is_empty(), "missing {missing:?}");
+    }
+
+    #[test]
+    fn summary_request_uses_summary_temperature() {
+        let mut prefs = ai_prefs::default_preferences();
+        let chatgpt = prefs.providers.get_mut("chatgpt").unwrap();
+        chatgpt.temperature = Some(1.2);
+        chatgpt.summary_temperature = Some(0.2);
+        let ctx = ai_prefs::context_from_preferences(prefs, "chatgpt").unwrap();
+        let ai: AiInvokePayload = serde_json::from_str(r#"{"providerId": "chatgpt"}"#).unwrap();
+
+        let request = summary_request("chatgpt", &ai, &ctx, Vec::new());
+        assert_eq!(request.temperature, Some(0.2));
+        assert!(request.json_mode);
+
+        let ai: AiInvokePayload =
+            serde_json::from_str(r#"{"providerId": "chatgpt", "summaryTemperature": 3.5}"#)
+                .unwrap();
+        let request = summary_request("chatgpt", &ai, &ctx, Vec::new());
+        assert_eq!(request.temperature, Some(2.0));
+    }
+
+    struct QueuedRefresh {
+        aborted: std::rc::Rc<std::cell::Cell<bool>>,
+    }
+
+    impl AbortHandle for QueuedRefresh {
+        fn abort(&self) {
+            self.aborted.set(true);
+        }
+    }
+
+    #[test]
+    fn rapid_saves_within_debounce_make_one_ai_call() {
+        static REGISTRY: Lazy<Mutex<RefreshRegistry<tauri::async_runtime::JoinHandle<()>>>> =
+            Lazy::new(|| Mutex::new(RefreshRegistry::default()));
+        let debounce = std::time::Duration::from_millis(100);
+        let ai_calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        for _ in 0..3 {
+            let ai_calls = std::sync::Arc::clone(&ai_calls);
+            schedule_debounced(
+                &REGISTRY,
+                "2024-05-01".to_string(),
+                debounce,
+                move || async move {
+                    ai_calls.fetch_add(1, Ordering::SeqCst);
+                },
+            );
+        }
+
+        // 越过防抖窗口后再多等一个窗口，确认被取代的任务不会迟到执行。
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while ai_calls.load(Ordering::SeqCst) == 0 && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        std::thread::sleep(debounce * 2);
+        assert_eq!(ai_calls.load(Ordering::SeqCst), 1);
+        assert!(REGISTRY.lock().unwrap().pending.is_empty());
+    }
+
+    #[test]
+    fn marking_private_cancels_refresh_and_drops_ai_summary() {
+        let mut registry = RefreshRegistry::default();
+        let aborted = std::rc::Rc::new(std::cell::Cell::new(false));
+        registry.schedule("2024-05-01".to_string(), |_| QueuedRefresh {
+            aborted: std::rc::Rc::clone(&aborted),
+        });
+        registry.cancel("2024-05-01");
+        assert!(aborted.get());
+        assert!(registry.pending.is_empty());
+
+        let body = "Went for a walk by the river.";
+        let mut entry = ai_summarized_entry(body);
+        apply_private_flag(&mut entry, body, true, placeholders());
+        assert!(entry.private);
+        assert_eq!(entry.ai_summary.as_deref(), summarize_body(body).as_deref());
+        assert_eq!(entry.summary_source, Some(SummarySource::Local));
+        assert!(!entry.summary_stale);
+
+        // 取消私密不会恢复或改写摘要。
+        apply_private_flag(&mut entry, body, false, placeholders());
+        assert!(!entry.private);
+        assert_eq!(entry.summary_source, Some(SummarySource::Local));
+    }
+
+    #[test]
+    fn code_fence_drops_trailing_notes() {
+        let reply = "```json\n{\"summary\": \"walk\"}\n```\nnote: done";
+        assert_eq!(strip_code_fence_block(reply), "{\"summary\": \"walk\"}");
+    }
+
+    #[test]
+    fn code_fence_keeps_inner_backticks() {
+        let reply = "````markdown\nuse ``` for code\n```\nstill inside\n````\n";
+        assert_eq!(
+            strip_code_fence_block(reply),
+            "use ``` for code\n```\nstill inside"
+        );
+        let inline = "```\n{\"summary\": \"a ``` b\"}\n```";
+        assert_eq!(strip_code_fence_block(inline), "{\"summary\": \"a ``` b\"}");
+    }
+}
@@ -0,0 +1,23 @@
+//! Environment-variable overrides for provider settings, shared by preferences and secret storage.
+
+/// 读取 `ECHONOTE_<PROVIDER>_<KEY>` 环境变量，供 CI 与高级用户临时覆盖配置；空值视为未设置。
+///
+/// Provider ID 转为大写，非字母数字字符替换为 `_`，如 `openai-custom-foo` 对应
+/// `ECHONOTE_OPENAI_CUSTOM_FOO_BASE_URL`。
+pub fn provider_env_var(provider_id: &str, key: &str) -> Option<String> {
+    let provider: String = provider_id
+        .trim()
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() {
+                ch.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    std::env::var(format!("ECHONOTE_{provider}_{key}"))
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
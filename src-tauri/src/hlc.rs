@@ -0,0 +1,180 @@
+//! Hybrid logical clock used to stamp entries, persisted so ordering survives restarts and clock skew.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::app_prefs;
+use crate::security::device;
+
+const STATE_FILE_NAME: &str = "hlc_state.json";
+// 本地时钟落后于已记录时间戳超过该阈值时视为时钟偏差。
+const SKEW_WARNING_THRESHOLD_MS: i64 = 5 * 60 * 1000;
+
+static STATE: Lazy<Mutex<Option<ClockState>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ClockState {
+    last_timestamp: i64,
+    logical: u64,
+}
+
+/// HLC 当前状态，`skewMs` 为本地时钟落后于最近时间戳的毫秒数。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HlcStatus {
+    pub last_timestamp: i64,
+    pub logical_counter: u64,
+    pub local_timestamp: i64,
+    pub skew_ms: i64,
+    pub skew_detected: bool,
+}
+
+/// 启动时检查本地时钟是否明显早于上次记录的 HLC 时间戳，检测到偏差时输出警告。
+pub fn check_clock_skew(app: &AppHandle) -> Result<HlcStatus, String> {
+    let status = status(app)?;
+    if status.skew_detected {
+        eprintln!(
+            "[EchoNote] system clock is {}s behind the last HLC timestamp; entries keep ordering via the logical counter",
+            status.skew_ms / 1000
+        );
+    }
+    Ok(status)
+}
+
+pub fn status(app: &AppHandle) -> Result<HlcStatus, String> {
+    let state = {
+        let mut guard = lock_state()?;
+        *loaded_state(app, &mut guard)?
+    };
+    let local_timestamp = Utc::now().timestamp_millis();
+    let skew_ms = (state.last_timestamp - local_timestamp).max(0);
+    Ok(HlcStatus {
+        last_timestamp: state.last_timestamp,
+        logical_counter: state.logical,
+        local_timestamp,
+        skew_ms,
+        skew_detected: skew_ms > SKEW_WARNING_THRESHOLD_MS,
+    })
+}
+
+/// 生成下一个 HLC（`{timestamp}-{logical}-{device_id}`）。
+///
+/// 本地时钟回拨时沿用上次的时间戳并递增逻辑计数，保证同一设备上严格单调。
+pub fn next(app: &AppHandle) -> Result<String, String> {
+    let device_id = device::device_id(app)?;
+    let mut guard = lock_state()?;
+    let state = loaded_state(app, &mut guard)?;
+
+    let now = Utc::now().timestamp_millis();
+    if now > state.last_timestamp {
+        state.last_timestamp = now;
+        state.logical = 0;
+    } else {
+        state.logical += 1;
+    }
+    let snapshot = *state;
+    // 持锁落盘：并发调用按生成顺序写入，较旧的状态不会覆盖较新的状态。
+    if let Err(err) = persist_state(app, &snapshot) {
+        eprintln!("[EchoNote] failed to persist HLC state: {err}");
+    }
+    drop(guard);
+
+    Ok(format!(
+        "{}-{}-{device_id}",
+        snapshot.last_timestamp, snapshot.logical
+    ))
+}
+
+/// 解析 HLC 字符串中的物理时间戳（毫秒），格式不符时返回错误。
+pub fn timestamp_millis(hlc: &str) -> Result<i64, String> {
+    let mut parts = hlc.trim().splitn(3, '-');
+    let timestamp = parts
+        .next()
+        .and_then(|value| value.parse::<i64>().ok())
+        .filter(|value| *value >= 0);
+    let logical_valid = parts
+        .next()
+        .is_some_and(|value| value.parse::<u64>().is_ok());
+    match (timestamp, logical_valid) {
+        (Some(timestamp), true) => Ok(timestamp),
+        _ => Err(format!("malformed HLC: {hlc}")),
+    }
+}
+
+/// 清空内存中的时钟状态并删除持久化文件，返回被删除的文件路径。
+pub fn reset(app: &AppHandle) -> Result<Option<PathBuf>, String> {
+    *lock_state()? = None;
+    if app_prefs::is_ephemeral() {
+        return Ok(None);
+    }
+    let path = state_path(app)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    fs::remove_file(&path)
+        .map_err(|err| format!("failed to remove HLC state {}: {err}", path.display()))?;
+    Ok(Some(path))
+}
+
+fn lock_state() -> Result<std::sync::MutexGuard<'static, Option<ClockState>>, String> {
+    STATE
+        .lock()
+        .map_err(|_| "failed to lock HLC state".to_string())
+}
+
+fn loaded_state<'a>(
+    app: &AppHandle,
+    slot: &'a mut Option<ClockState>,
+) -> Result<&'a mut ClockState, String> {
+    if slot.is_none() {
+        *slot = Some(load_state(app)?);
+    }
+    Ok(slot.get_or_insert_with(ClockState::default))
+}
+
+fn load_state(app: &AppHandle) -> Result<ClockState, String> {
+    if app_prefs::is_ephemeral() {
+        return Ok(ClockState::default());
+    }
+    let path = state_path(app)?;
+    if !path.exists() {
+        return Ok(ClockState::default());
+    }
+    let content = fs::read_to_string(&path)
+        .map_err(|err| format!("failed to read HLC state {}: {err}", path.display()))?;
+    // 状态文件损坏时从零开始，新的时间戳仍由本地时钟驱动。
+    Ok(serde_json::from_str(&content).unwrap_or_default())
+}
+
+fn persist_state(app: &AppHandle, state: &ClockState) -> Result<(), String> {
+    // 临时模式不落盘，时钟状态只在内存中保持单调。
+    if app_prefs::is_ephemeral() {
+        return Ok(());
+    }
+    let path = state_path(app)?;
+    let serialized = serde_json::to_string(state)
+        .map_err(|err| format!("failed to serialize HLC state: {err}"))?;
+    // 先写临时文件再替换，写入中途退出也不会留下截断的状态文件。
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serialized)
+        .map_err(|err| format!("failed to write HLC state {}: {err}", tmp.display()))?;
+    fs::rename(&tmp, &path)
+        .map_err(|err| format!("failed to replace HLC state {}: {err}", path.display()))
+}
+
+fn state_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|err| format!("failed to resolve app data dir: {err}"))?;
+    fs::create_dir_all(&dir)
+        .map_err(|err| format!("failed to prepare app data dir {}: {err}", dir.display()))?;
+    Ok(dir.join(STATE_FILE_NAME))
+}
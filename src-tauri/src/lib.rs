@@ -1,10 +1,15 @@
 //! EchoNote Tauri Core Lib
 
+mod ai_log;
 mod ai_migration;
 mod ai_prefs;
 mod ai_provider;
+mod app_prefs;
+mod archive;
 mod commands;
 mod entry_service;
+mod env_overrides;
+mod hlc;
 mod models;
 mod security;
 mod storage;
@@ -17,18 +22,98 @@ pub fn run() {
         // 注册允许前端调用的指令，新增命令需在此同步登记。
         .invoke_handler(tauri::generate_handler![
             commands::list_entries_by_month,
+            commands::list_recent_entries,
+            commands::list_entries_without_ai_summary,
+            commands::related_entries,
+            commands::year_activity,
             commands::get_entry_body_by_date,
+            commands::stream_month_bodies,
+            commands::entry_exists,
+            commands::get_entry_path,
+            commands::search_entries,
+            commands::search_month,
+            commands::export_jsonl,
+            commands::cancel_export,
             commands::save_entry_by_date,
+            commands::save_entries_batch,
+            commands::append_to_today,
+            commands::set_entry_language,
+            commands::set_entry_private,
             commands::invoke_generate_hero_greeting,
+            commands::invoke_generate_hero_greeting_stream,
+            commands::preview_greeting_prompt,
+            commands::preview_context_summary,
+            commands::rebuild_metadata,
+            commands::verify_integrity,
+            commands::storage_info,
+            commands::add_read_root,
+            commands::set_body_compression,
+            commands::set_revision_limit,
+            commands::set_hash_algorithm,
+            commands::set_document_style,
+            commands::list_entry_revisions,
+            commands::get_entry_revision,
+            commands::restore_entry_revision,
+            commands::set_filename_date_format,
+            commands::reconcile_dates,
+            commands::find_duplicates,
+            commands::merge_duplicates,
+            commands::validate_entry_document,
+            commands::count_text,
+            commands::estimate_tokens,
+            commands::archive_month,
+            commands::unarchive_month,
+            commands::cleanup_empty_entries,
+            commands::invoke_ai_chat_json,
+            commands::test_summarize,
+            commands::monthly_digest,
+            commands::sentiment_trend,
             commands::list_ai_models,
+            commands::list_all_models,
+            commands::clear_model_cache,
+            commands::clear_all_model_caches,
+            commands::validate_base_url,
+            commands::session_usage,
+            commands::get_limits,
+            commands::last_provider_error,
+            commands::hlc_status,
+            commands::describe_hlc,
+            commands::wipe_confirm_token,
+            commands::wipe_all_data,
+            commands::set_offline_mode,
+            commands::get_offline_mode,
+            commands::set_debug_logging,
+            commands::read_ai_log,
+            commands::export_ai_preferences,
+            commands::import_ai_preferences,
+            commands::set_model_alias,
+            commands::set_provider_hidden,
+            commands::provider_capabilities,
+            commands::resolve_provider_context,
+            commands::config_paths,
+            commands::run_migration,
+            commands::set_legacy_config_handling,
             commands::store_api_secret,
             commands::delete_api_secret,
             commands::has_api_secret,
+            commands::api_secret_source,
+            commands::rotate_secrets,
         ])
         .setup(|app| {
             if let Err(err) = ai_migration::migrate_if_needed(&app.handle()) {
                 eprintln!("[EchoNote] AI config migration skipped: {err}");
             }
+            match ai_migration::reconcile_config_locations(app.handle()) {
+                Ok(moved) => {
+                    for note in moved {
+                        eprintln!("[EchoNote] config location: {note}");
+                    }
+                }
+                Err(err) => eprintln!("[EchoNote] config location check skipped: {err}"),
+            }
+            if let Err(err) = hlc::check_clock_skew(app.handle()) {
+                eprintln!("[EchoNote] HLC clock check skipped: {err}");
+            }
             Ok(())
         })
         .run(tauri::generate_context!())
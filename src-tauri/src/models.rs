@@ -33,12 +33,27 @@ impl EntryRecord {
 }
 
 /// 日记元数据
+///
+/// 旧版本写入的字段名（如 `summary`、`lang`、下划线风格的键）经 `StoredDiaryEntry` 兼容读取，
+/// 新旧键同时出现时以当前字段名为准；写入时统一使用当前字段名。
+// frontmatter 中的布尔标记彼此独立，且需保持扁平的键名，不合并为枚举或位集。
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredDiaryEntry")]
 pub struct DiaryEntry {
     /// $Timestamp + "-" + $LogicalCounter + "-" + DeviceID
     pub hlc: String,
-    /// BLAKE3 HASH
+    /// 正文 HASH，算法见 `hash_algo`
     pub hash: String,
+    /// 生成 `hash` 的算法，缺省（旧条目）为 BLAKE3
+    #[serde(rename = "hashAlgo", skip_serializing_if = "HashAlgorithm::is_default")]
+    pub hash_algo: HashAlgorithm,
+    /// 磁盘上正文的编码方式，缺省为明文；由存储层在写入时设置
+    #[serde(
+        rename = "bodyEncoding",
+        skip_serializing_if = "BodyEncoding::is_plain"
+    )]
+    pub body_encoding: BodyEncoding,
     /// 日期：YYYY-MM-DD
     pub date: String,
     /// 每日 Emoji
@@ -50,4 +65,466 @@ pub struct DiaryEntry {
     /// 语言
     #[serde(skip_serializing_if = "Option::is_none")]
     pub language: Option<String>,
+    /// 语言由用户手动指定，保存与重建元数据时不再自动检测覆盖
+    #[serde(rename = "languageManual", skip_serializing_if = "std::ops::Not::not")]
+    pub language_manual: bool,
+    /// 字数：CJK 字符逐字计数，其余按空白分词
+    #[serde(rename = "wordCount", skip_serializing_if = "Option::is_none")]
+    pub word_count: Option<u32>,
+    /// 摘要来源：AI 生成或本地截断（未启用 AI、AI 失败后的回退）；旧条目缺少该字段时为未知
+    #[serde(rename = "summarySource", skip_serializing_if = "Option::is_none")]
+    pub summary_source: Option<SummarySource>,
+    /// AI 摘要因 token 上限被截断（`finish_reason` 为 `length`），前端可提示以更高上限重新生成
+    #[serde(
+        rename = "summaryTruncated",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub summary_truncated: bool,
+    /// 沿用的 AI 摘要写于正文修改之前（跳过 AI 保存），已不能代表当前正文，前端可提示重新生成
+    #[serde(rename = "summaryStale", skip_serializing_if = "std::ops::Not::not")]
+    pub summary_stale: bool,
+    /// 私密条目：不发送给任何 AI（只用本地摘要），也不参与问候上下文、相关条目、月度总结与情绪分析
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub private: bool,
+    /// 条目文件的绝对路径，仅随命令返回给前端，不写入也不读取 frontmatter
+    #[serde(rename = "filePath", skip_serializing_if = "Option::is_none")]
+    pub file_path: Option<String>,
+    /// 文件名中的日期，仅在与 frontmatter 的 date 不一致时返回，提示用户修复
+    #[serde(rename = "filenameDate", skip_serializing_if = "Option::is_none")]
+    pub filename_date: Option<String>,
+}
+
+/// `DiaryEntry` 的读取形式：旧键作为独立字段读入，避免与新键同时出现时报 duplicate field。
+#[derive(Deserialize)]
+struct StoredDiaryEntry {
+    hlc: String,
+    hash: String,
+    #[serde(rename = "hashAlgo", default)]
+    hash_algo: HashAlgorithm,
+    #[serde(rename = "bodyEncoding", default)]
+    body_encoding: BodyEncoding,
+    date: String,
+    emoji: Option<String>,
+    #[serde(rename = "aiSummary")]
+    ai_summary: Option<String>,
+    #[serde(rename = "ai_summary")]
+    legacy_ai_summary: Option<String>,
+    #[serde(rename = "summary")]
+    legacy_summary: Option<String>,
+    language: Option<String>,
+    #[serde(rename = "lang")]
+    legacy_lang: Option<String>,
+    #[serde(rename = "languageManual")]
+    language_manual: Option<bool>,
+    #[serde(rename = "language_manual")]
+    legacy_language_manual: Option<bool>,
+    #[serde(rename = "wordCount")]
+    word_count: Option<u32>,
+    #[serde(rename = "word_count")]
+    legacy_word_count: Option<u32>,
+    #[serde(rename = "words")]
+    legacy_words: Option<u32>,
+    #[serde(rename = "summarySource")]
+    summary_source: Option<SummarySource>,
+    #[serde(rename = "summary_source")]
+    legacy_summary_source: Option<SummarySource>,
+    #[serde(rename = "summaryTruncated")]
+    summary_truncated: Option<bool>,
+    #[serde(rename = "summary_truncated")]
+    legacy_summary_truncated: Option<bool>,
+    #[serde(rename = "summaryStale", default)]
+    summary_stale: bool,
+    #[serde(default)]
+    private: bool,
+}
+
+impl From<StoredDiaryEntry> for DiaryEntry {
+    fn from(stored: StoredDiaryEntry) -> Self {
+        Self {
+            hlc: stored.hlc,
+            hash: stored.hash,
+            hash_algo: stored.hash_algo,
+            body_encoding: stored.body_encoding,
+            date: stored.date,
+            emoji: stored.emoji,
+            ai_summary: stored
+                .ai_summary
+                .or(stored.legacy_ai_summary)
+                .or(stored.legacy_summary),
+            language: stored.language.or(stored.legacy_lang),
+            language_manual: stored
+                .language_manual
+                .or(stored.legacy_language_manual)
+                .unwrap_or_default(),
+            word_count: stored
+                .word_count
+                .or(stored.legacy_word_count)
+                .or(stored.legacy_words),
+            summary_source: stored.summary_source.or(stored.legacy_summary_source),
+            summary_truncated: stored
+                .summary_truncated
+                .or(stored.legacy_summary_truncated)
+                .unwrap_or_default(),
+            summary_stale: stored.summary_stale,
+            private: stored.private,
+            file_path: None,
+            filename_date: None,
+        }
+    }
+}
+
+impl DiaryEntry {
+    /// 按条目记录的算法校验正文是否与 frontmatter 中的 hash 一致。
+    pub fn body_matches(&self, body: &str) -> bool {
+        self.hash_algo.digest(body) == self.hash
+    }
+}
+
+/// 正文 HASH 算法，记录在 frontmatter 中，混用不同算法的日记仍能各自校验
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Blake3,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// 计算正文的十六进制摘要。
+    pub fn digest(self, body: &str) -> String {
+        use std::fmt::Write as _;
+
+        match self {
+            Self::Blake3 => blake3::hash(body.as_bytes()).to_hex().to_string(),
+            Self::Sha256 => ring::digest::digest(&ring::digest::SHA256, body.as_bytes())
+                .as_ref()
+                .iter()
+                .fold(String::new(), |mut hex, byte| {
+                    let _ = write!(hex, "{byte:02x}");
+                    hex
+                }),
+        }
+    }
+
+    // serde 的 skip_serializing_if 要求以引用传参。
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// 正文编码，记录在 frontmatter 中，避免依赖正文内容判断是否压缩
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyEncoding {
+    #[default]
+    Plain,
+    /// zstd 压缩后的 base64 文本
+    Zstd,
+}
+
+impl BodyEncoding {
+    // serde 的 skip_serializing_if 要求以引用传参。
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    const fn is_plain(&self) -> bool {
+        matches!(self, Self::Plain)
+    }
+}
+
+/// 摘要来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SummarySource {
+    Ai,
+    #[default]
+    Local,
+}
+
+/// 年度热力图中的单日活跃度
+#[derive(Debug, Clone, Serialize)]
+pub struct DayActivity {
+    /// 日期：YYYY-MM-DD
+    pub date: String,
+    #[serde(rename = "hasEntry")]
+    pub has_entry: bool,
+    #[serde(rename = "wordCount", skip_serializing_if = "Option::is_none")]
+    pub word_count: Option<u32>,
+    /// 当日心情，取自每日 Emoji
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mood: Option<String>,
+}
+
+/// 情绪趋势中的单日得分
+#[derive(Debug, Clone, Serialize)]
+pub struct SentimentPoint {
+    /// 日期：YYYY-MM-DD
+    pub date: String,
+    /// 情绪得分，-1（消极）到 1（积极）
+    pub score: f32,
+    /// 得分来源：AI 评分或本地词典估算
+    pub source: SummarySource,
+}
+
+/// 分页结果，供搜索等可能无界的列表接口使用。
+#[derive(Debug, Clone, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// 匹配的总条数（分页前）
+    pub total: usize,
+    /// 当前页之后是否还有更多结果
+    #[serde(rename = "hasMore")]
+    pub has_more: bool,
+}
+
+impl<T> Page<T> {
+    /// 从完整结果中截取 `[offset, offset + limit)` 区间。
+    pub fn slice(all: Vec<T>, offset: usize, limit: usize) -> Self {
+        let total = all.len();
+        let items: Vec<T> = all.into_iter().skip(offset).take(limit).collect();
+        let has_more = offset.saturating_add(items.len()) < total;
+        Self {
+            items,
+            total,
+            has_more,
+        }
+    }
+}
+
+/// 相关日记及其相关度得分
+#[derive(Debug, Clone, Serialize)]
+pub struct RelatedEntry {
+    #[serde(flatten)]
+    pub entry: DiaryEntry,
+    /// 相关度：关键词重合度，Emoji 相同时额外加分
+    pub score: f64,
+}
+
+/// 月内搜索命中的条目及其上下文片段
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub entry: DiaryEntry,
+    /// 命中位置前后的正文（或摘要）片段，换行已替换为空格
+    pub snippet: String,
+    /// 命中文本在片段中的起始位置（按字符计）
+    #[serde(rename = "highlightStart")]
+    pub highlight_start: usize,
+    /// 命中文本的长度（按字符计）
+    #[serde(rename = "highlightLength")]
+    pub highlight_length: usize,
+}
+
+/// 尚无 AI 摘要的条目，供"批量补全摘要"界面使用
+#[derive(Debug, Clone, Serialize)]
+pub struct MissingAiSummary {
+    pub date: String,
+    /// 当前的本地摘要；摘要为空或仍是生成中占位符时为 None
+    #[serde(rename = "localSummary", skip_serializing_if = "Option::is_none")]
+    pub local_summary: Option<String>,
+}
+
+/// 日记的历史版本
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryRevision {
+    /// 版本号：归档时间（毫秒）与旧正文 hash 前缀
+    pub id: String,
+    /// 归档时间（Unix 毫秒）
+    #[serde(rename = "savedAt")]
+    pub saved_at: i64,
+    /// 该版本正文的 HASH，按配置的哈希算法计算
+    pub hash: String,
+    #[serde(rename = "wordCount", skip_serializing_if = "Option::is_none")]
+    pub word_count: Option<u32>,
+}
+
+/// 存储目录与磁盘占用信息
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageInfo {
+    /// 存储根目录的绝对路径
+    pub root: String,
+    /// 所有条目文件（.md）的总字节数
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: u64,
+    #[serde(rename = "entryCount")]
+    pub entry_count: usize,
+    /// 所在磁盘卷的剩余空间，无法获取时为 None
+    #[serde(rename = "freeBytes", skip_serializing_if = "Option::is_none")]
+    pub free_bytes: Option<u64>,
+}
+
+/// 当前解析出的配置 / 数据目录及各文件路径，用于排查配置位置问题；无法解析时为 None
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigPaths {
+    pub config_dir: Option<String>,
+    pub data_dir: Option<String>,
+    pub app_preferences: Option<String>,
+    pub ai_preferences: Option<String>,
+    pub secrets: Option<String>,
+    pub storage_root: Option<String>,
+    /// 配置目录与数据目录是否为同一位置
+    pub same_dir: bool,
+}
+
+/// 导入前校验条目文档的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryDocumentValidation {
+    pub entry: DiaryEntry,
+    /// 解码后正文的字符数
+    #[serde(rename = "bodyLength")]
+    pub body_length: usize,
+}
+
+/// 同一日期对应多个文件时的单个文件信息
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateFile {
+    pub path: String,
+    pub hlc: String,
+    #[serde(rename = "wordCount", skip_serializing_if = "Option::is_none")]
+    pub word_count: Option<u32>,
+}
+
+/// 有多个文件指向同一日期的条目
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateEntry {
+    pub date: String,
+    pub files: Vec<DuplicateFile>,
+}
+
+/// 合并重复条目的策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateMergeStrategy {
+    /// 保留 HLC 最新的版本，丢弃其余文件
+    KeepNewestHlc,
+    /// 按 HLC 从旧到新拼接各版本正文（相同正文只保留一份）
+    Concatenate,
+}
+
+/// 合并重复条目的结果；`applied` 为 false 时仅为预演，磁盘未被修改
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateMergeReport {
+    pub date: String,
+    pub applied: bool,
+    /// 合并后写入的文件
+    #[serde(rename = "targetPath")]
+    pub target_path: String,
+    /// 合并后将被删除的其他文件
+    pub removed: Vec<String>,
+    /// 合并后正文的 HASH，按配置的哈希算法计算
+    pub hash: String,
+    #[serde(rename = "wordCount")]
+    pub word_count: u32,
+}
+
+/// 前端可读取的请求限制，便于在发送前提示或自行截断
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiLimits {
+    /// 当前生效的单次请求体积上限（字节）
+    pub max_request_bytes: u64,
+    /// 允许设置的最小值
+    pub min_allowed: u64,
+    /// 允许设置的最大值
+    pub max_allowed: u64,
+}
+
+/// 任意文本的字数统计，`words` 与保存时写入的 `wordCount` 算法一致
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextCount {
+    pub words: u32,
+    /// 全部字符数（按 Unicode 标量值计，含空白）
+    pub chars: u32,
+    /// 其中 CJK 表意字符、假名与谚文的数量
+    pub cjk_chars: u32,
+}
+
+/// 本地 token 估算方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenEstimateMethod {
+    /// 使用 `OpenAI` 系列模型对应的 tiktoken 编码精确计数
+    Tiktoken,
+    /// 按字符数估算：非 CJK 约 4 字符 1 token，CJK 约 1.5 字符 1 token
+    Heuristic,
+}
+
+/// `estimate_tokens` 的结果，仅供编辑器显示“≈N tokens”，与服务端实际计费可能略有出入
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenEstimate {
+    pub tokens: u32,
+    pub method: TokenEstimateMethod,
+    /// 使用 tiktoken 时的编码名，例如 `cl100k_base`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+/// 旧版 `ai_config.json` 迁移的结果；`migrated` 为 false 时 `skippedReason` 说明原因
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationReport {
+    pub migrated: bool,
+    /// 合并进偏好设置的 Provider
+    pub providers: Vec<String>,
+    /// 迁移了加密 API Key 的 Provider
+    pub keys: Vec<String>,
+    /// 强制迁移前备份的现有文件
+    pub backups: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped_reason: Option<String>,
+}
+
+/// 清除全部数据的结果：实际删除的文件与目录
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WipeReport {
+    pub removed: Vec<String>,
+}
+
+/// 存储完整性检查结果：正文 HASH 与 frontmatter 记录不一致的条目可能已损坏或被外部编辑
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityReport {
+    /// 成功读取并完成校验的条目数
+    pub checked: usize,
+    /// HASH 不一致（或缺失）的日期，重新保存即可按当前正文更新
+    pub divergent: Vec<String>,
+    /// 无法读取或解析的日期及原因
+    pub unreadable: Vec<String>,
+}
+
+/// 按 frontmatter 日期重命名文件的结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DateReconcileReport {
+    /// 已重命名的条目（新的日期）
+    pub renamed: Vec<String>,
+    /// 目标文件已存在而跳过的条目（原文件路径）
+    pub conflicts: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_and_current_keys_may_coexist() {
+        let yaml = "hlc: h\nhash: x\ndate: 2024-05-01\nsummary: old\naiSummary: new\n\
+                    lang: de\nlanguage: en\nwords: 3\nword_count: 4\nwordCount: 5\n";
+        let entry: DiaryEntry = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(entry.ai_summary.as_deref(), Some("new"));
+        assert_eq!(entry.language.as_deref(), Some("en"));
+        assert_eq!(entry.word_count, Some(5));
+
+        let legacy: DiaryEntry =
+            serde_yaml::from_str("hlc: h\nhash: x\ndate: 2024-05-01\nsummary: old\nlang: de\n")
+                .unwrap();
+        assert_eq!(legacy.ai_summary.as_deref(), Some("old"));
+        assert_eq!(legacy.summary_source, None);
+
+        let written = serde_yaml::to_string(&entry).unwrap();
+        assert!(!written.contains("summary:") && !written.contains("lang:"));
+        let reread: DiaryEntry = serde_yaml::from_str(&written).unwrap();
+        assert_eq!(serde_yaml::to_string(&reread).unwrap(), written);
+    }
 }
@@ -2,40 +2,80 @@
 
 use std::fs;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
 use aes_gcm::aead::{Aead, KeyInit};
 use aes_gcm::{Aes256Gcm, Key, Nonce};
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
-use once_cell::sync::OnceCell;
+use once_cell::sync::Lazy;
 use rand::rngs::OsRng;
 use rand::RngCore;
 use ring::hkdf::{Salt, HKDF_SHA256};
 use tauri::{AppHandle, Manager};
 use uuid::Uuid;
 
+use crate::app_prefs;
+
 // 固定密钥的原始 seed（由产品要求提供），实际用于派生 AES-256 密钥。
 const DEVICE_KEY_SEED: &[u8] = b"Ech0N0te";
 const DEVICE_KEY_SALT: &[u8] = b"echonote-device-key";
 const ENCODING_PREFIX: &str = "v1:";
+const DEVICE_ID_FILE_NAME: &str = "device_id";
 
-static DEVICE_ID: OnceCell<String> = OnceCell::new();
+static DEVICE_ID: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
 
 /// 返回缓存的 device_id，如不存在则在应用数据目录生成新的 UUID v4。
 pub fn device_id(app: &AppHandle) -> Result<String, String> {
+    let mut cached = lock_device_id()?;
+    if let Some(id) = cached.as_ref() {
+        return Ok(id.clone());
+    }
+    let id = load_or_create_device_id(app)?;
+    *cached = Some(id.clone());
+    drop(cached);
+    Ok(id)
+}
+
+/// 丢弃缓存的 `device_id` 并覆盖删除磁盘文件；下次访问时会生成新的 ID。
+pub fn wipe(app: &AppHandle) -> Result<Option<PathBuf>, String> {
+    let cached = lock_device_id()?.take();
+    if let Some(id) = cached {
+        let mut bytes = id.into_bytes();
+        bytes.fill(0);
+        std::hint::black_box(&bytes);
+    }
+    if app_prefs::is_ephemeral() {
+        return Ok(None);
+    }
+    let file = device_id_path(app)?;
+    Ok(super::shred_file(&file)?.then_some(file))
+}
+
+fn lock_device_id() -> Result<std::sync::MutexGuard<'static, Option<String>>, String> {
     DEVICE_ID
-        .get_or_try_init(|| load_or_create_device_id(app))
-        .map(|value| value.clone())
+        .lock()
+        .map_err(|_| "failed to lock device id".to_string())
 }
 
-fn load_or_create_device_id(app: &AppHandle) -> Result<String, String> {
+fn device_id_path(app: &AppHandle) -> Result<PathBuf, String> {
     let dir = app
         .path()
         .app_data_dir()
         .map_err(|err| format!("failed to resolve app data dir: {err}"))?;
-    fs::create_dir_all(&dir)
-        .map_err(|err| format!("failed to prepare app data dir {}: {err}", dir.display()))?;
-    let file = dir.join("device_id");
+    Ok(dir.join(DEVICE_ID_FILE_NAME))
+}
+
+fn load_or_create_device_id(app: &AppHandle) -> Result<String, String> {
+    // 临时模式下密钥也只在内存中，使用一次性的 device_id 即可。
+    if app_prefs::is_ephemeral() {
+        return Ok(Uuid::new_v4().to_string());
+    }
+    let file = device_id_path(app)?;
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|err| format!("failed to prepare app data dir {}: {err}", dir.display()))?;
+    }
 
     if file.exists() {
         if let Ok(existing) = read_existing(&file) {
@@ -1,5 +1,27 @@
 //! Security utilities: device identification, crypto helpers, and secret storage.
 
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
 pub mod crypto;
 pub mod device;
 pub mod secrets;
+
+/// 先用零覆盖文件内容并落盘，再删除文件；文件不存在时返回 `false`。
+pub fn shred_file(path: &Path) -> Result<bool, String> {
+    let Ok(meta) = fs::metadata(path) else {
+        return Ok(false);
+    };
+    let length = usize::try_from(meta.len()).unwrap_or(usize::MAX);
+    OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|mut file| {
+            file.write_all(&vec![0u8; length])?;
+            file.sync_all()
+        })
+        .map_err(|err| format!("failed to overwrite {}: {err}", path.display()))?;
+    fs::remove_file(path).map_err(|err| format!("failed to remove {}: {err}", path.display()))?;
+    Ok(true)
+}
@@ -3,19 +3,26 @@
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager};
 
 use super::crypto::{self, EncryptedBlob};
 use super::device;
+use crate::app_prefs;
+use crate::env_overrides;
 
 const SECRET_FILE_NAME: &str = "ai_secrets.dat";
 const LEGACY_KEYS_FILE: &str = "ai_keys.json";
 const LEGACY_COMBINED_FILE: &str = "ai_config.json";
 
+/// 临时模式下的密钥仅保存在内存中，退出后即丢弃。
+static EPHEMERAL_SECRETS: Lazy<Mutex<SecretStore>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct SecretSlot {
     pub salt: Option<String>,
@@ -36,6 +43,21 @@ pub struct LegacyProviderSlot {
     pub selected_model: Option<String>,
 }
 
+/// 重新加密的结果：成功的 Provider 列表与失败的 Provider → 错误信息。
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SecretRotationReport {
+    pub rotated: Vec<String>,
+    pub failed: HashMap<String, String>,
+}
+
+/// API Key 的来源：本地加密存储或 `ECHONOTE_<PROVIDER>_API_KEY` 环境变量。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeySource {
+    Stored,
+    Env,
+}
+
 type SecretStore = HashMap<String, SecretSlot>;
 pub type LegacyStore = HashMap<String, LegacyProviderSlot>;
 
@@ -54,10 +76,11 @@ pub fn save_api_key(app: &AppHandle, provider_id: &str, api_key: &str) -> Result
     persist_store(app, &store)
 }
 
+/// 读取 Provider 的 API Key；未存储时回退到 `ECHONOTE_<PROVIDER>_API_KEY` 环境变量（不会写入密钥文件）。
 pub fn load_api_key(app: &AppHandle, provider_id: &str) -> Result<Option<String>, String> {
     let store = load_store(app)?;
     let Some(secret) = store.get(provider_id) else {
-        return Ok(None);
+        return Ok(env_overrides::provider_env_var(provider_id, "API_KEY"));
     };
     let blob = deserialize_blob(secret)?;
     let device_id = device::device_id(app)?;
@@ -74,15 +97,95 @@ pub fn delete_api_key(app: &AppHandle, provider_id: &str) -> Result<(), String>
     Ok(())
 }
 
+/// 使用新的随机 salt / nonce 重新加密所有已存储的密钥。
+///
+/// 单个槽位解密失败时保留原内容并记录错误，不影响其他槽位；全部处理完后一次性原子写回。
+pub fn rotate_encryption(app: &AppHandle) -> Result<SecretRotationReport, String> {
+    let device_id = device::device_id(app)?;
+    let mut store = load_store(app)?;
+    let mut report = SecretRotationReport::default();
+
+    for (provider_id, slot) in &mut store {
+        let rotated = deserialize_blob(slot)
+            .and_then(|blob| crypto::decrypt(device_id.as_bytes(), &blob))
+            .and_then(|plaintext| crypto::encrypt(device_id.as_bytes(), &plaintext));
+        match rotated {
+            Ok(blob) => {
+                *slot = SecretSlot {
+                    salt: Some(BASE64.encode(blob.salt)),
+                    nonce: Some(BASE64.encode(blob.nonce)),
+                    ciphertext: Some(BASE64.encode(blob.ciphertext)),
+                };
+                report.rotated.push(provider_id.clone());
+            }
+            Err(err) => {
+                report.failed.insert(provider_id.clone(), err);
+            }
+        }
+    }
+
+    if !report.rotated.is_empty() {
+        persist_store(app, &store)?;
+    }
+    report.rotated.sort();
+    Ok(report)
+}
+
 pub fn has_api_key(app: &AppHandle, provider_id: &str) -> Result<bool, String> {
+    Ok(api_key_source(app, provider_id)?.is_some())
+}
+
+/// 与 `load_api_key` 的查找顺序一致：先看本地存储，再看环境变量；都没有时返回 None。
+pub fn api_key_source(app: &AppHandle, provider_id: &str) -> Result<Option<ApiKeySource>, String> {
     let store = load_store(app)?;
-    let Some(slot) = store.get(provider_id) else {
-        return Ok(false);
-    };
-    Ok(slot
-        .ciphertext
-        .as_ref()
-        .is_some_and(|cipher| !cipher.trim().is_empty()))
+    if let Some(slot) = store.get(provider_id) {
+        let stored = slot
+            .ciphertext
+            .as_ref()
+            .is_some_and(|cipher| !cipher.trim().is_empty());
+        return Ok(stored.then_some(ApiKeySource::Stored));
+    }
+    Ok(env_overrides::provider_env_var(provider_id, "API_KEY").map(|_| ApiKeySource::Env))
+}
+
+/// 清空内存中的密钥（先逐字节置零），并覆盖删除磁盘上的密钥文件及旧版遗留文件。
+///
+/// 返回实际删除的文件路径。
+pub fn wipe(app: &AppHandle) -> Result<Vec<PathBuf>, String> {
+    let mut ephemeral = EPHEMERAL_SECRETS
+        .lock()
+        .map_err(|_| "failed to lock ephemeral secret store".to_string())?;
+    for slot in ephemeral.values_mut() {
+        for field in [&mut slot.salt, &mut slot.nonce, &mut slot.ciphertext] {
+            if let Some(value) = field.take() {
+                let mut bytes = value.into_bytes();
+                bytes.fill(0);
+                std::hint::black_box(&bytes);
+            }
+        }
+    }
+    ephemeral.clear();
+    drop(ephemeral);
+
+    if app_prefs::is_ephemeral() {
+        return Ok(Vec::new());
+    }
+    let secrets = secrets_path(app)?;
+    let legacy_combined = legacy_combined_path(app)?;
+    let candidates = [
+        secrets.with_extension("dat.tmp"),
+        secrets,
+        legacy_keys_path(app)?,
+        legacy_combined.with_extension("bak"),
+        legacy_combined,
+    ];
+    let mut removed = Vec::new();
+    for path in candidates {
+        if super::shred_file(&path)? {
+            removed.push(path);
+        }
+    }
+    Ok(removed)
 }
 
 pub fn persist_store_snapshot(app: &AppHandle, store: &SecretStore) -> Result<(), String> {
@@ -123,6 +226,12 @@ fn legacy_keys_path(app: &AppHandle) -> Result<PathBuf, String> {
 }
 
 fn load_store(app: &AppHandle) -> Result<SecretStore, String> {
+    if app_prefs::is_ephemeral() {
+        return EPHEMERAL_SECRETS
+            .lock()
+            .map(|store| store.clone())
+            .map_err(|_| "failed to lock ephemeral secret store".to_string());
+    }
     let path = secrets_path(app)?;
     if path.exists() {
         return read_store(&path);
@@ -169,6 +278,13 @@ fn read_legacy_store(path: &PathBuf) -> Result<LegacyStore, String> {
 }
 
 fn persist_store(app: &AppHandle, store: &SecretStore) -> Result<(), String> {
+    if app_prefs::is_ephemeral() {
+        EPHEMERAL_SECRETS
+            .lock()
+            .map_err(|_| "failed to lock ephemeral secret store".to_string())?
+            .clone_from(store);
+        return Ok(());
+    }
     let path = secrets_path(app)?;
     if let Some(dir) = path.parent() {
         fs::create_dir_all(dir)
@@ -176,7 +292,11 @@ fn persist_store(app: &AppHandle, store: &SecretStore) -> Result<(), String> {
     }
     let serialized = serde_json::to_string_pretty(store)
         .map_err(|err| format!("failed to serialize secret store: {err}"))?;
-    fs::write(&path, serialized).map_err(|err| format!("failed to write {}: {err}", path.display()))
+    // 先写入临时文件再重命名，避免写入中断导致密钥文件损坏。
+    let tmp = path.with_extension("dat.tmp");
+    fs::write(&tmp, serialized)
+        .map_err(|err| format!("failed to write {}: {err}", tmp.display()))?;
+    fs::rename(&tmp, &path).map_err(|err| format!("failed to replace {}: {err}", path.display()))
 }
 
 fn deserialize_blob(secret: &SecretSlot) -> Result<EncryptedBlob, String> {
@@ -1,22 +1,49 @@
 //! File-based diary storage utilities.
 
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::format::{Item, StrftimeItems};
 use chrono::{Datelike, NaiveDate};
+use once_cell::sync::Lazy;
 use tauri::{AppHandle, Manager};
 
-use crate::models::{DiaryEntry, EntryRecord};
+use crate::app_prefs;
+use crate::archive;
+use crate::models::{BodyEncoding, DateReconcileReport, DiaryEntry, EntryRecord, EntryRevision};
 
 const DATE_FORMAT: &str = "%Y-%m-%d";
+/// 默认的文件名日期格式，读取时总会作为回退格式尝试。
+pub const DEFAULT_FILENAME_DATE_FORMAT: &str = DATE_FORMAT;
 // 仅预读前若干字节获取 frontmatter，避免大文件浪费 I/O。
 const FRONTMATTER_INITIAL_BYTES: u64 = 1024;
 const FRONTMATTER_ADDITIONAL_BYTES: u64 = 2048;
+// 压缩正文的标记行，其后为 base64 编码的 zstd 数据。
+const ZSTD_LEVEL: i32 = 3;
+// 历史版本目录，非数字目录名不会被条目扫描读取。
+const HISTORY_DIR_NAME: &str = ".history";
+// 归档月份的文件后缀，归档文件位于年份目录下：`YYYY/YYYY-MM.tar.gz`。
+const MONTH_ARCHIVE_SUFFIX: &str = ".tar.gz";
+
+// 月、日可互换的样本日期，用于检测自定义文件名格式与默认格式之间的歧义。
+const AMBIGUITY_PROBES: [(i32, u32, u32); 2] = [(2024, 1, 2), (2024, 11, 12)];
+
+/// 临时模式下的全部条目，只存在于内存中，进程退出即丢弃。
+static EPHEMERAL_ENTRIES: Lazy<Mutex<BTreeMap<NaiveDate, EntryRecord>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
 
 #[derive(Debug, Clone)]
 pub struct StorageLayout {
     root: PathBuf,
+    filename_date_format: String,
+    ephemeral: bool,
+    /// 额外的只读根目录：读取时排在主目录之后，写入始终只落在主目录。
+    extra_roots: Vec<PathBuf>,
 }
 
 impl StorageLayout {
@@ -35,22 +62,202 @@ impl StorageLayout {
                 })
             })?;
 
-        ensure_dir(&base)?;
-        Ok(Self { root: base })
+        let ephemeral = app_prefs::is_ephemeral();
+        if !ephemeral {
+            ensure_dir(&base)?;
+        }
+        Ok(Self {
+            root: base,
+            filename_date_format: DEFAULT_FILENAME_DATE_FORMAT.to_string(),
+            ephemeral,
+            extra_roots: Vec::new(),
+        })
     }
 
     pub fn root(&self) -> &Path {
         &self.root
     }
+
+    /// 临时模式下所有读写都只针对内存，不触碰磁盘。
+    pub const fn is_ephemeral(&self) -> bool {
+        self.ephemeral
+    }
+
+    /// 使用自定义的文件名日期格式（如 `%Y_%m_%d`）；格式无效时保持默认值。
+    #[must_use]
+    pub fn with_filename_date_format(mut self, format: Option<&str>) -> Self {
+        self.filename_date_format = format
+            .map(str::trim)
+            .filter(|value| validate_filename_date_format(value).is_ok())
+            .unwrap_or(DEFAULT_FILENAME_DATE_FORMAT)
+            .to_string();
+        self
+    }
+
+    /// 挂载额外的只读根目录（如旧日记文件夹），与主目录相同的路径会被忽略。
+    #[must_use]
+    pub fn with_extra_roots(mut self, roots: &[String]) -> Self {
+        self.extra_roots = roots
+            .iter()
+            .map(PathBuf::from)
+            .filter(|root| *root != self.root)
+            .collect();
+        self
+    }
+
+    /// 按优先级返回各额外根目录的只读视图，沿用主目录的文件名格式；临时模式下没有额外目录。
+    ///
+    /// 暂时不可用的目录（如未挂载的移动硬盘）直接跳过。
+    fn read_only_views(&self) -> impl Iterator<Item = Self> + '_ {
+        self.extra_roots
+            .iter()
+            .filter(|root| !self.ephemeral && root.is_dir())
+            .map(|root| Self {
+                root: root.clone(),
+                filename_date_format: self.filename_date_format.clone(),
+                ephemeral: false,
+                extra_roots: Vec::new(),
+            })
+    }
+
+    fn file_name_for(&self, date: NaiveDate) -> String {
+        format!("{}.md", date.format(&self.filename_date_format))
+    }
+
+    /// 按当前格式解析文件名，失败时回退到默认格式，兼容切换格式前写入的文件。
+    fn parse_file_stem(&self, stem: &str) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(stem, &self.filename_date_format)
+            .or_else(|_| NaiveDate::parse_from_str(stem, DEFAULT_FILENAME_DATE_FORMAT))
+            .ok()
+    }
+}
+
+/// 校验文件名日期格式：必须能无损往返解析出同一天，且不包含路径分隔符。
+pub fn validate_filename_date_format(format: &str) -> Result<(), String> {
+    use std::fmt::Write as _;
+
+    if format.is_empty() {
+        return Err("filename date format must not be empty".to_string());
+    }
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("invalid filename date format \"{format}\""));
+    }
+    let sample = NaiveDate::from_ymd_opt(2024, 12, 31)
+        .ok_or_else(|| "failed to build sample date".to_string())?;
+    let mut rendered = String::new();
+    write!(rendered, "{}", sample.format(format))
+        .map_err(|_| format!("invalid filename date format \"{format}\""))?;
+    if rendered.contains(['/', '\\']) || rendered.starts_with('.') {
+        return Err(format!(
+            "filename date format \"{format}\" must not produce path separators"
+        ));
+    }
+    if NaiveDate::parse_from_str(&rendered, format).ok() != Some(sample) {
+        return Err(format!(
+            "filename date format \"{format}\" must include year, month and day"
+        ));
+    }
+    // 切换格式后旧文件仍按默认格式读取：同一文件名在两种格式下必须解析出同一天，否则旧文件会被误读为其他日期。
+    for (year, month, day) in AMBIGUITY_PROBES {
+        let probe = NaiveDate::from_ymd_opt(year, month, day)
+            .ok_or_else(|| "failed to build sample date".to_string())?;
+        let custom = probe.format(format).to_string();
+        let default = probe.format(DEFAULT_FILENAME_DATE_FORMAT).to_string();
+        let misread = [
+            NaiveDate::parse_from_str(&custom, DEFAULT_FILENAME_DATE_FORMAT),
+            NaiveDate::parse_from_str(&default, format),
+        ]
+        .into_iter()
+        .any(|parsed| parsed.is_ok_and(|parsed| parsed != probe));
+        if misread {
+            return Err(format!(
+                "filename date format \"{format}\" is ambiguous with existing YYYY-MM-DD file names"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 正文写入选项，由偏好设置决定。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteOptions {
+    /// 正文编码（明文或 zstd 压缩）；frontmatter 始终保持明文。
+    pub body_encoding: BodyEncoding,
+    /// 每篇日记保留的历史版本数，0 表示不记录历史。
+    pub revision_limit: usize,
+    pub document_style: DocumentStyle,
+}
+
+/// 文档排版风格，用于与其他 Markdown 工具互通；解析时两种风格均可识别。
+#[derive(Debug, Clone, Copy)]
+pub struct DocumentStyle {
+    /// 结束 `---` 之后的空行数，缺省为 1（即 `---\n\n`）。
+    pub blank_lines_after_frontmatter: usize,
+    /// 确保文件以换行结尾；缺省关闭，正文原样写入。
+    pub trailing_newline: bool,
+}
+
+pub const DEFAULT_BLANK_LINES_AFTER_FRONTMATTER: usize = 1;
+
+impl Default for DocumentStyle {
+    fn default() -> Self {
+        Self {
+            blank_lines_after_frontmatter: DEFAULT_BLANK_LINES_AFTER_FRONTMATTER,
+            trailing_newline: false,
+        }
+    }
 }
 
 /// Persist a diary entry as `$APP_DATA/YYYY/MM/YYYY-MM-DD.md`.
-pub fn write_entry(layout: &StorageLayout, summary: &DiaryEntry, body: &str) -> Result<(), String> {
+pub fn write_entry(
+    layout: &StorageLayout,
+    summary: &DiaryEntry,
+    body: &str,
+    options: &WriteOptions,
+) -> Result<(), String> {
     let date = NaiveDate::parse_from_str(&summary.date, DATE_FORMAT)
         .map_err(|err| format!("invalid diary date {}: {err}", summary.date))?;
-    let path = entry_path(layout.root(), &date, true)?;
+    if layout.is_ephemeral() {
+        let record = EntryRecord::new(summary.clone(), body.to_string());
+        lock_ephemeral()?.insert(date, record);
+        return Ok(());
+    }
+    // 写入始终落在散文件中，已归档的月份先自动解档。
+    if month_archive_path(layout, date.year(), date.month()).exists() {
+        unarchive_month(layout, date.year(), date.month())?;
+    }
+    let path = entry_path(layout, date, true)?;
+    if options.revision_limit > 0 {
+        archive_previous_version(layout, date, &path, body, options.revision_limit)?;
+    }
+
+    let document = render_document(summary, body, options)?;
+    fs::write(&path, document)
+        .map_err(|err| format!("failed to write entry file {}: {err}", path.display()))?;
 
-    let yaml = serde_yaml::to_string(summary)
+    // 切换文件名格式后首次写入时，移除旧格式的同日文件，避免重复条目。
+    let legacy = path.with_file_name(format!("{}.md", date.format(DEFAULT_FILENAME_DATE_FORMAT)));
+    if legacy != path && legacy.exists() {
+        fs::remove_file(&legacy)
+            .map_err(|err| format!("failed to remove {}: {err}", legacy.display()))?;
+    }
+    Ok(())
+}
+
+/// 按写入选项生成条目文件内容（frontmatter 与编码后的正文）。
+fn render_document(
+    summary: &DiaryEntry,
+    body: &str,
+    options: &WriteOptions,
+) -> Result<String, String> {
+    // 文件路径等属于运行时信息，确保不会落入 frontmatter。
+    let persisted = DiaryEntry {
+        body_encoding: options.body_encoding,
+        file_path: None,
+        filename_date: None,
+        ..summary.clone()
+    };
+    let yaml = serde_yaml::to_string(&persisted)
         .map_err(|err| format!("failed to serialize diary metadata: {err}"))?;
     let mut document = String::new();
     document.push_str("---\n");
@@ -58,38 +265,462 @@ pub fn write_entry(layout: &StorageLayout, summary: &DiaryEntry, body: &str) ->
     if !yaml.ends_with('\n') {
         document.push('\n');
     }
-    document.push_str("---\n\n");
-    document.push_str(body);
-
-    fs::write(&path, document)
-        .map_err(|err| format!("failed to write entry file {}: {err}", path.display()))
+    document.push_str("---\n");
+    let style = options.document_style;
+    document.push_str(&"\n".repeat(style.blank_lines_after_frontmatter));
+    document.push_str(&encode_body(body, options)?);
+    if style.trailing_newline && !document.ends_with('\n') {
+        document.push('\n');
+    }
+    Ok(document)
 }
 
 /// Load a specific entry by date.
+///
+/// 主目录优先，找不到时依次查找额外的只读根目录。
 pub fn load_entry(layout: &StorageLayout, date: &str) -> Result<Option<EntryRecord>, String> {
     let date = NaiveDate::parse_from_str(date, DATE_FORMAT)
         .map_err(|err| format!("invalid date {date}: {err}"))?;
-    let path = entry_path(layout.root(), &date, false)?;
-    if !path.exists() {
-        return Ok(None);
+    if layout.is_ephemeral() {
+        return Ok(lock_ephemeral()?.get(&date).cloned());
+    }
+    if let Some(record) = load_root_entry(layout, date)? {
+        return Ok(Some(record));
+    }
+    for view in layout.read_only_views() {
+        if let Some(record) = load_root_entry(&view, date)? {
+            return Ok(Some(record));
+        }
     }
+    Ok(None)
+}
+
+/// 只在 `layout` 的根目录（散文件与月份归档）中查找条目。
+fn load_root_entry(layout: &StorageLayout, date: NaiveDate) -> Result<Option<EntryRecord>, String> {
+    let Some(path) = existing_entry_path(layout, date)? else {
+        return load_archived_entry(layout, date);
+    };
     let content = fs::read_to_string(&path)
         .map_err(|err| format!("failed to read entry {}: {err}", path.display()))?;
     parse_document(&content).map(Some)
 }
 
+/// Check whether an entry exists for a date without parsing it.
+///
+/// 散文件只检查路径；月份已归档时读取归档的文件列表。
+pub fn entry_exists(layout: &StorageLayout, date: NaiveDate) -> Result<bool, String> {
+    if layout.is_ephemeral() {
+        return Ok(lock_ephemeral()?.contains_key(&date));
+    }
+    if root_entry_exists(layout, date)? {
+        return Ok(true);
+    }
+    for view in layout.read_only_views() {
+        if root_entry_exists(&view, date)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn root_entry_exists(layout: &StorageLayout, date: NaiveDate) -> Result<bool, String> {
+    if existing_entry_path(layout, date)?.is_some() {
+        return Ok(true);
+    }
+    let archive_path = month_archive_path(layout, date.year(), date.month());
+    if !archive_path.exists() {
+        return Ok(false);
+    }
+    let candidates = [
+        layout.file_name_for(date),
+        format!("{}.md", date.format(DEFAULT_FILENAME_DATE_FORMAT)),
+    ];
+    let files = archive::read_tar_gz_cached(&archive_path)?;
+    Ok(files.iter().any(|(file, _)| candidates.contains(file)))
+}
+
+/// 从月份归档中读取单篇日记，归档不存在或不含该日期时返回 None。
+fn load_archived_entry(
+    layout: &StorageLayout,
+    date: NaiveDate,
+) -> Result<Option<EntryRecord>, String> {
+    let archive_path = month_archive_path(layout, date.year(), date.month());
+    if !archive_path.exists() {
+        return Ok(None);
+    }
+    let candidates = [
+        layout.file_name_for(date),
+        format!("{}.md", date.format(DEFAULT_FILENAME_DATE_FORMAT)),
+    ];
+    let files = archive::read_tar_gz_cached(&archive_path)?;
+    candidates
+        .iter()
+        .find_map(|name| files.iter().find(|(file, _)| file == name))
+        .map(|(_, data)| parse_document(&String::from_utf8_lossy(data)))
+        .transpose()
+}
+
+/// Pack a month directory into `YYYY/YYYY-MM.tar.gz` and remove the loose files.
+///
+/// 返回归档的条目数；归档写入成功后只删除已打包的条目文件，目录中仍有其他文件时保留目录。
+pub fn archive_month(layout: &StorageLayout, year: i32, month: u32) -> Result<usize, String> {
+    if layout.is_ephemeral() {
+        return Err("month archives are not available in ephemeral mode".to_string());
+    }
+    let archive_path = month_archive_path(layout, year, month);
+    if archive_path.exists() {
+        return Err(format!("{year:04}-{month:02} is already archived"));
+    }
+    let Some(month_dir) = month_dir_path(layout.root(), year, month, false)? else {
+        return Err(format!("no entries stored for {year:04}-{month:02}"));
+    };
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(&month_dir)
+        .map_err(|err| format!("failed to read {}: {err}", month_dir.display()))?
+        .flatten()
+    {
+        let path = entry.path();
+        if !path.is_file() || path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+            continue;
+        }
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let data = fs::read(&path)
+            .map_err(|err| format!("failed to read entry {}: {err}", path.display()))?;
+        files.push((name.to_string(), data));
+    }
+    if files.is_empty() {
+        return Err(format!("no entries stored for {year:04}-{month:02}"));
+    }
+    files.sort();
+
+    archive::write_tar_gz(&archive_path, &files)?;
+    for (name, _) in &files {
+        let path = month_dir.join(name);
+        fs::remove_file(&path)
+            .map_err(|err| format!("failed to remove entry {}: {err}", path.display()))?;
+    }
+    if fs::remove_dir(&month_dir).is_err() {
+        eprintln!(
+            "[EchoNote] kept {} after archiving: directory is not empty",
+            month_dir.display()
+        );
+    }
+    Ok(files.len())
+}
+
+/// Restore an archived month back into loose files and delete the archive.
+///
+/// 与已存在的散文件重名时按 frontmatter 中的 HLC 保留较新的一份；无法解析而分不出新旧时
+/// 保留归档并返回错误，避免删除唯一的副本。返回实际恢复的条目数。
+pub fn unarchive_month(layout: &StorageLayout, year: i32, month: u32) -> Result<usize, String> {
+    let archive_path = month_archive_path(layout, year, month);
+    if layout.is_ephemeral() || !archive_path.exists() {
+        return Ok(0);
+    }
+    let files = archive::read_tar_gz(&archive_path)?;
+    let month_dir = month_dir_path(layout.root(), year, month, true)?
+        .ok_or_else(|| "failed to resolve month directory".to_string())?;
+    let mut restored = 0;
+    let mut conflicts = Vec::new();
+    for (name, data) in files {
+        let target = month_dir.join(&name);
+        if target.exists() {
+            let loose = fs::read(&target)
+                .map_err(|err| format!("failed to read entry {}: {err}", target.display()))?;
+            match archived_copy_is_newer(&loose, &data) {
+                Some(true) => {}
+                Some(false) => continue,
+                None => {
+                    conflicts.push(name);
+                    continue;
+                }
+            }
+        }
+        fs::write(&target, data)
+            .map_err(|err| format!("failed to restore {}: {err}", target.display()))?;
+        restored += 1;
+    }
+    if !conflicts.is_empty() {
+        return Err(format!(
+            "kept {} because these entries differ from the loose files and cannot be ordered: {}",
+            archive_path.display(),
+            conflicts.join(", ")
+        ));
+    }
+    fs::remove_file(&archive_path)
+        .map_err(|err| format!("failed to remove {}: {err}", archive_path.display()))?;
+    Ok(restored)
+}
+
+/// 比较同名的散文件与归档副本：内容相同或散文件不旧于归档时返回 false，
+/// 归档副本更新时返回 true；任一方无法解析时返回 None。
+fn archived_copy_is_newer(loose: &[u8], archived: &[u8]) -> Option<bool> {
+    if loose == archived {
+        return Some(false);
+    }
+    let hlc_of = |data: &[u8]| {
+        parse_document(&String::from_utf8_lossy(data))
+            .ok()
+            .map(|record| record.summary().hlc.clone())
+    };
+    Some(hlc_of(archived)? > hlc_of(loose)?)
+}
+
+fn month_archive_path(layout: &StorageLayout, year: i32, month: u32) -> PathBuf {
+    layout
+        .root()
+        .join(format!("{year:04}"))
+        .join(format!("{year:04}-{month:02}{MONTH_ARCHIVE_SUFFIX}"))
+}
+
+/// 列出年份目录下的归档月份。
+fn archived_months(year_dir: &Path) -> Vec<(i32, u32)> {
+    let Ok(entries) = fs::read_dir(year_dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| parse_archive_month(&entry.path()))
+        .collect()
+}
+
+/// Delete the entry file of a date, returning whether anything was removed.
+///
+/// 同时清理当前格式与默认格式的同日文件，避免删除后旧文件重新出现。
+/// 条目只存在于额外的只读根目录时返回错误说明，不会修改该目录。
+pub fn delete_entry(layout: &StorageLayout, date: NaiveDate) -> Result<bool, String> {
+    if layout.is_ephemeral() {
+        return Ok(lock_ephemeral()?.remove(&date).is_some());
+    }
+    unarchive_month(layout, date.year(), date.month())?;
+    let mut removed = false;
+    while let Some(path) = existing_entry_path(layout, date)? {
+        fs::remove_file(&path)
+            .map_err(|err| format!("failed to remove entry {}: {err}", path.display()))?;
+        removed = true;
+    }
+    if !removed {
+        if let Some(root) = read_only_root_of(layout, date)? {
+            return Err(format!(
+                "entry {date} is stored in read-only root {} and cannot be deleted here",
+                root.display()
+            ));
+        }
+    }
+    Ok(removed)
+}
+
+/// 条目只存在于额外只读根目录时返回该目录；主目录中存在或各处都没有时返回 None。
+pub fn read_only_root_of(
+    layout: &StorageLayout,
+    date: NaiveDate,
+) -> Result<Option<PathBuf>, String> {
+    if layout.is_ephemeral() || root_entry_exists(layout, date)? {
+        return Ok(None);
+    }
+    for view in layout.read_only_views() {
+        if root_entry_exists(&view, date)? {
+            return Ok(Some(view.root));
+        }
+    }
+    Ok(None)
+}
+
+/// List archived revisions of an entry, newest first.
+pub fn list_entry_revisions(
+    layout: &StorageLayout,
+    date: NaiveDate,
+) -> Result<Vec<EntryRevision>, String> {
+    let dir = history_dir(layout, date);
+    if layout.is_ephemeral() || !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut revisions = Vec::new();
+    for path in revision_files(&dir)?.into_iter().rev() {
+        let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let Ok(record) = read_frontmatter_record(&path) else {
+            continue;
+        };
+        let saved_at = id
+            .split('-')
+            .next()
+            .and_then(|millis| millis.parse().ok())
+            .unwrap_or_default();
+        revisions.push(EntryRevision {
+            id: id.to_string(),
+            saved_at,
+            hash: record.summary().hash.clone(),
+            word_count: record.summary().word_count,
+        });
+    }
+    Ok(revisions)
+}
+
+/// Load an archived revision by id.
+pub fn load_entry_revision(
+    layout: &StorageLayout,
+    date: NaiveDate,
+    id: &str,
+) -> Result<Option<EntryRecord>, String> {
+    // 版本号只包含数字、字母与连字符，防止路径穿越。
+    if id.is_empty() || !id.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-') {
+        return Err(format!("invalid revision id \"{id}\""));
+    }
+    let path = history_dir(layout, date).join(format!("{id}.md"));
+    if layout.is_ephemeral() || !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path)
+        .map_err(|err| format!("failed to read revision {}: {err}", path.display()))?;
+    parse_document(&content).map(Some)
+}
+
+/// 正文发生变化时，将磁盘上的旧版本归档到历史目录，并清理超出上限的最旧版本。
+///
+/// 条目的 HLC 在多次保存间保持不变，因此版本号使用归档时间与旧正文 hash 前缀。
+/// hash 按旧正文重新计算，不信任 frontmatter 中可能被篡改的值。
+fn archive_previous_version(
+    layout: &StorageLayout,
+    date: NaiveDate,
+    path: &Path,
+    body: &str,
+    limit: usize,
+) -> Result<(), String> {
+    let Some(existing) = find_entry_file(layout, path.parent().unwrap_or(path), date) else {
+        return Ok(());
+    };
+    let content = fs::read_to_string(&existing)
+        .map_err(|err| format!("failed to read entry {}: {err}", existing.display()))?;
+    let previous = parse_document(&content)?;
+    if previous.body() == body {
+        return Ok(());
+    }
+    store_revision(layout, date, &content, &previous, limit)
+}
+
+/// 将条目文件的原始内容存为该日期的历史版本，供合并重复文件时为落选的文件留底。
+pub fn snapshot_entry(
+    layout: &StorageLayout,
+    date: NaiveDate,
+    content: &str,
+    limit: usize,
+) -> Result<(), String> {
+    if layout.is_ephemeral() || limit == 0 {
+        return Ok(());
+    }
+    let record = parse_document(content)?;
+    store_revision(layout, date, content, &record, limit)
+}
+
+fn store_revision(
+    layout: &StorageLayout,
+    date: NaiveDate,
+    content: &str,
+    previous: &EntryRecord,
+    limit: usize,
+) -> Result<(), String> {
+    let dir = history_dir(layout, date);
+    ensure_dir(&dir)?;
+    let digest = previous.summary().hash_algo.digest(previous.body());
+    let id = format!(
+        "{}-{}",
+        chrono::Utc::now().timestamp_millis(),
+        &digest[..digest.len().min(12)]
+    );
+    let target = dir.join(format!("{id}.md"));
+    fs::write(&target, content)
+        .map_err(|err| format!("failed to write revision {}: {err}", target.display()))?;
+
+    let files = revision_files(&dir)?;
+    for stale in files.iter().take(files.len().saturating_sub(limit)) {
+        fs::remove_file(stale)
+            .map_err(|err| format!("failed to prune revision {}: {err}", stale.display()))?;
+    }
+    Ok(())
+}
+
+fn history_dir(layout: &StorageLayout, date: NaiveDate) -> PathBuf {
+    layout
+        .root()
+        .join(HISTORY_DIR_NAME)
+        .join(date.format(DATE_FORMAT).to_string())
+}
+
+/// 返回历史目录中的版本文件，按版本号（归档时间）升序排列。
+fn revision_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|err| format!("failed to read {}: {err}", dir.display()))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file() && path.extension().and_then(|ext| ext.to_str()) == Some("md")
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
 /// Load all entries for a month (year-month) and return them as records.
+///
+/// 额外的只读根目录按日期合并进结果，同一天以主目录（其次是更靠前的额外目录）为准。
 pub fn load_month_entries(
     layout: &StorageLayout,
     year: i32,
     month: u32,
 ) -> Result<Vec<EntryRecord>, String> {
-    let month_dir = month_dir_path(layout.root(), year, month, false)?;
-    if month_dir.is_none() {
-        return Ok(Vec::new());
+    if layout.is_ephemeral() {
+        return Ok(lock_ephemeral()?
+            .iter()
+            .filter(|(date, _)| date.year() == year && date.month() == month)
+            .map(|(_, record)| record.clone())
+            .collect());
     }
-    let month_dir = month_dir.unwrap();
+    let mut records = load_root_month_entries(layout, year, month)?;
+    let mut seen: HashSet<String> = records
+        .iter()
+        .map(|record| record.summary().date.clone())
+        .collect();
+    for view in layout.read_only_views() {
+        let view_records = load_root_month_entries(&view, year, month);
+        for record in skip_unreadable_root(&view, view_records).unwrap_or_default() {
+            if seen.insert(record.summary().date.clone()) {
+                records.push(record);
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// 只读取 `layout` 根目录下某月的条目（散文件或月份归档）。
+fn load_root_month_entries(
+    layout: &StorageLayout,
+    year: i32,
+    month: u32,
+) -> Result<Vec<EntryRecord>, String> {
+    // 透明读取月份归档；归档后保留下来的目录中仍可能有散文件。
     let mut records = Vec::new();
+    let archive_path = month_archive_path(layout, year, month);
+    if archive_path.exists() {
+        records.extend(
+            archive::read_tar_gz_cached(&archive_path)?
+                .iter()
+                .filter(|(name, _)| {
+                    Path::new(name).extension().and_then(|ext| ext.to_str()) == Some("md")
+                })
+                .filter_map(|(name, data)| {
+                    let record = parse_document(&String::from_utf8_lossy(data)).ok()?;
+                    Some(flag_filename_mismatch(layout, Path::new(name), record))
+                }),
+        );
+    }
+    let Some(month_dir) = month_dir_path(layout.root(), year, month, false)? else {
+        return Ok(records);
+    };
     for entry in fs::read_dir(&month_dir)
         .map_err(|err| format!("failed to read {}: {err}", month_dir.display()))?
     {
@@ -99,17 +730,550 @@ pub fn load_month_entries(
                 continue;
             }
             if let Ok(record) = read_frontmatter_record(&path) {
-                records.push(record);
+                records.push(flag_filename_mismatch(layout, &path, record));
             }
         }
     }
     Ok(records)
 }
 
-fn entry_path(root: &Path, date: &NaiveDate, ensure: bool) -> Result<PathBuf, String> {
-    let month_dir = month_dir_path(root, date.year(), date.month(), ensure)?;
+/// Rename files whose name disagrees with their frontmatter date, returning what changed.
+///
+/// frontmatter 视为唯一可信来源；目标位置已有文件时跳过并记录冲突，不覆盖任何数据。
+pub fn reconcile_month_dates(
+    layout: &StorageLayout,
+    year: i32,
+    month: u32,
+) -> Result<DateReconcileReport, String> {
+    let mut report = DateReconcileReport::default();
+    if layout.is_ephemeral() {
+        return Ok(report);
+    }
+    // 只整理主目录，额外的只读根目录不做任何改动。
+    for record in load_root_month_entries(layout, year, month)? {
+        let summary = record.summary();
+        let Some(filename_date) = summary.filename_date.as_deref() else {
+            continue;
+        };
+        let Ok(target_date) = NaiveDate::parse_from_str(&summary.date, DATE_FORMAT) else {
+            eprintln!(
+                "[EchoNote] cannot reconcile {filename_date}: invalid frontmatter date {}",
+                summary.date
+            );
+            continue;
+        };
+        let Some(source) = find_month_file(layout, year, month, filename_date)? else {
+            continue;
+        };
+        let target = entry_path(layout, target_date, true)?;
+        if target.exists() {
+            report.conflicts.push(source.display().to_string());
+            continue;
+        }
+        fs::rename(&source, &target).map_err(|err| {
+            format!(
+                "failed to rename {} to {}: {err}",
+                source.display(),
+                target.display()
+            )
+        })?;
+        report.renamed.push(summary.date.clone());
+    }
+    Ok(report)
+}
+
+/// Find dates backed by more than one entry file, keyed by frontmatter date.
+///
+/// 同一日期可能同时存在自定义格式与默认格式的文件，或另一天的文件带有该日期的 frontmatter；
+/// 无法解析的文件不参与检测。月份归档中的条目以 `YYYY-MM.tar.gz/<文件名>` 的形式列出。
+pub fn find_duplicate_files(
+    layout: &StorageLayout,
+) -> Result<BTreeMap<NaiveDate, Vec<PathBuf>>, String> {
+    let mut by_date: BTreeMap<NaiveDate, Vec<PathBuf>> = BTreeMap::new();
+    if layout.is_ephemeral() {
+        return Ok(by_date);
+    }
+    for year_dir in read_child_dirs(layout.root())? {
+        for month_dir in read_child_dirs(&year_dir)? {
+            for entry in fs::read_dir(&month_dir)
+                .map_err(|err| format!("failed to read {}: {err}", month_dir.display()))?
+                .flatten()
+            {
+                let path = entry.path();
+                if !path.is_file() || path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+                    continue;
+                }
+                let Ok(record) = read_frontmatter_record(&path) else {
+                    continue;
+                };
+                if let Ok(date) = NaiveDate::parse_from_str(&record.summary().date, DATE_FORMAT) {
+                    by_date.entry(date).or_default().push(path);
+                }
+            }
+        }
+        for (year, month) in archived_months(&year_dir) {
+            let archive_path = month_archive_path(layout, year, month);
+            for (name, data) in archive::read_tar_gz_cached(&archive_path)?.iter() {
+                let Some(date) = extract_frontmatter(&String::from_utf8_lossy(data))
+                    .ok()
+                    .and_then(|(summary, _)| {
+                        NaiveDate::parse_from_str(&summary.date, DATE_FORMAT).ok()
+                    })
+                else {
+                    continue;
+                };
+                by_date
+                    .entry(date)
+                    .or_default()
+                    .push(archive_path.join(name));
+            }
+        }
+    }
+    by_date.retain(|_, paths| paths.len() > 1);
+    for paths in by_date.values_mut() {
+        paths.sort();
+    }
+    Ok(by_date)
+}
+
+/// 读取指定路径的完整条目（frontmatter 与正文），路径也可以指向月份归档中的条目。
+pub fn load_entry_file(path: &Path) -> Result<EntryRecord, String> {
+    parse_document(&read_entry_content(path)?)
+}
+
+/// 读取条目文件的原始内容，路径也可以指向月份归档中的条目。
+pub fn read_entry_content(path: &Path) -> Result<String, String> {
+    if let Some((archive_path, name)) = archive_member(path) {
+        let files = archive::read_tar_gz_cached(archive_path)?;
+        let (_, data) = files
+            .iter()
+            .find(|(file, _)| file == name)
+            .ok_or_else(|| format!("entry {} not found", path.display()))?;
+        return Ok(String::from_utf8_lossy(data).into_owned());
+    }
+    fs::read_to_string(path)
+        .map_err(|err| format!("failed to read entry {}: {err}", path.display()))
+}
+
+/// 条目文件解档后所在的散文件路径；普通路径原样返回。
+pub fn loose_entry_path(layout: &StorageLayout, path: &Path) -> PathBuf {
+    archive_member(path)
+        .and_then(|(archive_path, name)| {
+            let (year, month) = parse_archive_month(archive_path)?;
+            Some(
+                layout
+                    .root()
+                    .join(format!("{year:04}"))
+                    .join(format!("{month:02}"))
+                    .join(name),
+            )
+        })
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// 删除单个条目文件；归档中的条目先解档再删除。文件已不存在时返回 false。
+pub fn remove_entry_file(layout: &StorageLayout, path: &Path) -> Result<bool, String> {
+    if let Some((year, month)) =
+        archive_member(path).and_then(|(archive_path, _)| parse_archive_month(archive_path))
+    {
+        unarchive_month(layout, year, month)?;
+    }
+    let path = loose_entry_path(layout, path);
+    if !path.exists() {
+        return Ok(false);
+    }
+    fs::remove_file(&path).map_err(|err| format!("failed to remove {}: {err}", path.display()))?;
+    Ok(true)
+}
+
+/// 拆分 `YYYY-MM.tar.gz/<文件名>` 形式的归档条目路径。
+fn archive_member(path: &Path) -> Option<(&Path, &str)> {
+    let archive_path = path.parent()?;
+    let is_archive = archive_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(MONTH_ARCHIVE_SUFFIX));
+    if !is_archive {
+        return None;
+    }
+    Some((archive_path, path.file_name()?.to_str()?))
+}
+
+/// 从 `YYYY-MM.tar.gz` 文件名解析归档的年月。
+fn parse_archive_month(archive_path: &Path) -> Option<(i32, u32)> {
+    let stem = archive_path
+        .file_name()?
+        .to_str()?
+        .strip_suffix(MONTH_ARCHIVE_SUFFIX)?;
+    let (year, month) = stem.split_once('-')?;
+    let month: u32 = month.parse().ok()?;
+    (1..=12)
+        .contains(&month)
+        .then_some((year.parse().ok()?, month))
+}
+
+/// 文件名日期与 frontmatter 日期不一致时记录日志，并在摘要中标出文件名日期。
+fn flag_filename_mismatch(layout: &StorageLayout, path: &Path, record: EntryRecord) -> EntryRecord {
+    let Some(filename_date) = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| layout.parse_file_stem(stem))
+    else {
+        return record;
+    };
+    let summary = record.summary();
+    let frontmatter_date = NaiveDate::parse_from_str(&summary.date, DATE_FORMAT).ok();
+    if frontmatter_date == Some(filename_date) {
+        return record;
+    }
+
+    let filename_date = filename_date.format(DATE_FORMAT).to_string();
+    eprintln!(
+        "[EchoNote] entry {} has frontmatter date {} but filename date {filename_date}",
+        path.display(),
+        summary.date
+    );
+    let mut flagged = summary.clone();
+    flagged.filename_date = Some(filename_date);
+    EntryRecord::new(flagged, record.body().to_string())
+}
+
+/// 在月份目录中按文件名日期查找文件（当前格式优先，其次默认格式）。
+fn find_month_file(
+    layout: &StorageLayout,
+    year: i32,
+    month: u32,
+    filename_date: &str,
+) -> Result<Option<PathBuf>, String> {
+    let date = NaiveDate::parse_from_str(filename_date, DATE_FORMAT)
+        .map_err(|err| format!("invalid date {filename_date}: {err}"))?;
+    let Some(dir) = month_dir_path(layout.root(), year, month, false)? else {
+        return Ok(None);
+    };
+    Ok(find_entry_file(layout, &dir, date))
+}
+
+/// List every `(year, month)` that has a month directory, newest first.
+///
+/// 包含额外只读根目录中的月份。
+pub fn list_populated_months(layout: &StorageLayout) -> Result<Vec<(i32, u32)>, String> {
+    if layout.is_ephemeral() {
+        let mut months: Vec<(i32, u32)> = lock_ephemeral()?
+            .keys()
+            .map(|date| (date.year(), date.month()))
+            .collect();
+        months.dedup();
+        months.reverse();
+        return Ok(months);
+    }
+    let mut months = list_root_months(layout.root())?;
+    for view in layout.read_only_views() {
+        let view_months = list_root_months(view.root());
+        months.extend(skip_unreadable_root(&view, view_months).unwrap_or_default());
+    }
+    months.sort_unstable_by(|a, b| b.cmp(a));
+    months.dedup();
+    Ok(months)
+}
+
+fn list_root_months(root: &Path) -> Result<Vec<(i32, u32)>, String> {
+    let mut months = Vec::new();
+    for year_dir in read_child_dirs(root)? {
+        months.extend(archived_months(&year_dir));
+        let Some(year) = dir_number::<i32>(&year_dir) else {
+            continue;
+        };
+        for month_dir in read_child_dirs(&year_dir)? {
+            if let Some(month) = dir_number::<u32>(&month_dir).filter(|m| (1..=12).contains(m)) {
+                months.push((year, month));
+            }
+        }
+    }
+    Ok(months)
+}
+
+fn dir_number<T: std::str::FromStr>(path: &Path) -> Option<T> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.parse().ok())
+}
+
+/// 删除全部条目：年份目录（含月份目录与归档）以及历史版本目录；临时模式下只清空内存。
+///
+/// 返回被删除的目录路径。
+pub fn wipe_entries(layout: &StorageLayout) -> Result<Vec<PathBuf>, String> {
+    if layout.is_ephemeral() {
+        lock_ephemeral()?.clear();
+        return Ok(Vec::new());
+    }
+    let mut targets = read_child_dirs(layout.root())?;
+    let history = layout.root().join(HISTORY_DIR_NAME);
+    if history.is_dir() {
+        targets.push(history);
+    }
+    for dir in &targets {
+        fs::remove_dir_all(dir)
+            .map_err(|err| format!("failed to remove {}: {err}", dir.display()))?;
+    }
+    Ok(targets)
+}
+
+/// List every stored entry date, sorted ascending.
+///
+/// 包含额外只读根目录中的条目，与按月列表保持一致。
+pub fn list_entry_dates(layout: &StorageLayout) -> Result<Vec<NaiveDate>, String> {
+    if layout.is_ephemeral() {
+        return Ok(lock_ephemeral()?.keys().copied().collect());
+    }
+    let mut dates = list_root_entry_dates(layout)?;
+    for view in layout.read_only_views() {
+        let view_dates = list_root_entry_dates(&view);
+        dates.extend(skip_unreadable_root(&view, view_dates).unwrap_or_default());
+    }
+    dates.sort();
+    dates.dedup();
+    Ok(dates)
+}
+
+/// 只列出 `layout` 根目录下的条目日期（散文件与月份归档）。
+fn list_root_entry_dates(layout: &StorageLayout) -> Result<Vec<NaiveDate>, String> {
+    let mut dates = Vec::new();
+    for year_dir in read_child_dirs(layout.root())? {
+        for (year, month) in archived_months(&year_dir) {
+            let archive_path = month_archive_path(layout, year, month);
+            for (name, _) in archive::read_tar_gz_cached(&archive_path)?.iter() {
+                if let Some(date) = name
+                    .strip_suffix(".md")
+                    .and_then(|stem| layout.parse_file_stem(stem))
+                {
+                    dates.push(date);
+                }
+            }
+        }
+        for month_dir in read_child_dirs(&year_dir)? {
+            for entry in fs::read_dir(&month_dir)
+                .map_err(|err| format!("failed to read {}: {err}", month_dir.display()))?
+                .flatten()
+            {
+                let path = entry.path();
+                if !path.is_file() || path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+                    continue;
+                }
+                let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                    continue;
+                };
+                if let Some(date) = layout.parse_file_stem(stem) {
+                    dates.push(date);
+                }
+            }
+        }
+    }
+    Ok(dates)
+}
+
+/// 额外根目录读取失败（如权限不足）时记录并跳过，不影响主目录的结果。
+fn skip_unreadable_root<T>(view: &StorageLayout, result: Result<T, String>) -> Option<T> {
+    result
+        .map_err(|err| {
+            eprintln!(
+                "[EchoNote] skipping read-only root {}: {err}",
+                view.root().display()
+            );
+        })
+        .ok()
+}
+
+/// Sum the size of every entry file, returning `(total_bytes, entry_count)`.
+///
+/// 月份归档按压缩后的文件大小计入，条目数按归档内的条目计算。
+pub fn entry_disk_usage(layout: &StorageLayout) -> Result<(u64, usize), String> {
+    if layout.is_ephemeral() {
+        let entries = lock_ephemeral()?;
+        let bytes = entries
+            .values()
+            .map(|record| record.body().len() as u64)
+            .sum();
+        return Ok((bytes, entries.len()));
+    }
+    let mut total_bytes = 0;
+    let mut entry_count = 0;
+    for year_dir in read_child_dirs(layout.root())? {
+        for month_dir in read_child_dirs(&year_dir)? {
+            for entry in fs::read_dir(&month_dir)
+                .map_err(|err| format!("failed to read {}: {err}", month_dir.display()))?
+                .flatten()
+            {
+                let path = entry.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+                    continue;
+                }
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                if metadata.is_file() {
+                    total_bytes += metadata.len();
+                    entry_count += 1;
+                }
+            }
+        }
+        for (year, month) in archived_months(&year_dir) {
+            let archive_path = month_archive_path(layout, year, month);
+            let Ok(metadata) = fs::metadata(&archive_path) else {
+                continue;
+            };
+            total_bytes += metadata.len();
+            entry_count += archive::read_tar_gz_cached(&archive_path)?
+                .iter()
+                .filter(|(name, _)| {
+                    Path::new(name).extension().and_then(|ext| ext.to_str()) == Some("md")
+                })
+                .count();
+        }
+    }
+    Ok((total_bytes, entry_count))
+}
+
+/// 查询存储根目录所在磁盘卷的可用空间；非 Unix 平台或查询失败时返回 None。
+pub fn available_space(layout: &StorageLayout) -> Option<u64> {
+    if layout.is_ephemeral() {
+        return None;
+    }
+    available_space_at(layout.root())
+}
+
+#[cfg(unix)]
+#[allow(unsafe_code)]
+fn available_space_at(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: c_path 为合法的 NUL 结尾字符串，stat 指向足够大小的可写内存；
+    // 仅在返回 0（成功）时才读取已初始化的结果。
+    let stat = unsafe {
+        if libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+const fn available_space_at(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Rewrite every stored body with the given options, returning how many files changed.
+///
+/// 已处于目标格式的条目会被跳过，因此可重复执行；月份归档中的条目在归档内改写，不会解档。
+pub fn rewrite_entry_bodies(
+    layout: &StorageLayout,
+    options: &WriteOptions,
+) -> Result<usize, String> {
+    let mut rewritten = 0;
+    if layout.is_ephemeral() {
+        return Ok(rewritten);
+    }
+    for date in list_entry_dates(layout)? {
+        let Some(path) = existing_entry_path(layout, date)? else {
+            continue;
+        };
+        let content = fs::read_to_string(&path)
+            .map_err(|err| format!("failed to read entry {}: {err}", path.display()))?;
+        let record = parse_document(&content)?;
+        if record.summary().body_encoding == options.body_encoding {
+            continue;
+        }
+        write_entry(layout, record.summary(), record.body(), options)?;
+        rewritten += 1;
+    }
+    for year_dir in read_child_dirs(layout.root())? {
+        for (year, month) in archived_months(&year_dir) {
+            rewritten +=
+                rewrite_archived_bodies(&month_archive_path(layout, year, month), options)?;
+        }
+    }
+    Ok(rewritten)
+}
+
+/// 改写归档内编码与目标不同的条目并重新打包，返回改写的条目数。
+fn rewrite_archived_bodies(archive_path: &Path, options: &WriteOptions) -> Result<usize, String> {
+    let mut files = archive::read_tar_gz(archive_path)?;
+    let mut rewritten = 0;
+    for (_, data) in &mut files {
+        let record = parse_document(&String::from_utf8_lossy(data))?;
+        if record.summary().body_encoding == options.body_encoding {
+            continue;
+        }
+        *data = render_document(record.summary(), record.body(), options)?.into_bytes();
+        rewritten += 1;
+    }
+    if rewritten > 0 {
+        archive::write_tar_gz(archive_path, &files)?;
+    }
+    Ok(rewritten)
+}
+
+fn lock_ephemeral(
+) -> Result<std::sync::MutexGuard<'static, BTreeMap<NaiveDate, EntryRecord>>, String> {
+    EPHEMERAL_ENTRIES
+        .lock()
+        .map_err(|_| "failed to lock ephemeral entries".to_string())
+}
+
+fn read_child_dirs(path: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(path)
+        .map_err(|err| format!("failed to read {}: {err}", path.display()))?
+        .flatten()
+    {
+        let child = entry.path();
+        let is_numeric = child
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.chars().all(|ch| ch.is_ascii_digit()));
+        if child.is_dir() && is_numeric {
+            dirs.push(child);
+        }
+    }
+    Ok(dirs)
+}
+
+/// 计算条目文件的绝对路径，不检查文件是否存在，也不创建目录。
+pub fn entry_file_path(layout: &StorageLayout, date: NaiveDate) -> PathBuf {
+    layout
+        .root()
+        .join(format!("{:04}", date.year()))
+        .join(format!("{:02}", date.month()))
+        .join(layout.file_name_for(date))
+}
+
+fn entry_path(layout: &StorageLayout, date: NaiveDate, ensure: bool) -> Result<PathBuf, String> {
+    let month_dir = month_dir_path(layout.root(), date.year(), date.month(), ensure)?;
     let dir = month_dir.ok_or_else(|| "failed to resolve month directory".to_string())?;
-    Ok(dir.join(format!("{}.md", date.format(DATE_FORMAT))))
+    Ok(dir.join(layout.file_name_for(date)))
+}
+
+/// 返回已存在的条目文件：优先当前格式，其次默认格式。
+fn existing_entry_path(layout: &StorageLayout, date: NaiveDate) -> Result<Option<PathBuf>, String> {
+    let Some(dir) = month_dir_path(layout.root(), date.year(), date.month(), false)? else {
+        return Ok(None);
+    };
+    Ok(find_entry_file(layout, &dir, date))
+}
+
+fn find_entry_file(layout: &StorageLayout, dir: &Path, date: NaiveDate) -> Option<PathBuf> {
+    let candidates = [
+        layout.file_name_for(date),
+        format!("{}.md", date.format(DEFAULT_FILENAME_DATE_FORMAT)),
+    ];
+    candidates
+        .into_iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
 }
 
 fn month_dir_path(
@@ -140,9 +1304,64 @@ fn ensure_dir(path: &Path) -> Result<(), String> {
         .map_err(|err| format!("failed to create directory {}: {err}", path.display()))
 }
 
-fn parse_document(document: &str) -> Result<EntryRecord, String> {
+/// 解析完整的条目文档（frontmatter + 正文），与加载磁盘条目使用同一解析器。
+pub fn parse_document(document: &str) -> Result<EntryRecord, String> {
     let (summary, remainder) = extract_frontmatter(document)?;
-    Ok(EntryRecord::new(summary, remainder.to_string()))
+    let body = match summary.body_encoding {
+        BodyEncoding::Plain => plain_body(&summary, remainder).to_string(),
+        BodyEncoding::Zstd => decode_body(summary.body_encoding, remainder)?,
+    };
+    Ok(EntryRecord::new(summary, body))
+}
+
+/// 从 frontmatter 之后的内容中还原明文正文。
+///
+/// 写入时在 `---` 之后补的空行与结尾换行不属于正文，但正文本身也可能以换行开头或结尾，
+/// 因此按 frontmatter 中的 hash 找出唯一一致的切分；都不一致时（如手动编辑过）
+/// 去掉全部前导空行，结尾保持原样。
+fn plain_body<'a>(summary: &DiaryEntry, stored: &'a str) -> &'a str {
+    let mut candidate = stored;
+    loop {
+        if summary.body_matches(candidate) {
+            return candidate;
+        }
+        if let Some(stripped) = candidate
+            .strip_suffix('\n')
+            .filter(|stripped| summary.body_matches(stripped))
+        {
+            return stripped;
+        }
+        match candidate
+            .strip_prefix("\r\n")
+            .or_else(|| candidate.strip_prefix('\n'))
+        {
+            Some(next) => candidate = next,
+            None => return candidate,
+        }
+    }
+}
+
+/// 按写入选项编码正文；压缩与否记录在 frontmatter 的 `bodyEncoding` 中，不在正文里加标记。
+fn encode_body(body: &str, options: &WriteOptions) -> Result<String, String> {
+    if options.body_encoding == BodyEncoding::Plain {
+        return Ok(body.to_string());
+    }
+    let compressed = zstd::encode_all(body.as_bytes(), ZSTD_LEVEL)
+        .map_err(|err| format!("failed to compress entry body: {err}"))?;
+    Ok(format!("{}\n", BASE64.encode(compressed)))
+}
+
+/// 按 frontmatter 记录的编码解码正文：明文正文原样返回，即使内容形似压缩数据。
+fn decode_body(encoding: BodyEncoding, stored: &str) -> Result<String, String> {
+    if encoding == BodyEncoding::Plain {
+        return Ok(stored.to_string());
+    }
+    let compressed = BASE64
+        .decode(stored.trim().as_bytes())
+        .map_err(|err| format!("invalid compressed body encoding: {err}"))?;
+    let decompressed = zstd::decode_all(compressed.as_slice())
+        .map_err(|err| format!("failed to decompress entry body: {err}"))?;
+    String::from_utf8(decompressed).map_err(|_| "decompressed body is not valid UTF-8".to_string())
 }
 
 fn read_frontmatter_record(path: &Path) -> Result<EntryRecord, String> {
@@ -189,15 +1408,342 @@ fn extract_frontmatter<'a>(document: &'a str) -> Result<(DiaryEntry, &'a str), S
         .find(closing_marker)
         .ok_or_else(|| "entry missing closing frontmatter delimiter".to_string())?;
     let (frontmatter_block, rest) = body_start.split_at(closing_idx);
-    let mut remainder = rest
+    // 结束 `---` 之后的换行与空行留给 `plain_body` 按 hash 区分。
+    let remainder = rest
         .strip_prefix(closing_marker)
         .ok_or_else(|| "entry closing delimiter malformed".to_string())?;
 
-    while remainder.starts_with(['\r', '\n']) {
-        remainder = &remainder[1..];
-    }
-
     let summary: DiaryEntry = serde_yaml::from_str(frontmatter_block)
         .map_err(|err| format!("failed to parse diary metadata: {err}"))?;
     Ok((summary, remainder))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::HashAlgorithm;
+
+    fn temp_layout() -> StorageLayout {
+        let root = std::env::temp_dir().join(format!("echonote-storage-{}", uuid::Uuid::new_v4()));
+        ensure_dir(&root).unwrap();
+        StorageLayout {
+            root,
+            filename_date_format: DEFAULT_FILENAME_DATE_FORMAT.to_string(),
+            ephemeral: false,
+            extra_roots: Vec::new(),
+        }
+    }
+
+    fn summary_for(date: &str, body: &str) -> DiaryEntry {
+        let mut summary: DiaryEntry =
+            serde_yaml::from_str(&format!("hlc: test\nhash: ''\ndate: {date}\n")).unwrap();
+        summary.hash = HashAlgorithm::Blake3.digest(body);
+        summary
+    }
+
+    fn write(layout: &StorageLayout, date: &str, body: &str, options: &WriteOptions) {
+        write_entry(layout, &summary_for(date, body), body, options).unwrap();
+    }
+
+    #[test]
+    fn revisions_keep_previous_bodies_up_to_limit() {
+        let layout = temp_layout();
+        let options = WriteOptions {
+            revision_limit: 2,
+            ..WriteOptions::default()
+        };
+        for body in ["one", "two", "three", "four"] {
+            write(&layout, "2024-03-04", body, &options);
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        let date = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let revisions = list_entry_revisions(&layout, date).unwrap();
+        let bodies: Vec<String> = revisions
+            .iter()
+            .map(|revision| {
+                load_entry_revision(&layout, date, &revision.id)
+                    .unwrap()
+                    .unwrap()
+                    .body()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(bodies, ["three", "two"]);
+        assert!(load_entry_revision(&layout, date, "../2024-03-04").is_err());
+        fs::remove_dir_all(layout.root()).unwrap();
+    }
+
+    #[test]
+    fn revision_id_ignores_tampered_frontmatter_hash() {
+        let layout = temp_layout();
+        let mut summary = summary_for("2024-03-05", "old");
+        summary.hash = "../../évil/path".to_string();
+        let options = WriteOptions {
+            revision_limit: 5,
+            ..WriteOptions::default()
+        };
+        write_entry(&layout, &summary, "old", &options).unwrap();
+        write(&layout, "2024-03-05", "new", &options);
+
+        let date = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        let revisions = list_entry_revisions(&layout, date).unwrap();
+        assert_eq!(revisions.len(), 1);
+        let (millis, digest) = revisions[0].id.split_once('-').unwrap();
+        assert!(millis.chars().all(|ch| ch.is_ascii_digit()));
+        assert_eq!(digest, &HashAlgorithm::Blake3.digest("old")[..12]);
+        fs::remove_dir_all(layout.root()).unwrap();
+    }
+
+    #[test]
+    fn archiving_keeps_unrelated_files() {
+        let layout = temp_layout();
+        write(&layout, "2024-02-03", "archived", &WriteOptions::default());
+        let month_dir = layout.root().join("2024").join("02");
+        fs::write(month_dir.join("notes.txt"), "keep me").unwrap();
+
+        assert_eq!(archive_month(&layout, 2024, 2).unwrap(), 1);
+        assert!(month_dir.join("notes.txt").exists());
+        assert!(!month_dir.join("2024-02-03.md").exists());
+        let date = NaiveDate::from_ymd_opt(2024, 2, 3).unwrap();
+        assert!(entry_exists(&layout, date).unwrap());
+        assert_eq!(
+            load_entry(&layout, "2024-02-03").unwrap().unwrap().body(),
+            "archived"
+        );
+        assert_eq!(load_month_entries(&layout, 2024, 2).unwrap().len(), 1);
+        fs::remove_dir_all(layout.root()).unwrap();
+    }
+
+    #[test]
+    fn archived_entries_count_toward_usage_and_rewrites() {
+        let layout = temp_layout();
+        let zstd = WriteOptions {
+            body_encoding: BodyEncoding::Zstd,
+            ..WriteOptions::default()
+        };
+        write(&layout, "2024-08-01", "# Day\n\narchived", &zstd);
+        archive_month(&layout, 2024, 8).unwrap();
+
+        let archive_path = month_archive_path(&layout, 2024, 8);
+        let archive_size = fs::metadata(&archive_path).unwrap().len();
+        assert_eq!(entry_disk_usage(&layout).unwrap(), (archive_size, 1));
+
+        assert_eq!(
+            rewrite_entry_bodies(&layout, &WriteOptions::default()).unwrap(),
+            1
+        );
+        assert!(archive_path.exists());
+        let record = load_entry(&layout, "2024-08-01").unwrap().unwrap();
+        assert_eq!(record.summary().body_encoding, BodyEncoding::Plain);
+        assert_eq!(record.body(), "# Day\n\narchived");
+        assert_eq!(
+            rewrite_entry_bodies(&layout, &WriteOptions::default()).unwrap(),
+            0
+        );
+        fs::remove_dir_all(layout.root()).unwrap();
+    }
+
+    #[test]
+    fn unarchive_keeps_newer_copy_and_refuses_unordered_conflicts() {
+        let layout = temp_layout();
+        let options = WriteOptions::default();
+        let document = |body: &str, hlc: &str| {
+            let mut summary = summary_for("2024-09-01", body);
+            summary.hlc = hlc.to_string();
+            render_document(&summary, body, &options).unwrap()
+        };
+        let loose_path = layout.root().join("2024").join("09").join("2024-09-01.md");
+        let archive_with = |body: &str, hlc: &str| {
+            let archive_path = month_archive_path(&layout, 2024, 9);
+            ensure_dir(archive_path.parent().unwrap()).unwrap();
+            let files = [(
+                "2024-09-01.md".to_string(),
+                document(body, hlc).into_bytes(),
+            )];
+            archive::write_tar_gz(&archive_path, &files).unwrap();
+        };
+        ensure_dir(loose_path.parent().unwrap()).unwrap();
+
+        // 散文件更新：保留散文件，归档照常删除。
+        archive_with("archived", "1000-0-a");
+        fs::write(&loose_path, document("loose", "2000-0-a")).unwrap();
+        assert_eq!(unarchive_month(&layout, 2024, 9).unwrap(), 0);
+        assert_eq!(
+            load_entry(&layout, "2024-09-01").unwrap().unwrap().body(),
+            "loose"
+        );
+        assert!(!month_archive_path(&layout, 2024, 9).exists());
+
+        // 归档副本更新：覆盖散文件。
+        archive_with("archived", "3000-0-a");
+        assert_eq!(unarchive_month(&layout, 2024, 9).unwrap(), 1);
+        assert_eq!(
+            load_entry(&layout, "2024-09-01").unwrap().unwrap().body(),
+            "archived"
+        );
+
+        // 散文件无法解析：保留归档并报错。
+        archive_with("archived", "4000-0-a");
+        fs::write(&loose_path, "not a diary entry").unwrap();
+        let err = unarchive_month(&layout, 2024, 9).unwrap_err();
+        assert!(err.contains("2024-09-01.md"), "{err}");
+        assert!(month_archive_path(&layout, 2024, 9).exists());
+        fs::remove_dir_all(layout.root()).unwrap();
+    }
+
+    #[test]
+    fn plain_bodies_round_trip_exactly() {
+        let layout = temp_layout();
+        for (blank_lines, trailing_newline) in [(0, false), (1, true), (3, true)] {
+            let options = WriteOptions {
+                document_style: DocumentStyle {
+                    blank_lines_after_frontmatter: blank_lines,
+                    trailing_newline,
+                },
+                ..WriteOptions::default()
+            };
+            for body in ["", "day", "day\n", "\n\nindented\n\n", "\r\nwindows\r\n"] {
+                write(&layout, "2024-06-01", body, &options);
+                let record = load_entry(&layout, "2024-06-01").unwrap().unwrap();
+                assert_eq!(record.body(), body, "blank lines {blank_lines}");
+            }
+        }
+        fs::remove_dir_all(layout.root()).unwrap();
+    }
+
+    #[test]
+    fn duplicates_inside_month_archives_are_found_and_removable() {
+        let layout = temp_layout();
+        write(&layout, "2024-07-01", "original", &WriteOptions::default());
+        let month_dir = layout.root().join("2024").join("07");
+        let content = fs::read_to_string(month_dir.join("2024-07-01.md")).unwrap();
+        fs::write(month_dir.join("2024-07-09.md"), &content).unwrap();
+        archive_month(&layout, 2024, 7).unwrap();
+
+        let date = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+        let paths = find_duplicate_files(&layout)
+            .unwrap()
+            .remove(&date)
+            .unwrap();
+        assert_eq!(paths.len(), 2);
+        let stray = &paths[1];
+        assert_eq!(load_entry_file(stray).unwrap().body(), "original");
+        assert_eq!(
+            loose_entry_path(&layout, stray),
+            month_dir.join("2024-07-09.md")
+        );
+
+        snapshot_entry(&layout, date, &read_entry_content(stray).unwrap(), 5).unwrap();
+        assert!(remove_entry_file(&layout, stray).unwrap());
+        assert!(find_duplicate_files(&layout).unwrap().is_empty());
+        assert_eq!(list_entry_revisions(&layout, date).unwrap().len(), 1);
+        fs::remove_dir_all(layout.root()).unwrap();
+    }
+
+    #[test]
+    fn filename_format_must_not_be_ambiguous_with_default() {
+        assert!(validate_filename_date_format("%Y_%m_%d").is_ok());
+        assert!(validate_filename_date_format("%d.%m.%Y").is_ok());
+        assert!(validate_filename_date_format("%Y-%d-%m").is_err());
+        assert!(validate_filename_date_format("%Y").is_err());
+        assert!(validate_filename_date_format("%Y/%m/%d").is_err());
+    }
+
+    #[test]
+    fn legacy_file_names_survive_format_switch() {
+        let layout = temp_layout();
+        write(&layout, "2024-01-02", "old", &WriteOptions::default());
+
+        let custom = layout.clone().with_filename_date_format(Some("%Y_%m_%d"));
+        let date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        assert_eq!(list_entry_dates(&custom).unwrap(), [date]);
+        assert_eq!(
+            load_entry(&custom, "2024-01-02").unwrap().unwrap().body(),
+            "old"
+        );
+
+        write(&custom, "2024-01-02", "new", &WriteOptions::default());
+        let month_dir = layout.root().join("2024").join("01");
+        assert!(month_dir.join("2024_01_02.md").exists());
+        assert!(!month_dir.join("2024-01-02.md").exists());
+        assert_eq!(list_entry_dates(&custom).unwrap(), [date]);
+        fs::remove_dir_all(layout.root()).unwrap();
+    }
+
+    #[test]
+    fn plain_body_resembling_compressed_data_round_trips() {
+        let layout = temp_layout();
+        let body = "!zst:v1\nKLUv/QBYSQAAaGVsbG8=\n";
+        write(&layout, "2024-05-01", body, &WriteOptions::default());
+
+        let record = load_entry(&layout, "2024-05-01").unwrap().unwrap();
+        assert_eq!(record.body(), body);
+        fs::remove_dir_all(layout.root()).unwrap();
+    }
+
+    #[test]
+    fn compressed_body_is_flagged_in_frontmatter() {
+        let layout = temp_layout();
+        let options = WriteOptions {
+            body_encoding: BodyEncoding::Zstd,
+            ..WriteOptions::default()
+        };
+        write(&layout, "2024-05-02", "# Day\n\nhello", &options);
+
+        let date = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+        let raw = fs::read_to_string(entry_file_path(&layout, date)).unwrap();
+        assert!(raw.contains("bodyEncoding: zstd"));
+        assert!(!raw.contains("hello"));
+        let record = load_entry(&layout, "2024-05-02").unwrap().unwrap();
+        assert_eq!(record.body(), "# Day\n\nhello");
+
+        assert_eq!(
+            rewrite_entry_bodies(&layout, &WriteOptions::default()).unwrap(),
+            1
+        );
+        let raw = fs::read_to_string(entry_file_path(&layout, date)).unwrap();
+        assert!(!raw.contains("bodyEncoding"));
+        assert!(raw.ends_with("# Day\n\nhello"));
+        fs::remove_dir_all(layout.root()).unwrap();
+    }
+
+    #[test]
+    fn extra_roots_join_listings_and_refuse_deletes() {
+        let old = temp_layout();
+        write(&old, "2023-01-15", "old journal", &WriteOptions::default());
+        let broken = temp_layout();
+        let archive_path = month_archive_path(&broken, 2023, 1);
+        ensure_dir(archive_path.parent().unwrap()).unwrap();
+        fs::write(&archive_path, b"not a tarball").unwrap();
+
+        let roots = [old.root(), broken.root()].map(|root| root.display().to_string());
+        let layout = temp_layout().with_extra_roots(&roots);
+        write(
+            &layout,
+            "2024-02-01",
+            "new journal",
+            &WriteOptions::default(),
+        );
+
+        let dates = list_entry_dates(&layout).unwrap();
+        let old_date = NaiveDate::from_ymd_opt(2023, 1, 15).unwrap();
+        assert_eq!(
+            dates,
+            [old_date, NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()]
+        );
+        let january = load_month_entries(&layout, 2023, 1).unwrap();
+        assert_eq!(january.len(), 1);
+        assert_eq!(
+            list_populated_months(&layout).unwrap(),
+            [(2024, 2), (2023, 1)]
+        );
+
+        let err = delete_entry(&layout, old_date).unwrap_err();
+        assert!(err.contains("read-only root"), "{err}");
+        assert!(entry_exists(&old, old_date).unwrap());
+        for root in [layout.root(), old.root(), broken.root()] {
+            fs::remove_dir_all(root).unwrap();
+        }
+    }
+}
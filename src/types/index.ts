@@ -6,6 +6,12 @@ export interface DiaryEntry {
   emoji?: string; // 每日 Emoji
   aiSummary?: string; // AI 生成的摘要
   language?: string; // 创作语言
+  hash?: string; // 正文 HASH
+}
+
+/** `entry-metadata-updated` 事件负载：条目字段 + 触发本次摘要的保存所返回的 HASH */
+export interface EntryMetadataUpdated extends DiaryEntry {
+  previousHash: string;
 }
 
 /** 应用状态 */
@@ -64,6 +70,11 @@ export interface AiInvokePayload {
   greetingPrompt?: string | null;
 }
 
+export interface HeroGreetingResult {
+  greeting: string;
+  isLocal: boolean;
+}
+
 export interface HeroGreetingRequest {
   providerId: AiProviderId;
   userPrompt?: string | null;
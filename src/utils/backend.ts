@@ -3,6 +3,7 @@ import type {
   DiaryEntry as EntrySummary,
   AiInvokePayload,
   HeroGreetingRequest,
+  HeroGreetingResult,
 } from "../types";
 
 type Invoke = typeof import("@tauri-apps/api/core").invoke;
@@ -63,18 +64,22 @@ export async function saveEntryByDate(
   date: string,
   body: string,
   ai?: AiInvokePayload | null,
+  expectedHash?: string | null,
 ): Promise<EntrySummary> {
   return safeInvoke<EntrySummary>("save_entry_by_date", {
     date,
     body,
     ai: ai ?? null,
+    expectedHash: expectedHash ?? null,
   });
 }
 
 export async function invokeGenerateHeroGreeting(
   request: HeroGreetingRequest,
-): Promise<string> {
-  return safeInvoke<string>("invoke_generate_hero_greeting", { request });
+): Promise<HeroGreetingResult> {
+  return safeInvoke<HeroGreetingResult>("invoke_generate_hero_greeting", {
+    request,
+  });
 }
 
 export async function listAiModels(providerId: string): Promise<string[]> {
@@ -99,3 +104,11 @@ export async function deleteProviderApiKey(
 export async function hasProviderApiKey(providerId: string): Promise<boolean> {
   return safeInvoke<boolean>("has_api_secret", { providerId });
 }
+
+export async function getProviderApiKeySource(
+  providerId: string,
+): Promise<"stored" | "env" | null> {
+  return safeInvoke<"stored" | "env" | null>("api_secret_source", {
+    providerId,
+  });
+}
@@ -54,8 +54,10 @@ export async function generateHeroGreeting(
     timezone,
   });
 
-  const greeting = extractGreetingFromResponse(response);
+  const greeting = extractGreetingFromResponse(response.greeting);
   if (!greeting) return null;
+  // 本地回退的问候语不写入缓存，下次打开时仍会尝试 AI 生成。
+  if (response.isLocal) return greeting;
 
   persistGreeting(todayIso, locale, {
     signature,